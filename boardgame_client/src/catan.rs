@@ -1,32 +1,186 @@
-use bevy::asset::{AssetMetaCheck, LoadState};
+use bevy::asset::LoadState;
+use bevy::audio::Volume;
+use bevy::ecs::system::SystemParam;
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
-use bevy::window::WindowResolution;
-use bevy::DefaultPlugins;
+use bevy::render::{camera::ClearColorConfig, view::RenderLayers};
 use bevy_consumable_event::{
     ConsumableEventApp, ConsumableEventReader, ConsumableEventWriter,
 };
 use bevy_vector_shapes::prelude::*;
-#[cfg(target_family = "wasm")]
-use bevy_web_asset::WebAssetPlugin;
 use std::{
     collections::{HashMap, HashSet},
     f32::consts::PI,
     ops::Deref,
+    time::Duration,
 };
 
 use boardgame_common::{
-    catan::element::{
-        CatanCommon, DevCard, DevelopmentCard, GameAct, GameMsg, GameStart, PlayerCommon,
-        SelectRobber, TileKind, Trade, TradeRequest, TradeResponse, TradeTarget,
+    catan::{
+        element::{
+            Build, CatanCommon, DevCard, DevelopmentCard, GameAct, GameMsg, GameStart,
+            PlayerCommon, SelectRobber, TileKind, Trade, TradeRequest, TradeResponse,
+            TradeTarget,
+        },
+        state::checksum,
     },
     element::{Coordinate, Line},
-    network::{new_client, ClientMsg, NetworkClientEvent},
 };
 
-use crate::common::{CameraPlugin, NetworkClt, Platform, WindowResizePlugin};
+// inserted by the embedding app before the plugin, the defaults fit a native build
+#[derive(Resource, Debug, Clone)]
+pub struct CatanConfig {
+    // a directory or an url, every image path is relative to it
+    pub asset_root: String,
+    // the trade, card and drop boards are drawn above the board at this z
+    pub overlay_layer: f32,
+    // the smallest hit box a finger can reliably tap, in logical pixels
+    pub touch_size: f32,
+    // how far a pinch or the wheel can magnify the board
+    pub max_zoom: f32,
+    // the hud is drawn by a second camera on this render layer, so zooming and
+    // panning the board camera leaves it in place
+    pub hud_layer: u8,
+    // the turn, discard and trade cues, the app wide volume scales it further
+    pub cue_volume: f32,
+    // board events play this many times faster, zero applies them at once
+    pub animation_speed: f32,
+}
+
+impl Default for CatanConfig {
+    fn default() -> Self {
+        Self {
+            asset_root: "catan".to_string(),
+            overlay_layer: 2.0,
+            touch_size: 44.0,
+            max_zoom: 4.0,
+            hud_layer: 1,
+            cue_volume: 0.5,
+            animation_speed: 1.0,
+        }
+    }
+}
+
+impl CatanConfig {
+    fn asset(&self, path: &str) -> String {
+        format!("{}/{}", self.asset_root, path)
+    }
+
+    fn hud_layers(&self) -> Option<RenderLayers> {
+        Some(RenderLayers::layer(self.hud_layer))
+    }
+}
+
+// keys of the local player, the embedding app inserts its own to rebind them
+#[derive(Resource, Debug, Clone)]
+pub struct CatanKeys {
+    pub build_settlement: KeyCode,
+    pub build_city: KeyCode,
+    pub build_road: KeyCode,
+    pub trade: KeyCode,
+    pub buy_card: KeyCode,
+    pub use_card: KeyCode,
+    pub end_turn: KeyCode,
+    pub undo: KeyCode,
+    // leaves a build mode, a card or a trade, rejects a trade offered to us
+    pub cancel: KeyCode,
+    // clicks the focused spot
+    pub confirm: KeyCode,
+    pub focus_next: KeyCode,
+    pub focus_previous: KeyCode,
+    // picks the resource more and less change on the trade and drop boards,
+    // in the order wood, brick, grain, wool, stone
+    pub resources: [KeyCode; 5],
+    pub more: KeyCode,
+    pub less: KeyCode,
+    // held, more and less change what the trade asks for instead of the offer
+    pub want: KeyCode,
+}
+
+impl Default for CatanKeys {
+    fn default() -> Self {
+        Self {
+            build_settlement: KeyCode::KeyS,
+            build_city: KeyCode::KeyC,
+            build_road: KeyCode::KeyR,
+            trade: KeyCode::KeyT,
+            buy_card: KeyCode::KeyB,
+            use_card: KeyCode::KeyD,
+            end_turn: KeyCode::KeyE,
+            undo: KeyCode::KeyZ,
+            cancel: KeyCode::Escape,
+            confirm: KeyCode::Enter,
+            focus_next: KeyCode::ArrowRight,
+            focus_previous: KeyCode::ArrowLeft,
+            resources: [
+                KeyCode::Digit1,
+                KeyCode::Digit2,
+                KeyCode::Digit3,
+                KeyCode::Digit4,
+                KeyCode::Digit5,
+            ],
+            more: KeyCode::ArrowUp,
+            less: KeyCode::ArrowDown,
+            want: KeyCode::ShiftLeft,
+        }
+    }
+}
+
+impl CatanKeys {
+    fn operation(&self, operation: Operation) -> KeyCode {
+        match operation {
+            Operation::BuildSettlement => self.build_settlement,
+            Operation::BuildCity => self.build_city,
+            Operation::BuildRoad => self.build_road,
+            Operation::Trade => self.trade,
+            Operation::BuyCard => self.buy_card,
+            Operation::UseCard => self.use_card,
+            Operation::EndTurn => self.end_turn,
+            Operation::Undo => self.undo,
+        }
+    }
+}
+
+// fed by the embedding app, from a server, an in-process game or a replay
+#[derive(Event, Debug, Clone)]
+pub enum CatanInput {
+    // a game is about to start, the next message is its GameStart
+    Connected,
+    Disconnected,
+    Rejected(String),
+    Message(GameMsg),
+}
+
+// an action of the local player, the embedding app delivers it
+#[derive(Event, Debug, Clone)]
+pub struct CatanOutput(pub GameAct);
+
+// something the local player has to act on arrived, the plugin plays a cue and
+// shows a banner, the embedding app can surface it outside the window too
+#[derive(Event, Debug, Clone)]
+pub struct CatanAlert {
+    pub text: String,
+    cue: Cue,
+}
 
-const BOARD_LAYER: f32 = 1.0;
-const TRADEBPARD_LAYER: f32 = 2.0;
+#[derive(Debug, Clone, Copy)]
+enum Cue {
+    Turn,
+    Discard,
+    Trade,
+}
+
+impl Cue {
+    // plain tones, so there are no sound assets to load
+    fn pitch(&self) -> Pitch {
+        let (frequency, millis) = match self {
+            Cue::Turn => (880.0, 250),
+            Cue::Discard => (440.0, 400),
+            Cue::Trade => (660.0, 150),
+        };
+        Pitch::new(frequency, Duration::from_millis(millis))
+    }
+}
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
 enum CatanState {
@@ -64,14 +218,25 @@ struct Catan {
     points: Vec<Vec<Vec3>>,
     players: Vec<CatanPlayer>,
     radius: Option<f32>,
+    // the board moves without changing size when the window does
+    origin: Vec3,
     me: usize,
     current_turn: usize,
-    stealing_candidate: HashSet<usize>,
+    stealing_candidate: Vec<usize>,
     selected_yop: Option<TileKind>,
     road_building: Option<Line>,
+    init_settlement: Option<Coordinate>,
     used_card: bool,
     drop_cnt: usize,
+    discarding: Vec<usize>,
+    bank: [usize; TileKind::Max as usize],
     dice: (u8, u8),
+    names: Vec<String>,
+    // our builds this turn, newest last, the server takes them back in that order
+    undo: Vec<Build>,
+    // the current player's hand before each road they paid for this turn, a road
+    // building card announced after its two roads hands the cost back
+    road_hands: Vec<[usize; TileKind::Max as usize]>,
 }
 
 impl Catan {
@@ -113,6 +278,7 @@ impl Catan {
             tiles: draw_tiles,
             points: draw_points,
             radius: None,
+            origin: Vec3::ZERO,
             players: start
                 .players
                 .iter()
@@ -124,19 +290,33 @@ impl Catan {
             me: start.you,
             current_turn: 0,
             drop_cnt: 0,
-            stealing_candidate: HashSet::new(),
+            discarding: Vec::new(),
+            bank: start.bank,
+            stealing_candidate: Vec::new(),
             selected_yop: None,
             road_building: None,
+            init_settlement: None,
             used_card: false,
             dice: (1, 1),
+            names: start.names,
+            undo: Vec::new(),
+            road_hands: Vec::new(),
+        }
+    }
+
+    fn name(&self, player: usize) -> String {
+        match self.names.get(player) {
+            Some(name) => name.clone(),
+            None => format!("Player {}", player + 1),
         }
     }
 
     fn update_radius(&mut self, radius: f32, translate: Vec3) {
         match self.radius {
-            Some(r) if r == radius => {},
+            Some(r) if r == radius && self.origin == translate => {},
             _ => {
                 self.radius = Some(radius);
+                self.origin = translate;
                 let x_offset = radius;
                 let y_offset =
                     ((radius * radius - (radius / 2. * radius / 2.)) as f32).sqrt();
@@ -192,37 +372,289 @@ impl Catan {
     }
 }
 
+// where the board and each part of the hud go, in window coordinates, rebuilt
+// whenever the window changes size or orientation
+#[derive(Resource, Debug, Clone, Default)]
+struct Layout {
+    window: Vec2,
+    board: Rect,
+    bank: Rect,
+    history: Rect,
+    players: Rect,
+    hand: Rect,
+    menu: Rect,
+    // the card pickers and the trade, drop and steal boards are drawn above the rest
+    cards: Rect,
+    dialog: Rect,
+    touch: f32,
+}
+
+impl Layout {
+    fn new(width: f32, height: f32, touch: f32) -> Self {
+        let (left, top) = (-width / 2., height / 2.);
+        let short = width.min(height);
+        let mut row = (short * 0.1).max(touch);
+        let mut layout = Self {
+            window: Vec2::new(width, height),
+            touch,
+            ..default()
+        };
+
+        let hud_top = if width < height * 1.3 {
+            // portrait and square windows stack the hud rows under the board
+            let bank = (width / 7.).min(height * 0.05);
+            let board = (height - bank - row * 3.).min(width).max(short * 0.5);
+            // tall phones hand the height the board cannot use to the hud rows
+            row = (height - bank - board) / 3.;
+            layout.bank = Rect::new(left, top - bank, -left, top);
+            layout.board = Rect::new(left, top - bank - board, -left, top - bank);
+            layout.history = Rect::new(
+                -left - (width * 0.3).max(160.).min(width),
+                top - bank - board * 0.4,
+                -left,
+                top - bank,
+            );
+            layout.board.min.y
+        } else {
+            // landscape keeps the board square on the left, the hud in a column
+            let column = (width * 0.35).max(width - height);
+            let bank = (column / 7.).min(height * 0.06);
+            layout.board = Rect::new(left, -top, -left - column, top);
+            layout.bank = Rect::new(-left - column, top - bank, -left, top);
+            layout.history =
+                Rect::new(-left - column, -top + row * 3., -left, top - bank);
+            layout.history.min.y
+        };
+        let (hud_left, hud_right) = (layout.bank.min.x, layout.bank.max.x);
+        layout.players = Rect::new(hud_left, hud_top - row, hud_right, hud_top);
+        layout.hand = Rect::new(hud_left, hud_top - row * 2., hud_right, hud_top - row);
+        layout.menu = Rect::new(hud_left, -top, hud_right, hud_top - row * 2.);
+
+        let board = layout.board;
+        let card = (board.width() / 5.).min(board.height() * 0.2);
+        layout.cards =
+            Rect::from_center_size(board.center(), Vec2::new(board.width(), card));
+        layout.dialog = Rect::from_center_size(Vec2::ZERO, Vec2::splat(short * 0.9));
+        layout
+    }
+
+    // from a cursor or touch position, which start at the top left corner
+    fn centred(&self, position: Vec2) -> Vec2 {
+        Vec2::new(
+            position.x - self.window.x / 2.,
+            self.window.y / 2. - position.y,
+        )
+    }
+
+    // the side of the hexagon grid, it is centred in the board region
+    fn board_size(&self) -> f32 {
+        self.board.width().min(self.board.height())
+    }
+
+    // the i-th card of the card pickers, z is half its size
+    fn card(&self, i: usize) -> Vec3 {
+        let size = self.cards.height() * 0.9;
+        Vec3::new(
+            self.cards.min.x + size / 2. + size * i as f32,
+            self.cards.center().y,
+            size / 2.,
+        )
+    }
+
+    // the i-th player that can be robbed, z is half its size
+    fn steal_target(&self, i: usize) -> Vec3 {
+        let size = (self.dialog.width() / 7.).max(self.touch);
+        Vec3::new(
+            self.dialog.min.x + size + size * i as f32,
+            self.dialog.center().y,
+            size / 2.,
+        )
+    }
+
+    // the i-th resource of the hand, z is half its size
+    fn hand_slot(&self, i: usize) -> Vec3 {
+        let width = (self.hand.height() * 5.).min(self.hand.width());
+        let slot = width / 5.;
+        Vec3::new(
+            self.hand.center().x - width / 2. + slot * (i as f32 + 0.5),
+            self.hand.center().y,
+            (slot * 0.95).min(self.hand.height() * 0.95) / 2.,
+        )
+    }
+
+    // the i-th icon of the bank row, the bank itself first, z is half its size
+    fn bank_slot(&self, i: usize) -> Vec3 {
+        let icon = self.bank.height().min(self.bank.width() / 7.);
+        Vec3::new(
+            self.bank.max.x - icon * (6.5 - i as f32),
+            self.bank.center().y,
+            icon / 2.,
+        )
+    }
+
+    // half the side of a hit box, grown towards a finger but not into its neighbours
+    fn reach(&self, half: f32, spacing: f32) -> f32 {
+        half.max(self.touch / 2.).min(spacing / 2.)
+    }
+}
+
+fn update_layout(
+    windows: Query<&Window>, config: Res<CatanConfig>, mut layout: ResMut<Layout>,
+    mut panels: Query<&mut Style, With<HistoryPanel>>,
+    mut banners: Query<&mut Style, (With<AlertBanner>, Without<HistoryPanel>)>,
+) {
+    let Some(window) = windows.iter().next() else {
+        return;
+    };
+    if layout.window == Vec2::new(window.width(), window.height()) {
+        return;
+    }
+    *layout = Layout::new(window.width(), window.height(), config.touch_size);
+    for mut style in panels.iter_mut() {
+        history_style(&mut style, &layout);
+    }
+    for mut style in banners.iter_mut() {
+        alert_style(&mut style, &layout);
+    }
+}
+
+#[derive(Component)]
+struct HudCamera;
+
+// a touch that never moved far is a tap, anything else pans or pinches the board
+#[derive(Resource, Default)]
+struct Gesture {
+    moved: bool,
+    // where a finger was lifted this frame, in window coordinates
+    tap: Option<Vec2>,
+}
+
+// keeps the board point under the given window position in place
+fn zoom_view(camera: &mut Transform, at: Vec2, factor: f32, max_zoom: f32) {
+    let scale = (camera.scale.x / factor).clamp(1. / max_zoom, 1.);
+    let point = camera.translation.truncate() + at * camera.scale.x;
+    camera.translation = (point - at * scale).extend(camera.translation.z);
+    camera.scale = Vec3::new(scale, scale, 1.);
+}
+
+// the board region of the window only ever shows the board, so unzoomed the
+// camera is back where it started
+fn clamp_view(camera: &mut Transform, board: Rect) {
+    let room = 1. - camera.scale.x;
+    camera.translation.x = camera
+        .translation
+        .x
+        .clamp(board.min.x * room, board.max.x * room);
+    camera.translation.y = camera
+        .translation
+        .y
+        .clamp(board.min.y * room, board.max.y * room);
+}
+
+fn update_view(
+    touches: Res<Touches>, mut wheel: EventReader<MouseWheel>, windows: Query<&Window>,
+    panels: Query<&Interaction, With<HistoryPanel>>, layout: Res<Layout>,
+    config: Res<CatanConfig>, mut gesture: ResMut<Gesture>,
+    mut cameras: Query<&mut Transform, (With<Camera2d>, Without<HudCamera>)>,
+) {
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+    gesture.tap = None;
+
+    let fingers = touches.iter().collect::<Vec<_>>();
+    match fingers.as_slice() {
+        [] => {},
+        [finger] => {
+            if finger.distance().length() > config.touch_size / 2. {
+                gesture.moved = true;
+            }
+            // dragging over the hud does not move the board under it
+            if gesture.moved
+                && layout
+                    .board
+                    .contains(layout.centred(finger.start_position()))
+            {
+                let delta = layout.centred(finger.position())
+                    - layout.centred(finger.previous_position());
+                camera.translation -= (delta * camera.scale.x).extend(0.);
+            }
+        },
+        [first, second, ..] => {
+            gesture.moved = true;
+            let before = first
+                .previous_position()
+                .distance(second.previous_position());
+            let after = first.position().distance(second.position());
+            let middle = layout.centred((first.position() + second.position()) / 2.);
+            let previous = layout
+                .centred((first.previous_position() + second.previous_position()) / 2.);
+            camera.translation -= ((middle - previous) * camera.scale.x).extend(0.);
+            if before > 0. {
+                zoom_view(&mut camera, middle, after / before, config.max_zoom);
+            }
+        },
+    }
+    for finger in touches.iter_just_released() {
+        if !gesture.moved {
+            gesture.tap = Some(layout.centred(finger.position()));
+        }
+    }
+    if fingers.is_empty() {
+        gesture.moved = false;
+    }
+
+    let lines = wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 20.,
+        })
+        .sum::<f32>();
+    // over the history the wheel scrolls it instead
+    let cursor = windows
+        .iter()
+        .next()
+        .and_then(|window| window.cursor_position());
+    if let Some(cursor) = cursor {
+        let at = layout.centred(cursor);
+        if lines != 0.
+            && layout.board.contains(at)
+            && panels
+                .iter()
+                .all(|interaction| *interaction == Interaction::None)
+        {
+            zoom_view(&mut camera, at, 1.1f32.powf(lines), config.max_zoom);
+        }
+    }
+    clamp_view(&mut camera, layout.board);
+}
+
 fn check_build_city(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            for i in 0..catan.points.len() {
-                for j in 0..catan.points[i].len() {
-                    if x > catan.points[i][j].x - catan.radius.unwrap() * 0.2
-                        && x < catan.points[i][j].x + catan.radius.unwrap() * 0.2
-                        && y > catan.points[i][j].y - catan.radius.unwrap() * 0.2
-                        && y < catan.points[i][j].y + catan.radius.unwrap() * 0.2
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach = layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap());
+        for i in 0..catan.points.len() {
+            for j in 0..catan.points[i].len() {
+                if x > catan.points[i][j].x - reach
+                    && x < catan.points[i][j].x + reach
+                    && y > catan.points[i][j].y - reach
+                    && y < catan.points[i][j].y + reach
+                {
+                    let coordinate = Coordinate { x: i, y: j };
+                    let me = catan.me;
+                    if catan.inner.point_valid(coordinate)
+                        && catan.inner.point(coordinate).owner().is_some()
+                        && catan.inner.point(coordinate).owner().unwrap() == me
                     {
-                        let coordinate = Coordinate { x: i, y: j };
                         let me = catan.me;
-                        if catan.inner.point_valid(coordinate)
-                            && catan.inner.point(coordinate).owner().is_some()
-                            && catan.inner.point(coordinate).owner().unwrap() == me
-                        {
-                            let me = catan.me;
-                            catan.players[me].inner.resources
-                                [TileKind::Stone as usize] -= 3;
-                            catan.players[me].inner.resources
-                                [TileKind::Grain as usize] -= 2;
-                            action_writer.send(GameAct::BuildCity(coordinate).into());
-                            next_state.set(CatanState::Menu);
-                        }
+                        catan.players[me].inner.resources[TileKind::Stone as usize] -= 3;
+                        catan.players[me].inner.resources[TileKind::Grain as usize] -= 2;
+                        action_writer.send(GameAct::BuildCity(coordinate).into());
+                        next_state.set(CatanState::Menu);
                     }
                 }
             }
@@ -231,51 +663,40 @@ fn check_build_city(
 }
 
 fn check_build_settlement(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            for i in 0..catan.points.len() {
-                for j in 0..catan.points[i].len() {
-                    if x > catan.points[i][j].x - catan.radius.unwrap() * 0.2
-                        && x < catan.points[i][j].x + catan.radius.unwrap() * 0.2
-                        && y > catan.points[i][j].y - catan.radius.unwrap() * 0.2
-                        && y < catan.points[i][j].y + catan.radius.unwrap() * 0.2
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach = layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap());
+        for i in 0..catan.points.len() {
+            for j in 0..catan.points[i].len() {
+                if x > catan.points[i][j].x - reach
+                    && x < catan.points[i][j].x + reach
+                    && y > catan.points[i][j].y - reach
+                    && y < catan.points[i][j].y + reach
+                {
+                    let coordinate = Coordinate { x: i, y: j };
+                    let me = catan.me;
+                    if catan.inner.point_valid(coordinate)
+                        && catan.inner.point(coordinate).owner().is_none()
+                        && catan.players[me].inner.can_build_settlement()
+                        && catan.players[me].inner.have_roads_to(coordinate)
+                        && !catan.inner.point_get_points(coordinate).iter().any(|&p| {
+                            if let Some(p) = p {
+                                catan.inner.point(p).is_owned()
+                            } else {
+                                false
+                            }
+                        })
                     {
-                        let coordinate = Coordinate { x: i, y: j };
                         let me = catan.me;
-                        if catan.inner.point_valid(coordinate)
-                            && catan.inner.point(coordinate).owner().is_none()
-                            && catan.players[me].inner.can_build_settlement()
-                            && catan.players[me].inner.have_roads_to(coordinate)
-                            && !catan.inner.point_get_points(coordinate).iter().any(
-                                |&p| {
-                                    if let Some(p) = p {
-                                        catan.inner.point(p).is_owned()
-                                    } else {
-                                        false
-                                    }
-                                },
-                            )
-                        {
-                            let me = catan.me;
-                            catan.players[me].inner.resources[TileKind::Wood as usize] -=
-                                1;
-                            catan.players[me].inner.resources
-                                [TileKind::Grain as usize] -= 1;
-                            catan.players[me].inner.resources[TileKind::Wool as usize] -=
-                                1;
-                            catan.players[me].inner.resources
-                                [TileKind::Brick as usize] -= 1;
-                            action_writer
-                                .send(GameAct::BuildSettlement(coordinate).into());
-                            next_state.set(CatanState::Menu);
-                        }
+                        catan.players[me].inner.resources[TileKind::Wood as usize] -= 1;
+                        catan.players[me].inner.resources[TileKind::Grain as usize] -= 1;
+                        catan.players[me].inner.resources[TileKind::Wool as usize] -= 1;
+                        catan.players[me].inner.resources[TileKind::Brick as usize] -= 1;
+                        action_writer.send(GameAct::BuildSettlement(coordinate).into());
+                        next_state.set(CatanState::Menu);
                     }
                 }
             }
@@ -284,41 +705,35 @@ fn check_build_settlement(
 }
 
 fn check_init_settlement(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            for i in 0..catan.points.len() {
-                for j in 0..catan.points[i].len() {
-                    if x > catan.points[i][j].x - catan.radius.unwrap() * 0.2
-                        && x < catan.points[i][j].x + catan.radius.unwrap() * 0.2
-                        && y > catan.points[i][j].y - catan.radius.unwrap() * 0.2
-                        && y < catan.points[i][j].y + catan.radius.unwrap() * 0.2
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach = layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap());
+        for i in 0..catan.points.len() {
+            for j in 0..catan.points[i].len() {
+                if x > catan.points[i][j].x - reach
+                    && x < catan.points[i][j].x + reach
+                    && y > catan.points[i][j].y - reach
+                    && y < catan.points[i][j].y + reach
+                {
+                    let coordinate = Coordinate { x: i, y: j };
+                    let me = catan.me;
+                    if catan.inner.point_valid(coordinate)
+                        && catan.inner.point(coordinate).owner().is_none()
+                        && !catan.inner.point_get_points(coordinate).iter().any(|&p| {
+                            if let Some(p) = p {
+                                catan.inner.point(p).is_owned()
+                            } else {
+                                false
+                            }
+                        })
                     {
-                        let coordinate = Coordinate { x: i, y: j };
-                        let me = catan.me;
-                        if catan.inner.point_valid(coordinate)
-                            && catan.inner.point(coordinate).owner().is_none()
-                            && !catan.inner.point_get_points(coordinate).iter().any(
-                                |&p| {
-                                    if let Some(p) = p {
-                                        catan.inner.point(p).is_owned()
-                                    } else {
-                                        false
-                                    }
-                                },
-                            )
-                        {
-                            action_writer
-                                .send(GameAct::BuildSettlement(coordinate).into());
-                            catan.inner.add_settlement(me, coordinate);
-                            next_state.set(CatanState::InitRoad);
-                        }
+                        action_writer.send(GameAct::BuildSettlement(coordinate).into());
+                        catan.inner.add_settlement(me, coordinate);
+                        catan.init_settlement = Some(coordinate);
+                        next_state.set(CatanState::InitRoad);
                     }
                 }
             }
@@ -327,60 +742,50 @@ fn check_init_settlement(
 }
 
 fn check_init_road(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            for i in 0..catan.points.len() {
-                for j in 0..catan.points[i].len() {
-                    let point = Coordinate { x: i, y: j };
-                    if let Some(player) = catan.inner.point(point).owner() {
-                        if player == catan.me {
-                            for candidate in catan.inner.point_get_points(point) {
-                                if let Some(candidate) = candidate {
-                                    let road = Line::new(point, candidate);
-                                    if catan.inner.roads().get(&road).is_none() {
-                                        if x > (catan.points[i][j]
-                                            + catan.points[candidate.x][candidate.y])
-                                            .x
-                                            / 2.
-                                            - catan.radius.unwrap() * 0.2
-                                            && x < (catan.points[i][j]
-                                                + catan.points[candidate.x][candidate.y])
-                                                .x
-                                                / 2.
-                                                + catan.radius.unwrap() * 0.2
-                                            && y > (catan.points[i][j]
-                                                + catan.points[candidate.x][candidate.y])
-                                                .y
-                                                / 2.
-                                                - catan.radius.unwrap() * 0.2
-                                            && y < (catan.points[i][j]
-                                                + catan.points[candidate.x][candidate.y])
-                                                .y
-                                                / 2.
-                                                + catan.radius.unwrap() * 0.2
-                                        {
-                                            action_writer.send(
-                                                GameAct::BuildRoad(point, candidate)
-                                                    .into(),
-                                            );
-                                            let me = catan.me;
-                                            catan.inner.add_road(me, road);
-                                            catan.players[me].inner.add_road(road);
-                                            next_state.set(CatanState::Wait);
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach =
+            layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap() * 0.8);
+        // a setup road must touch the settlement that was just placed
+        let Some(point) = catan.init_settlement else {
+            return;
+        };
+        let (i, j) = (point.x, point.y);
+        for candidate in catan.inner.point_get_points(point) {
+            if let Some(candidate) = candidate {
+                let road = Line::new(point, candidate);
+                if catan.inner.roads().get(&road).is_none()
+                    && catan.inner.point_valid(candidate)
+                {
+                    if x > (catan.points[i][j] + catan.points[candidate.x][candidate.y]).x
+                        / 2.
+                        - reach
+                        && x < (catan.points[i][j]
+                            + catan.points[candidate.x][candidate.y])
+                            .x
+                            / 2.
+                            + reach
+                        && y > (catan.points[i][j]
+                            + catan.points[candidate.x][candidate.y])
+                            .y
+                            / 2.
+                            - reach
+                        && y < (catan.points[i][j]
+                            + catan.points[candidate.x][candidate.y])
+                            .y
+                            / 2.
+                            + reach
+                    {
+                        action_writer.send(GameAct::BuildRoad(point, candidate).into());
+                        let me = catan.me;
+                        catan.inner.add_road(me, road);
+                        catan.players[me].inner.add_road(road);
+                        catan.init_settlement = None;
+                        next_state.set(CatanState::Wait);
+                        break;
                     }
                 }
             }
@@ -391,173 +796,153 @@ fn check_init_road(
 fn draw_initable_roads(painter: &mut ShapePainter, catan: &ResMut<Catan>) {
     let config = painter.config().clone();
 
-    for i in 0..catan.points.len() {
-        for j in 0..catan.points[i].len() {
-            let point = Coordinate { x: i, y: j };
-            if let Some(player) = catan.inner.point(point).owner() {
-                if player == catan.me {
-                    for candidate in catan.inner.point_get_points(point) {
-                        if let Some(candidate) = candidate {
-                            let road = Line::new(point, candidate);
-                            if catan.inner.roads().get(&road).is_none() {
-                                painter.reset();
-                                painter.translate(
-                                    (catan.points[i][j]
-                                        + catan.points[candidate.x][candidate.y])
-                                        / 2.,
-                                );
-                                painter.color = Color::rgb(1.0, 1.0, 1.0);
-                                painter.circle(catan.radius.unwrap() * 0.2);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    for road in initable_roads(catan) {
+        painter.reset();
+        painter.translate(
+            (catan.points[road.start.x][road.start.y]
+                + catan.points[road.end.x][road.end.y])
+                / 2.,
+        );
+        painter.color = Color::rgb(1.0, 1.0, 1.0);
+        painter.circle(catan.radius.unwrap() * 0.2);
     }
     painter.set_config(config);
 }
 
 fn check_build_road(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let mut buildable_road = HashMap::new();
-
-            for (road, player) in catan.inner.roads() {
-                if *player == catan.me {
-                    for candidate in catan.inner.point_get_points(road.start) {
-                        if let Some(candidate) = candidate {
-                            let road = Line::new(road.start, candidate);
-                            if catan.inner.roads().get(&road).is_none() {
-                                buildable_road.insert(road, ());
-                            }
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach =
+            layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap() * 0.8);
+        let mut buildable_road = HashMap::new();
+
+        for (road, player) in catan.inner.roads() {
+            if *player == catan.me {
+                for candidate in catan.inner.point_get_points(road.start) {
+                    if let Some(candidate) = candidate {
+                        let road = Line::new(road.start, candidate);
+                        if catan.inner.roads().get(&road).is_none() {
+                            buildable_road.insert(road, ());
                         }
                     }
-                    for candidate in catan.inner.point_get_points(road.end) {
-                        if let Some(candidate) = candidate {
-                            let road = Line::new(road.end, candidate);
-                            if catan.inner.roads().get(&road).is_none() {
-                                buildable_road.insert(road, ());
-                            }
+                }
+                for candidate in catan.inner.point_get_points(road.end) {
+                    if let Some(candidate) = candidate {
+                        let road = Line::new(road.end, candidate);
+                        if catan.inner.roads().get(&road).is_none() {
+                            buildable_road.insert(road, ());
                         }
                     }
                 }
             }
+        }
 
-            for (road, _) in buildable_road {
-                if x > (catan.points[road.start.x][road.start.y]
+        for (road, _) in buildable_road {
+            if x > (catan.points[road.start.x][road.start.y]
+                + catan.points[road.end.x][road.end.y])
+                .x
+                / 2.
+                - reach
+                && x < (catan.points[road.start.x][road.start.y]
                     + catan.points[road.end.x][road.end.y])
                     .x
                     / 2.
-                    - catan.radius.unwrap() * 0.2
-                    && x < (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .x
-                        / 2.
-                        + catan.radius.unwrap() * 0.2
-                    && y > (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .y
-                        / 2.
-                        - catan.radius.unwrap() * 0.2
-                    && y < (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .y
-                        / 2.
-                        + catan.radius.unwrap() * 0.2
-                {
-                    let me = catan.me;
-                    catan.players[me].inner.resources[TileKind::Brick as usize] -= 1;
-                    catan.players[me].inner.resources[TileKind::Wood as usize] -= 1;
-                    action_writer.send(GameAct::BuildRoad(road.start, road.end).into());
-                    next_state.set(CatanState::Menu);
-                    break;
-                }
+                    + reach
+                && y > (catan.points[road.start.x][road.start.y]
+                    + catan.points[road.end.x][road.end.y])
+                    .y
+                    / 2.
+                    - reach
+                && y < (catan.points[road.start.x][road.start.y]
+                    + catan.points[road.end.x][road.end.y])
+                    .y
+                    / 2.
+                    + reach
+            {
+                let me = catan.me;
+                catan.players[me].inner.resources[TileKind::Brick as usize] -= 1;
+                catan.players[me].inner.resources[TileKind::Wood as usize] -= 1;
+                action_writer.send(GameAct::BuildRoad(road.start, road.end).into());
+                next_state.set(CatanState::Menu);
+                break;
             }
         }
     }
 }
 
 fn check_road_building_build_road(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, layout: Res<Layout>, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut next_card_state: ResMut<NextState<UseCardState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let mut buildable_road = HashMap::new();
-
-            for (road, player) in catan.inner.roads() {
-                if *player == catan.me {
-                    for candidate in catan.inner.point_get_points(road.start) {
-                        if let Some(candidate) = candidate {
-                            let road = Line::new(road.start, candidate);
-                            if catan.inner.roads().get(&road).is_none() {
-                                buildable_road.insert(road, ());
-                            }
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        let reach =
+            layout.reach(catan.radius.unwrap() * 0.2, catan.radius.unwrap() * 0.8);
+        let mut buildable_road = HashMap::new();
+
+        for (road, player) in catan.inner.roads() {
+            if *player == catan.me {
+                for candidate in catan.inner.point_get_points(road.start) {
+                    if let Some(candidate) = candidate {
+                        let road = Line::new(road.start, candidate);
+                        if catan.inner.roads().get(&road).is_none() {
+                            buildable_road.insert(road, ());
                         }
                     }
-                    for candidate in catan.inner.point_get_points(road.end) {
-                        if let Some(candidate) = candidate {
-                            let road = Line::new(road.end, candidate);
-                            if catan.inner.roads().get(&road).is_none() {
-                                buildable_road.insert(road, ());
-                            }
+                }
+                for candidate in catan.inner.point_get_points(road.end) {
+                    if let Some(candidate) = candidate {
+                        let road = Line::new(road.end, candidate);
+                        if catan.inner.roads().get(&road).is_none() {
+                            buildable_road.insert(road, ());
                         }
                     }
                 }
             }
+        }
 
-            for (road, _) in buildable_road {
-                if x > (catan.points[road.start.x][road.start.y]
+        for (road, _) in buildable_road {
+            if x > (catan.points[road.start.x][road.start.y]
+                + catan.points[road.end.x][road.end.y])
+                .x
+                / 2.
+                - reach
+                && x < (catan.points[road.start.x][road.start.y]
                     + catan.points[road.end.x][road.end.y])
                     .x
                     / 2.
-                    - catan.radius.unwrap() * 0.2
-                    && x < (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .x
-                        / 2.
-                        + catan.radius.unwrap() * 0.2
-                    && y > (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .y
-                        / 2.
-                        - catan.radius.unwrap() * 0.2
-                    && y < (catan.points[road.start.x][road.start.y]
-                        + catan.points[road.end.x][road.end.y])
-                        .y
-                        / 2.
-                        + catan.radius.unwrap() * 0.2
-                    && Some(road) != catan.road_building
-                {
-                    if catan.road_building.is_none() {
-                        catan.road_building = Some(road);
-                    } else {
-                        let road1 = catan.road_building.take().unwrap();
-                        action_writer.send(
-                            GameAct::UseDevelopmentCard((
-                                DevCard::RoadBuilding,
-                                DevelopmentCard::RoadBuilding([road1, road]),
-                            ))
-                            .into(),
-                        );
-                        next_card_state.set(UseCardState::SelectCard);
-                        next_state.set(CatanState::Menu);
-                    }
-                    break;
+                    + reach
+                && y > (catan.points[road.start.x][road.start.y]
+                    + catan.points[road.end.x][road.end.y])
+                    .y
+                    / 2.
+                    - reach
+                && y < (catan.points[road.start.x][road.start.y]
+                    + catan.points[road.end.x][road.end.y])
+                    .y
+                    / 2.
+                    + reach
+                && Some(road) != catan.road_building
+            {
+                if catan.road_building.is_none() {
+                    catan.road_building = Some(road);
+                } else {
+                    let road1 = catan.road_building.take().unwrap();
+                    action_writer.send(
+                        GameAct::UseDevelopmentCard((
+                            DevCard::RoadBuilding,
+                            DevelopmentCard::RoadBuilding([road1, road]),
+                        ))
+                        .into(),
+                    );
+                    next_card_state.set(UseCardState::SelectCard);
+                    next_state.set(CatanState::Menu);
                 }
+                break;
             }
         }
     }
@@ -565,64 +950,8 @@ fn check_road_building_build_road(
 
 fn draw_buildable_roads(painter: &mut ShapePainter, catan: &ResMut<Catan>) {
     let config = painter.config().clone();
-    let mut buildable_road = HashSet::new();
-
-    for (road, player) in catan.inner.roads() {
-        if *player == catan.me {
-            for candidate in catan.inner.point_get_points(road.start) {
-                if let Some(candidate) = candidate {
-                    let road = Line::new(road.start, candidate);
-                    if catan.inner.roads().get(&road).is_none()
-                        && catan.inner.point_valid(candidate)
-                        && Some(road) != catan.road_building
-                    {
-                        buildable_road.insert(road);
-                    }
-                }
-            }
-            for candidate in catan.inner.point_get_points(road.end) {
-                if let Some(candidate) = candidate {
-                    let road = Line::new(road.end, candidate);
-                    if catan.inner.roads().get(&road).is_none()
-                        && catan.inner.point_valid(candidate)
-                        && Some(road) != catan.road_building
-                    {
-                        buildable_road.insert(road);
-                    }
-                }
-            }
-        }
-    }
-
-    match catan.road_building {
-        Some(road) => {
-            for candidate in catan.inner.point_get_points(road.start) {
-                if let Some(candidate) = candidate {
-                    let road = Line::new(road.start, candidate);
-                    if catan.inner.roads().get(&road).is_none()
-                        && catan.inner.point_valid(candidate)
-                        && Some(road) != catan.road_building
-                    {
-                        buildable_road.insert(road);
-                    }
-                }
-            }
-            for candidate in catan.inner.point_get_points(road.end) {
-                if let Some(candidate) = candidate {
-                    let road = Line::new(road.end, candidate);
-                    if catan.inner.roads().get(&road).is_none()
-                        && catan.inner.point_valid(candidate)
-                        && Some(road) != catan.road_building
-                    {
-                        buildable_road.insert(road);
-                    }
-                }
-            }
-        },
-        None => {},
-    }
 
-    for road in buildable_road {
+    for road in buildable_roads(catan) {
         painter.reset();
         painter.translate(
             (catan.points[road.start.x][road.start.y]
@@ -712,107 +1041,66 @@ where
 }
 
 fn check_steal_target(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    catan: Res<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    layout: Res<Layout>, pointer: Pointer, catan: Res<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let offset = -windows.iter().next().unwrap().width() * 0.35;
-            let icon_size = windows.iter().next().unwrap().width() * 0.1;
-            for (i, player) in catan.stealing_candidate.iter().enumerate() {
-                if x > offset + icon_size * i as f32
-                    && x < offset + icon_size + icon_size * i as f32
-                    && y < icon_size / 2.
-                    && y > -icon_size / 2.
-                {
-                    action_writer.send(
-                        GameAct::SelectRobber((Some(*player), catan.inner.robber()))
-                            .into(),
-                    );
-                    next_state.set(CatanState::Menu);
-                    break;
-                }
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        for (i, player) in catan.stealing_candidate.iter().enumerate() {
+            let spot = layout.steal_target(i);
+            if x > spot.x - spot.z
+                && x < spot.x + spot.z
+                && y > spot.y - spot.z
+                && y < spot.y + spot.z
+            {
+                action_writer.send(GameAct::StealResource(*player).into());
+                next_state.set(CatanState::Wait);
+                break;
             }
         }
     }
 }
 
 fn draw_steal_target(
-    windows: Query<&Window>, mut painter: ShapePainter, catan: Res<Catan>,
-    img_store: Res<ImageStore>,
+    layout: Res<Layout>, mut painter: ShapePainter, catan: Res<Catan>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        painter.translate(Vec3::new(0., 0., TRADEBPARD_LAYER));
-        painter.rect(Vec2 {
-            x: window.width() * 0.7,
-            y: window.height() * 0.7,
-        });
-        let icon_size = window.width() * 0.1;
-        for (i, player) in catan.stealing_candidate.iter().enumerate() {
-            painter.reset();
-            painter.translate(Vec3::new(
-                -window.width() * 0.35 + icon_size / 2.0 + icon_size * i as f32,
-                0.0,
-                TRADEBPARD_LAYER + 0.1,
-            ));
-            painter.image(
-                img_store.settlement_img[*player].clone(),
-                Vec2::new(icon_size, icon_size),
-            );
-        }
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    painter.translate(layout.dialog.center().extend(config.overlay_layer));
+    painter.rect(layout.dialog.size());
+    for (i, player) in catan.stealing_candidate.iter().enumerate() {
+        let spot = layout.steal_target(i);
+        painter.reset();
+        painter.render_layers = config.hud_layers();
+        painter.translate(spot.truncate().extend(config.overlay_layer + 0.1));
+        painter.image(
+            img_store.settlement_img[*player].clone(),
+            Vec2::splat(spot.z * 2.),
+        );
     }
 }
 
 fn check_select_robber(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, catan: Res<Catan>, mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            for i in 0..catan.tiles.len() {
-                for j in 0..catan.tiles[i].len() {
-                    if x > catan.tiles[i][j].x - catan.radius.unwrap() * 0.9
-                        && x < catan.tiles[i][j].x + catan.radius.unwrap() * 0.9
-                        && y > catan.tiles[i][j].y - catan.radius.unwrap() * 0.9
-                        && y < catan.tiles[i][j].y + catan.radius.unwrap() * 0.9
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        for i in 0..catan.tiles.len() {
+            for j in 0..catan.tiles[i].len() {
+                if x > catan.tiles[i][j].x - catan.radius.unwrap() * 0.9
+                    && x < catan.tiles[i][j].x + catan.radius.unwrap() * 0.9
+                    && y > catan.tiles[i][j].y - catan.radius.unwrap() * 0.9
+                    && y < catan.tiles[i][j].y + catan.radius.unwrap() * 0.9
+                {
+                    let coordinate = Coordinate { x: i, y: j };
+                    if catan.inner.tile(coordinate).kind() != TileKind::Empty
+                        && catan.inner.tile(coordinate).kind() != TileKind::Dessert
+                        && catan.inner.robber() != coordinate
                     {
-                        let coordinate = Coordinate { x: i, y: j };
-                        if catan.inner.tile(coordinate).kind() != TileKind::Empty
-                            && catan.inner.tile(coordinate).kind() != TileKind::Dessert
-                            && catan.inner.robber() != coordinate
-                        {
-                            catan.inner.set_robber(coordinate);
-
-                            catan.stealing_candidate.clear();
-                            for point in catan.inner.tile_get_points(coordinate) {
-                                match catan.inner.point(point).owner() {
-                                    Some(owner) => {
-                                        if owner != catan.me {
-                                            catan.stealing_candidate.insert(owner);
-                                        }
-                                    },
-                                    None => continue,
-                                }
-                            }
-
-                            if !catan.stealing_candidate.is_empty() {
-                                next_state.set(CatanState::Stealing);
-                            } else {
-                                action_writer.send(
-                                    GameAct::SelectRobber((None, coordinate)).into(),
-                                );
-                                next_state.set(CatanState::Menu);
-                            }
-                        }
+                        // the server answers with the victims to steal from
+                        action_writer.send(GameAct::SelectRobber(coordinate).into());
+                        next_state.set(CatanState::Wait);
                     }
                 }
             }
@@ -820,99 +1108,37 @@ fn check_select_robber(
     }
 }
 
-fn check_knight_steal_target(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    catan: Res<Catan>, mut next_state: ResMut<NextState<CatanState>>,
-    mut next_card_state: ResMut<NextState<UseCardState>>,
-    mut action_writer: ConsumableEventWriter<GameAction>,
-) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            for (i, player) in catan.stealing_candidate.iter().enumerate() {
-                if x > -20.
-                    && x < 20.
-                    && y > 60. - 40. * i as f32 - 20.
-                    && y < 60. - 40. * i as f32 + 20.
-                {
-                    action_writer.send(
-                        GameAct::UseDevelopmentCard((
-                            DevCard::Knight,
-                            DevelopmentCard::Knight(SelectRobber {
-                                player: catan.me,
-                                target: Some(*player),
-                                coord: catan.inner.robber(),
-                            }),
-                        ))
-                        .into(),
-                    );
-                    next_card_state.set(UseCardState::SelectCard);
-                    next_state.set(CatanState::Menu);
-                    break;
-                }
-            }
-        }
-    }
-}
-
 fn check_knight_select_robber(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+    pointer: Pointer, catan: Res<Catan>, mut next_state: ResMut<NextState<CatanState>>,
     mut next_card_state: ResMut<NextState<UseCardState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            print!("check_knight_select_robber x: {} y: {}", x, y);
-            for i in 0..catan.tiles.len() {
-                for j in 0..catan.tiles[i].len() {
-                    if x > catan.tiles[i][j].x - catan.radius.unwrap() * 0.9
-                        && x < catan.tiles[i][j].x + catan.radius.unwrap() * 0.9
-                        && y > catan.tiles[i][j].y - catan.radius.unwrap() * 0.9
-                        && y < catan.tiles[i][j].y + catan.radius.unwrap() * 0.9
+    if let Some(Vec2 { x, y }) = pointer.board_click() {
+        print!("check_knight_select_robber x: {} y: {}", x, y);
+        for i in 0..catan.tiles.len() {
+            for j in 0..catan.tiles[i].len() {
+                if x > catan.tiles[i][j].x - catan.radius.unwrap() * 0.9
+                    && x < catan.tiles[i][j].x + catan.radius.unwrap() * 0.9
+                    && y > catan.tiles[i][j].y - catan.radius.unwrap() * 0.9
+                    && y < catan.tiles[i][j].y + catan.radius.unwrap() * 0.9
+                {
+                    let coordinate = Coordinate { x: i, y: j };
+                    if catan.inner.tile(coordinate).kind() != TileKind::Empty
+                        && catan.inner.tile(coordinate).kind() != TileKind::Dessert
+                        && catan.inner.robber() != coordinate
                     {
-                        let coordinate = Coordinate { x: i, y: j };
-                        if catan.inner.tile(coordinate).kind() != TileKind::Empty
-                            && catan.inner.tile(coordinate).kind() != TileKind::Dessert
-                            && catan.inner.robber() != coordinate
-                        {
-                            catan.inner.set_robber(coordinate);
-
-                            catan.stealing_candidate.clear();
-                            for point in catan.inner.tile_get_points(coordinate) {
-                                match catan.inner.point(point).owner() {
-                                    Some(owner) => {
-                                        if owner != catan.me {
-                                            catan.stealing_candidate.insert(owner);
-                                        }
-                                    },
-                                    None => continue,
-                                }
-                            }
-
-                            if !catan.stealing_candidate.is_empty() {
-                                next_card_state.set(UseCardState::KnightStealing);
-                            } else {
-                                action_writer.send(
-                                    GameAct::UseDevelopmentCard((
-                                        DevCard::Knight,
-                                        DevelopmentCard::Knight(SelectRobber {
-                                            player: catan.me,
-                                            target: None,
-                                            coord: coordinate,
-                                        }),
-                                    ))
-                                    .into(),
-                                );
-                                next_card_state.set(UseCardState::SelectCard);
-                                next_state.set(CatanState::Menu);
-                            }
-                        }
+                        action_writer.send(
+                            GameAct::UseDevelopmentCard((
+                                DevCard::Knight,
+                                DevelopmentCard::Knight(SelectRobber {
+                                    player: catan.me,
+                                    coord: coordinate,
+                                }),
+                            ))
+                            .into(),
+                        );
+                        next_card_state.set(UseCardState::SelectCard);
+                        next_state.set(CatanState::Wait);
                     }
                 }
             }
@@ -922,7 +1148,7 @@ fn check_knight_select_robber(
 
 fn draw_tiles(
     painter: &mut ShapePainter, catan: &ResMut<Catan>, img_store: &Res<ImageStore>,
-    state: &CatanState, card_state: &UseCardState,
+    state: &CatanState, card_state: &UseCardState, robber_moving: bool,
 ) {
     let config = painter.config().clone();
 
@@ -953,11 +1179,17 @@ fn draw_tiles(
                     _ => {},
                 }
                 if catan.inner.robber() == coord {
-                    painter.translate(Vec3::new(0., 0., 0.1));
-                    painter.image(
-                        img_store.robber_img.clone(),
-                        Vec2::new(catan.radius.unwrap() * 2., catan.radius.unwrap() * 2.),
-                    );
+                    // the animation draws it while it moves
+                    if !robber_moving {
+                        painter.translate(Vec3::new(0., 0., 0.1));
+                        painter.image(
+                            img_store.robber_img.clone(),
+                            Vec2::new(
+                                catan.radius.unwrap() * 2.,
+                                catan.radius.unwrap() * 2.,
+                            ),
+                        );
+                    }
                 } else if (state.eq(&CatanState::SelectRobber)
                     || card_state.eq(&UseCardState::Knight))
                     && catan.inner.tile(Coordinate { x: i, y: j }).kind()
@@ -1006,127 +1238,102 @@ fn draw_harbour(
 }
 
 fn draw_board(
-    mut painter: ShapePainter, mut catan: ResMut<Catan>, windows: Query<&Window>,
+    mut painter: ShapePainter, mut catan: ResMut<Catan>, layout: Res<Layout>,
     state: Res<State<CatanState>>, card_state: Res<State<UseCardState>>,
-    img_store: Res<ImageStore>,
+    img_store: Res<ImageStore>, history: Res<History>, animations: Res<Animations>,
 ) {
-    for window in windows.iter() {
-        info!(
-            "window width: {} height: {}",
-            window.width(),
-            window.height()
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let board_size = layout.board_size();
+    let board_translate = layout.board.center().extend(0.0);
+    let element_translate = Vec3 {
+        x: -board_size / 2.0,
+        y: -board_size / 2.0,
+        z: 0.0,
+    };
+    painter.translate(board_translate);
+    painter.with_children(|child_painter| {
+        child_painter.translate(element_translate);
+
+        let radius = board_size
+            / if catan.tiles.len() % 2 == 0 {
+                (catan.tiles.len() * 3 / 2) as f32 + 0.25
+            } else {
+                (catan.tiles.len() * 3 / 2) as f32 + 1.
+            }
+            .max(0.866 * 2. * catan.tiles[0].len() as f32);
+        catan.update_radius(radius, child_painter.transform.translation);
+        draw_tiles(
+            child_painter,
+            &catan,
+            &img_store,
+            state.get(),
+            card_state.get(),
+            animations.robber_moving(),
         );
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let board_size = window.width().min(window.height() * 0.7);
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: window.height() / 2. - board_size / 2.0,
-            z: 0.0,
-        };
-        let element_translate = Vec3 {
-            x: -board_size / 2.0,
-            y: -board_size / 2.0,
-            z: 0.0,
-        };
-        painter.translate(board_translate);
-        painter.with_children(|child_painter| {
-            child_painter.translate(element_translate);
+        draw_harbour(child_painter, &catan, &img_store);
+        draw_roads(child_painter, &catan, &img_store);
+        draw_points(child_painter, &catan, |point| {
+            if catan.inner.point_valid(point)
+                && catan.inner.point(point).owner().is_some()
+            {
+                if !catan.inner.point(point).is_city() {
+                    Some(PointDraw::Img(
+                        img_store.settlement_img
+                            [catan.inner.point(point).owner().unwrap()]
+                        .clone(),
+                    ))
+                } else {
+                    Some(PointDraw::Img(
+                        img_store.city_img[catan.inner.point(point).owner().unwrap()]
+                            .clone(),
+                    ))
+                }
+            } else {
+                None
+            }
+        });
 
-            let radius = board_size
-                / if catan.tiles.len() % 2 == 0 {
-                    (catan.tiles.len() * 3 / 2) as f32 + 0.25
+        if state.eq(&CatanState::BuidSettlement) {
+            draw_points(child_painter, &catan, |point| {
+                if settlement_spot(&catan, point)
+                    && catan.players[catan.me].inner.can_build_settlement()
+                    && catan.players[catan.me].inner.have_roads_to(point)
+                {
+                    Some(PointDraw::Circle(Color::WHITE))
                 } else {
-                    (catan.tiles.len() * 3 / 2) as f32 + 1.
-                }
-                .max(0.866 * 2. * catan.tiles[0].len() as f32);
-            catan.update_radius(radius, child_painter.transform.translation);
-            draw_tiles(
-                child_painter,
-                &catan,
-                &img_store,
-                state.get(),
-                card_state.get(),
-            );
-            draw_harbour(child_painter, &catan, &img_store);
-            draw_roads(child_painter, &catan, &img_store);
+                    None
+                }
+            });
+        } else if state.eq(&CatanState::BuildCity) {
             draw_points(child_painter, &catan, |point| {
-                if catan.inner.point_valid(point)
-                    && catan.inner.point(point).owner().is_some()
+                if city_spot(&catan, point)
+                    && catan.players[catan.me].inner.can_build_city()
                 {
-                    if !catan.inner.point(point).is_city() {
-                        Some(PointDraw::Img(
-                            img_store.settlement_img
-                                [catan.inner.point(point).owner().unwrap()]
-                            .clone(),
-                        ))
-                    } else {
-                        Some(PointDraw::Img(
-                            img_store.city_img[catan.inner.point(point).owner().unwrap()]
-                                .clone(),
-                        ))
-                    }
+                    Some(PointDraw::Circle(Color::WHITE))
+                } else {
+                    None
+                }
+            });
+        } else if state.eq(&CatanState::InitSettlement) {
+            draw_points(child_painter, &catan, |point| {
+                if settlement_spot(&catan, point) {
+                    Some(PointDraw::Circle(Color::WHITE))
                 } else {
                     None
                 }
             });
+        } else if state.eq(&CatanState::BuildRoad)
+            || card_state.eq(&UseCardState::RoadBuilding)
+        {
+            draw_buildable_roads(child_painter, &catan);
+        } else if state.eq(&CatanState::InitRoad) {
+            draw_initable_roads(child_painter, &catan);
+        }
 
-            if state.eq(&CatanState::BuidSettlement) {
-                draw_points(child_painter, &catan, |point| {
-                    if catan.inner.point_valid(point)
-                        && catan.inner.point(point).owner().is_none()
-                        && catan.players[catan.me].inner.can_build_settlement()
-                        && catan.players[catan.me].inner.have_roads_to(point)
-                        && !catan.inner.point_get_points(point).iter().any(|&p| {
-                            if let Some(p) = p {
-                                catan.inner.point(p).is_owned()
-                            } else {
-                                false
-                            }
-                        })
-                    {
-                        Some(PointDraw::Circle(Color::WHITE))
-                    } else {
-                        None
-                    }
-                });
-            } else if state.eq(&CatanState::BuildCity) {
-                draw_points(child_painter, &catan, |point| {
-                    if catan.inner.point(point).owner().is_some()
-                        && catan.inner.point(point).owner().unwrap() == catan.me
-                        && !catan.inner.point(point).is_city()
-                        && catan.players[catan.me].inner.can_build_city()
-                    {
-                        Some(PointDraw::Circle(Color::WHITE))
-                    } else {
-                        None
-                    }
-                });
-            } else if state.eq(&CatanState::InitSettlement) {
-                draw_points(child_painter, &catan, |point| {
-                    if catan.inner.point_valid(point)
-                        && catan.inner.point(point).owner().is_none()
-                        && !catan.inner.point_get_points(point).iter().any(|&p| {
-                            if let Some(p) = p {
-                                catan.inner.point(p).is_owned()
-                            } else {
-                                false
-                            }
-                        })
-                    {
-                        Some(PointDraw::Circle(Color::WHITE))
-                    } else {
-                        None
-                    }
-                });
-            } else if state.eq(&CatanState::BuildRoad)
-                || card_state.eq(&UseCardState::RoadBuilding)
-            {
-                draw_buildable_roads(child_painter, &catan);
-            } else if state.eq(&CatanState::InitRoad) {
-                draw_initable_roads(child_painter, &catan);
-            }
-        });
-    }
+        if let Some(highlight) = history.highlight() {
+            draw_highlight(child_painter, &catan, highlight);
+        }
+    });
 }
 
 #[derive(Component)]
@@ -1149,48 +1356,58 @@ fn intialize_game(mut next_state: ResMut<NextState<CatanLoadState>>) {
 }
 
 fn draw_player_board(
-    mut painter: ShapePainter, catan: Res<Catan>, windows: Query<&Window>,
-    img_store: Res<ImageStore>,
+    mut painter: ShapePainter, catan: Res<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>, animations: Res<Animations>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let player_card_y_size = window.height() * 0.1;
-        let player_card_x_size =
-            (window.width() - window.height() * 0.2) / catan.players.len() as f32;
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: -window.height() * 0.25,
-            z: 0.0,
-        };
+    let dice = animations.tumbling_dice().unwrap_or(catan.dice);
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let player_card_y_size = layout.players.height();
+    let board_translate = layout.players.center().extend(0.0);
+
+    painter.translate(board_translate);
+    painter.with_children(|child_painter| {
+        child_painter.translate(Vec3 {
+            x: -player_card_y_size / 2.,
+            y: 0.0,
+            z: 0.1,
+        });
+        child_painter.image(
+            img_store.dice_img[dice.0 as usize - 1].clone(),
+            Vec2 {
+                x: player_card_y_size,
+                y: player_card_y_size,
+            },
+        );
+        child_painter.translate(Vec3 {
+            x: player_card_y_size,
+            y: 0.0,
+            z: 0.1,
+        });
+        child_painter.image(
+            img_store.dice_img[dice.1 as usize - 1].clone(),
+            Vec2 {
+                x: player_card_y_size,
+                y: player_card_y_size,
+            },
+        );
 
-        painter.translate(board_translate);
-        painter.with_children(|child_painter| {
-            child_painter.translate(Vec3 {
-                x: -player_card_y_size / 2.,
-                y: 0.0,
-                z: 0.1,
-            });
-            child_painter.image(
-                img_store.dice_img[catan.dice.0 as usize - 1].clone(),
-                Vec2 {
-                    x: player_card_y_size,
-                    y: player_card_y_size,
-                },
-            );
+        // players who still have to drop resources after a seven
+        for player in catan.discarding.iter() {
             child_painter.translate(Vec3 {
-                x: player_card_y_size,
+                x: player_card_y_size * 0.75,
                 y: 0.0,
-                z: 0.1,
+                z: 0.0,
             });
             child_painter.image(
-                img_store.dice_img[catan.dice.1 as usize - 1].clone(),
+                img_store.settlement_img[*player].clone(),
                 Vec2 {
-                    x: player_card_y_size,
-                    y: player_card_y_size,
+                    x: player_card_y_size * 0.5,
+                    y: player_card_y_size * 0.5,
                 },
             );
-        });
-    }
+        }
+    });
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -1203,6 +1420,7 @@ enum Operation {
     BuyCard,
     UseCard,
     EndTurn,
+    Undo,
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -1213,477 +1431,582 @@ struct OperationEntry {
 }
 
 #[derive(Resource)]
-struct OperationMenu([OperationEntry; 7]);
+struct OperationMenu([OperationEntry; 8]);
 
 fn draw_menu(
-    mut painter: ShapePainter, windows: Query<&Window>, mut menu: ResMut<OperationMenu>,
-    img_store: Res<ImageStore>,
+    mut painter: ShapePainter, layout: Res<Layout>, mut menu: ResMut<OperationMenu>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let operation_board_x_size = window.width().min(window.height()) * 0.5;
-        let operation_board_y_size = window.width().min(window.height()) * 0.1;
-
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: -window.height() * 0.45,
-            z: 0.0,
-        };
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let operation_board_y_size = layout.menu.height();
+    let operation_board_x_size =
+        (operation_board_y_size * menu.0.len() as f32).min(layout.menu.width());
+
+    let board_translate = layout.menu.center().extend(0.0);
+
+    painter.translate(board_translate);
+    painter.with_children(|spawn_children| {
+        let config = spawn_children.config().clone();
+        let operation_size = operation_board_x_size / menu.0.len() as f32;
+        let xoffset = -operation_board_x_size / 2. + operation_size * 0.5;
+        for (i, oper) in menu.0.iter_mut().enumerate() {
+            let size = Vec2 {
+                x: operation_size * 0.95,
+                y: operation_size * 0.95,
+            };
+            spawn_children.set_config(config.clone());
+            spawn_children.translate(Vec3 {
+                x: xoffset + operation_size * i as f32,
+                y: 0.0,
+                z: 0.1,
+            });
+            spawn_children.color = Color::rgb(0.2, 0.5, 0.5);
+            spawn_children.rect(size);
+            spawn_children.translate(Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.1,
+            });
+            spawn_children.image(img_store.operation_img[&oper.operation].clone(), size);
+            oper.translate = spawn_children.transform.translation;
+            oper.size = size;
+        }
+    });
+}
 
-        painter.translate(board_translate);
-        painter.with_children(|spawn_children| {
-            let config = spawn_children.config().clone();
-            let operation_size = operation_board_x_size / menu.0.len() as f32;
-            let xoffset = -operation_board_x_size / 2. + operation_size * 0.5;
-            for (i, oper) in menu.0.iter_mut().enumerate() {
-                let size = Vec2 {
-                    x: operation_size * 0.95,
-                    y: operation_size * 0.95,
-                };
-                spawn_children.set_config(config.clone());
-                spawn_children.translate(Vec3 {
-                    x: xoffset + operation_size * i as f32,
-                    y: 0.0,
-                    z: 0.1,
-                });
-                spawn_children.color = Color::rgb(0.2, 0.5, 0.5);
-                spawn_children.rect(size);
-                spawn_children.translate(Vec3 {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.1,
-                });
-                spawn_children
-                    .image(img_store.operation_img[&oper.operation].clone(), size);
-                oper.translate = spawn_children.transform.translation;
-                oper.size = size;
-            }
-        });
+// the keyboard stands in for the mouse, it clicks on the spots of the open board
+#[derive(Resource)]
+struct Focus {
+    // in window coordinates, z is half the size of the spot
+    spots: Vec<Vec3>,
+    index: usize,
+    resource: TileKind,
+    // where the keyboard clicked this frame
+    click: Option<Vec2>,
+    // the spots are on the board, seen through the board camera, not on the hud
+    on_board: bool,
+}
+
+impl Default for Focus {
+    fn default() -> Self {
+        Self {
+            spots: Vec::new(),
+            index: 0,
+            resource: TileKind::Wood,
+            click: None,
+            on_board: false,
+        }
     }
 }
 
-fn limit_frame(mut settings: ResMut<bevy_framepace::FramepaceSettings>) {
-    settings.limiter = bevy_framepace::Limiter::from_framerate(10.0);
+#[derive(SystemParam)]
+struct Pointer<'w, 's> {
+    windows: Query<'w, 's, &'static Window>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    focus: Res<'w, Focus>,
+    gesture: Res<'w, Gesture>,
+    layout: Res<'w, Layout>,
+    cameras: Query<'w, 's, &'static Transform, (With<Camera2d>, Without<HudCamera>)>,
 }
 
-fn change_state(
-    keyboard_input: Res<ButtonInput<KeyCode>>, state: Res<State<CatanState>>,
-    mut next_state: ResMut<NextState<CatanState>>,
-) {
-    if keyboard_input.pressed(KeyCode::Space) {
-        match state.get() {
-            CatanState::Wait => {
-                next_state.set(CatanState::Menu);
-            },
-            CatanState::Menu => {
-                next_state.set(CatanState::BuidSettlement);
-            },
-            CatanState::BuidSettlement => {
-                next_state.set(CatanState::BuildCity);
-            },
-            CatanState::BuildCity => {
-                next_state.set(CatanState::BuildRoad);
-            },
-            CatanState::BuildRoad => {
-                next_state.set(CatanState::Trade);
-            },
-            CatanState::Trade => {
-                next_state.set(CatanState::SelectRobber);
-            },
-            CatanState::SelectRobber => {
-                next_state.set(CatanState::Wait);
-            },
-            _ => {},
+impl Pointer<'_, '_> {
+    // a left click, a tap or a keyboard click on the hud, in window coordinates
+    fn click(&self) -> Option<Vec2> {
+        if self.focus.click.is_some() {
+            return self.focus.click;
         }
-    };
-}
+        self.screen_click()
+    }
 
-fn check_menu_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    state: Res<State<CatanState>>, mut next_state: ResMut<NextState<CatanState>>,
-    mut next_trade_state: ResMut<NextState<TradeState>>,
-    mut next_card_state: ResMut<NextState<UseCardState>>, menu: ResMut<OperationMenu>,
-    catan: Res<Catan>, mut trade: ResMut<TradeBoard>,
-    mut action_writer: ConsumableEventWriter<GameAction>,
-) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            for i in 0..menu.0.len() {
-                let entry = &menu.0[i];
-                if x > entry.translate.x - entry.size.x / 2.
-                    && x < entry.translate.x + entry.size.x / 2.
-                    && y > entry.translate.y - entry.size.y / 2.
-                    && y < entry.translate.y + entry.size.y / 2.
-                {
-                    match entry.operation {
-                        Operation::BuildSettlement => {
-                            if catan.players[catan.me].inner.can_build_settlement() {
-                                if state.eq(&CatanState::BuidSettlement) {
-                                    next_state.set(CatanState::Menu);
-                                } else {
-                                    next_state.set(CatanState::BuidSettlement);
-                                }
-                            }
-                        },
-                        Operation::BuildCity => {
-                            if catan.players[catan.me].inner.can_build_city() {
-                                if state.eq(&CatanState::BuildCity) {
-                                    next_state.set(CatanState::Menu);
-                                } else {
-                                    next_state.set(CatanState::BuildCity);
-                                }
-                            }
-                        },
-                        Operation::BuildRoad => {
-                            if catan.players[catan.me].inner.can_build_road() {
-                                if state.eq(&CatanState::BuildRoad) {
-                                    next_state.set(CatanState::Menu);
-                                } else {
-                                    next_state.set(CatanState::BuildRoad);
-                                }
-                            }
-                        },
-                        Operation::Trade => {
-                            if catan.players[catan.me].inner.can_trade() {
-                                if state.eq(&CatanState::Trade) {
-                                    next_state.set(CatanState::Menu);
-                                } else {
-                                    next_state.set(CatanState::Trade);
-                                    trade.clear();
-                                    next_trade_state.set(TradeState::Offering);
-                                }
-                            }
-                        },
-                        Operation::BuyCard => {
-                            let me = catan.me;
-                            if catan.players[me].inner.can_buy_development_card() {
-                                action_writer.send(GameAct::BuyDevelopmentCard.into());
-                            }
-                        },
-                        Operation::UseCard => {
-                            if catan.players[catan.me].inner.can_use_development_card()
-                                && !catan.used_card
-                            {
-                                if state.eq(&CatanState::UseDevelopmentCard) {
-                                    next_state.set(CatanState::Menu);
-                                } else {
-                                    next_card_state.set(UseCardState::SelectCard);
-                                    next_state.set(CatanState::UseDevelopmentCard);
-                                }
-                            }
-                        },
-                        Operation::EndTurn => {
-                            next_state.set(CatanState::Wait);
-                            action_writer.send(GameAct::EndTurn.into());
-                        },
-                    }
-                }
-            }
+    // the same on the board, through the zoom and pan of the board camera
+    fn board_click(&self) -> Option<Vec2> {
+        if self.focus.click.is_some() {
+            return self.focus.click;
+        }
+        let click = self.screen_click()?;
+        if !self.layout.board.contains(click) {
+            return None;
+        }
+        let camera = self.cameras.get_single().ok()?;
+        Some(camera.translation.truncate() + click * camera.scale.x)
+    }
+
+    // a left click or a tap, in window coordinates
+    fn screen_click(&self) -> Option<Vec2> {
+        if self.gesture.tap.is_some() {
+            return self.gesture.tap;
         }
+        if !self.mouse.just_pressed(MouseButton::Left) {
+            return None;
+        }
+        let mouse = self.windows.iter().next()?.cursor_position()?;
+        Some(self.layout.centred(mouse))
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, States)]
-enum UseCardState {
-    #[default]
-    SelectCard,
-    Knight,
-    KnightStealing,
-    RoadBuilding,
-    YearOfPlenty,
-    Monopoly,
+fn settlement_spot(catan: &Catan, point: Coordinate) -> bool {
+    catan.inner.point_valid(point)
+        && catan.inner.point(point).owner().is_none()
+        && !catan
+            .inner
+            .point_get_points(point)
+            .iter()
+            .flatten()
+            .any(|p| catan.inner.point(*p).is_owned())
 }
 
-fn check_year_of_plenty_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
-    mut next_card_state: ResMut<NextState<UseCardState>>,
-    mut action_writer: ConsumableEventWriter<GameAction>, img_store: Res<ImageStore>,
-) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let card_board_x_size = windows.iter().next().unwrap().width();
-            let card_board_y_size = windows.iter().next().unwrap().height() * 0.2;
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            let mut i = 0;
-            for (kind, _) in img_store.resource_img.iter() {
-                if *kind != TileKind::Dessert && *kind != TileKind::Empty {
-                    if x > xoffset + card_size * i as f32 - card_size / 2.
-                        && x < xoffset + card_size * i as f32 + card_size / 2.
-                        && y > -card_board_y_size / 2.
-                        && y < card_board_y_size / 2.
-                    {
-                        if catan.selected_yop.is_none() {
-                            catan.selected_yop = Some(*kind);
-                        } else if catan.selected_yop.unwrap() != *kind {
-                            {
-                                action_writer.send(
-                                    GameAct::UseDevelopmentCard((
-                                        DevCard::YearOfPlenty,
-                                        DevelopmentCard::YearOfPlenty(
-                                            catan.selected_yop.take().unwrap(),
-                                            *kind,
-                                        ),
-                                    ))
-                                    .into(),
-                                );
-                                next_card_state.set(UseCardState::SelectCard);
-                                next_state.set(CatanState::Menu);
-                            }
-                        }
-                        break;
-                    }
-                    i += 1;
-                }
+fn city_spot(catan: &Catan, point: Coordinate) -> bool {
+    catan.inner.point_valid(point)
+        && catan.inner.point(point).owner() == Some(catan.me)
+        && !catan.inner.point(point).is_city()
+}
+
+fn robber_spot(catan: &Catan, tile: Coordinate) -> bool {
+    catan.inner.tile(tile).kind() != TileKind::Empty
+        && catan.inner.tile(tile).kind() != TileKind::Dessert
+        && catan.inner.robber() != tile
+}
+
+fn initable_roads(catan: &Catan) -> Vec<Line> {
+    let Some(point) = catan.init_settlement else {
+        return Vec::new();
+    };
+    catan
+        .inner
+        .point_get_points(point)
+        .into_iter()
+        .flatten()
+        .filter(|candidate| catan.inner.point_valid(*candidate))
+        .map(|candidate| Line::new(point, candidate))
+        .filter(|road| catan.inner.roads().get(road).is_none())
+        .collect()
+}
+
+fn buildable_roads(catan: &Catan) -> HashSet<Line> {
+    let mut ends = catan
+        .inner
+        .roads()
+        .iter()
+        .filter(|(_, player)| **player == catan.me)
+        .flat_map(|(road, _)| [road.start, road.end])
+        .collect::<Vec<_>>();
+    if let Some(road) = catan.road_building {
+        ends.extend([road.start, road.end]);
+    }
+
+    let mut roads = HashSet::new();
+    for end in ends {
+        for candidate in catan.inner.point_get_points(end).into_iter().flatten() {
+            let road = Line::new(end, candidate);
+            if catan.inner.roads().get(&road).is_none()
+                && catan.inner.point_valid(candidate)
+                && Some(road) != catan.road_building
+            {
+                roads.insert(road);
             }
         }
     }
+    roads
 }
 
-fn draw_year_of_plenty(
-    mut painter: ShapePainter, catan: Res<Catan>, windows: Query<&Window>,
-    img_store: Res<ImageStore>,
-) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let card_board_x_size = window.width();
-        let card_board_y_size = window.height() * 0.2;
+fn focus_spots(
+    catan: &Catan, layout: &Layout, state: &CatanState, card_state: &UseCardState,
+    trade_state: &TradeState, trade: &TradeBoard, drop_board: &DropBoard,
+) -> Vec<Vec3> {
+    let Some(radius) = catan.radius else {
+        return Vec::new();
+    };
+    let point = |point: Coordinate| {
+        catan.points[point.x][point.y]
+            .truncate()
+            .extend(radius * 0.2)
+    };
+    let road = |road: &Line| {
+        ((catan.points[road.start.x][road.start.y]
+            + catan.points[road.end.x][road.end.y])
+            / 2.)
+            .truncate()
+            .extend(radius * 0.2)
+    };
+    let points = || {
+        (0..catan.points.len())
+            .flat_map(|x| (0..catan.points[x].len()).map(move |y| Coordinate { x, y }))
+    };
+    let robber_spots = || -> Vec<Vec3> {
+        (0..catan.tiles.len())
+            .flat_map(|x| (0..catan.tiles[x].len()).map(move |y| Coordinate { x, y }))
+            .filter(|tile| robber_spot(catan, *tile))
+            .map(|tile| catan.tiles[tile.x][tile.y].truncate().extend(radius * 0.9))
+            .collect()
+    };
 
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: TRADEBPARD_LAYER,
-        };
+    let spots: Vec<Vec3> = match state {
+        CatanState::InitSettlement => points()
+            .filter(|p| settlement_spot(catan, *p))
+            .map(point)
+            .collect(),
+        CatanState::BuidSettlement => points()
+            .filter(|p| {
+                settlement_spot(catan, *p)
+                    && catan.players[catan.me].inner.can_build_settlement()
+                    && catan.players[catan.me].inner.have_roads_to(*p)
+            })
+            .map(point)
+            .collect(),
+        CatanState::BuildCity => points()
+            .filter(|p| {
+                city_spot(catan, *p) && catan.players[catan.me].inner.can_build_city()
+            })
+            .map(point)
+            .collect(),
+        CatanState::InitRoad => initable_roads(catan).iter().map(road).collect(),
+        CatanState::BuildRoad => {
+            let mut roads = buildable_roads(catan).into_iter().collect::<Vec<_>>();
+            roads.sort_by_key(|road| (road.start, road.end));
+            roads.iter().map(road).collect()
+        },
+        CatanState::SelectRobber => robber_spots(),
+        CatanState::Stealing => (0..catan.stealing_candidate.len())
+            .map(|i| layout.steal_target(i))
+            .collect(),
+        CatanState::Trade => match trade_state {
+            TradeState::Offering => vec![
+                trade.draw.player_yes,
+                trade.draw.bank_yes,
+                trade.draw.harbor_yes,
+                trade.draw.no,
+            ],
+            TradeState::Accepting => vec![trade.draw.player_yes, trade.draw.no],
+            TradeState::Confirming => {
+                let mut responses = trade.draw.response.iter().collect::<Vec<_>>();
+                responses.sort_by_key(|(player, _)| **player);
+                responses
+                    .into_iter()
+                    .map(|(_, yes)| yes.truncate().extend(trade.draw.icon_size / 2.))
+                    .collect()
+            },
+            _ => Vec::new(),
+        },
+        CatanState::DropResource => vec![drop_board.draw.yes],
+        CatanState::UseDevelopmentCard => match card_state {
+            UseCardState::SelectCard => catan.players[catan.me]
+                .inner
+                .cards
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(i, _)| layout.card(i))
+                .collect(),
+            UseCardState::Knight => robber_spots(),
+            UseCardState::RoadBuilding => {
+                let mut roads = buildable_roads(catan).into_iter().collect::<Vec<_>>();
+                roads.sort_by_key(|road| (road.start, road.end));
+                roads.iter().map(road).collect()
+            },
+            UseCardState::YearOfPlenty | UseCardState::Monopoly => {
+                (0..5).map(|i| layout.card(i)).collect()
+            },
+        },
+        _ => Vec::new(),
+    };
+    // boards that were not drawn yet have no size
+    spots
+        .into_iter()
+        .filter(|spot: &Vec3| spot.z > 0.)
+        .collect()
+}
 
-        painter.translate(board_translate);
-        painter.with_children(|spawn_children| {
-            let config = spawn_children.config().clone();
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            let mut i = 0;
-            for (kind, img) in img_store.resource_img.iter() {
-                if *kind != TileKind::Dessert && *kind != TileKind::Empty {
-                    let size = Vec2 {
-                        x: card_size,
-                        y: card_size,
-                    };
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: xoffset + card_size * i as f32,
-                        y: 0.0,
-                        z: 0.1,
-                    });
-                    if catan.selected_yop.is_some()
-                        && catan.selected_yop.unwrap() == *kind
-                    {
-                        spawn_children.color = Color::rgb(1.0, 1.0, 1.0);
-                        spawn_children.rect(Vec2 {
-                            x: card_size,
-                            y: card_size,
-                        });
-                        spawn_children.translate(Vec3 {
-                            x: 0.0,
-                            y: 0.0,
-                            z: 0.1,
-                        });
-                    }
-                    spawn_children.image(img.clone(), size);
-                    spawn_children.translate(Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.1,
-                    });
-                    spawn_children.image(
-                        img_store.number_img
-                            [catan.players[catan.me].inner.resources[*kind as usize]]
-                            .clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    i += 1;
-                }
-            }
-        });
+fn update_focus(
+    keys: Res<ButtonInput<KeyCode>>, bindings: Res<CatanKeys>, layout: Res<Layout>,
+    state: Res<State<CatanState>>, card_state: Res<State<UseCardState>>,
+    trade_state: Res<State<TradeState>>, catan: Res<Catan>, trade: Res<TradeBoard>,
+    drop_board: Res<DropBoard>, mut focus: ResMut<Focus>,
+) {
+    focus.spots = focus_spots(
+        &catan,
+        &layout,
+        state.get(),
+        card_state.get(),
+        trade_state.get(),
+        &trade,
+        &drop_board,
+    );
+    focus.on_board = match state.get() {
+        CatanState::UseDevelopmentCard => matches!(
+            card_state.get(),
+            UseCardState::Knight | UseCardState::RoadBuilding
+        ),
+        CatanState::Trade | CatanState::DropResource | CatanState::Stealing => false,
+        _ => true,
+    };
+    focus.click = None;
+    if focus.spots.is_empty() {
+        focus.index = 0;
+        return;
+    }
+
+    let count = focus.spots.len();
+    if keys.just_pressed(bindings.focus_next) {
+        focus.index += 1;
+    }
+    if keys.just_pressed(bindings.focus_previous) {
+        focus.index += count - 1;
+    }
+    focus.index %= count;
+    if keys.just_pressed(bindings.confirm) {
+        focus.click = Some(focus.spots[focus.index].truncate());
+    }
+
+    for (i, key) in bindings.resources.iter().enumerate() {
+        if keys.just_pressed(*key) {
+            focus.resource = TileKind::try_from(TileKind::Wood as u8 + i as u8).unwrap();
+        }
+    }
+    // more and less click the buttons of the picked resource
+    let buttons = match (state.get(), trade_state.get()) {
+        (CatanState::Trade, TradeState::Offering) if keys.pressed(bindings.want) => {
+            trade.draw.want.get(&focus.resource)
+        },
+        (CatanState::Trade, TradeState::Offering) => {
+            trade.draw.offer.get(&focus.resource)
+        },
+        (CatanState::DropResource, _) => drop_board.draw.drop.get(&focus.resource),
+        _ => None,
+    };
+    if let Some((add, sub)) = buttons {
+        if keys.just_pressed(bindings.more) {
+            focus.click = Some(add.truncate());
+        } else if keys.just_pressed(bindings.less) {
+            focus.click = Some(sub.truncate());
+        }
     }
 }
 
-fn check_monopoly_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
+fn draw_focus(mut painter: ShapePainter, focus: Res<Focus>, config: Res<CatanConfig>) {
+    if let Some(spot) = focus.spots.get(focus.index) {
+        if !focus.on_board {
+            painter.render_layers = config.hud_layers();
+        }
+        painter.translate(spot.truncate().extend(config.overlay_layer + 0.5));
+        painter.hollow = true;
+        painter.thickness = 3.;
+        painter.color = Color::YELLOW;
+        painter.circle(spot.z * 1.2);
+    }
+}
+
+fn check_cancel(
+    keys: Res<ButtonInput<KeyCode>>, bindings: Res<CatanKeys>,
+    state: Res<State<CatanState>>, card_state: Res<State<UseCardState>>,
+    trade_state: Res<State<TradeState>>, mut catan: ResMut<Catan>,
     mut next_state: ResMut<NextState<CatanState>>,
+    mut next_trade_state: ResMut<NextState<TradeState>>,
     mut next_card_state: ResMut<NextState<UseCardState>>,
-    mut action_writer: ConsumableEventWriter<GameAction>, img_store: Res<ImageStore>,
+    mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let card_board_x_size = windows.iter().next().unwrap().width();
-            let card_board_y_size = windows.iter().next().unwrap().height() * 0.2;
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            let mut i = 0;
-            for (kind, _) in img_store.resource_img.iter() {
-                if *kind != TileKind::Dessert && *kind != TileKind::Empty {
-                    if x > xoffset + card_size * i as f32 - card_size / 2.
-                        && x < xoffset + card_size * i as f32 + card_size / 2.
-                        && y > -card_board_y_size / 2.
-                        && y < card_board_y_size / 2.
-                    {
-                        action_writer.send(
-                            GameAct::UseDevelopmentCard((
-                                DevCard::Monopoly,
-                                DevelopmentCard::Monopoly(*kind),
-                            ))
-                            .into(),
-                        );
-                        next_card_state.set(UseCardState::SelectCard);
-                        next_state.set(CatanState::Menu);
-                        break;
-                    }
-                    i += 1;
-                }
+    if !keys.just_pressed(bindings.cancel) {
+        return;
+    }
+    match state.get() {
+        CatanState::BuidSettlement | CatanState::BuildCity | CatanState::BuildRoad => {
+            next_state.set(CatanState::Menu);
+        },
+        CatanState::UseDevelopmentCard => {
+            // a picked card was not played yet
+            if *card_state.get() != UseCardState::SelectCard {
+                catan.used_card = false;
+                catan.road_building = None;
+                catan.selected_yop = None;
+                next_card_state.set(UseCardState::SelectCard);
             }
-        }
+            next_state.set(CatanState::Menu);
+        },
+        CatanState::Trade => match trade_state.get() {
+            TradeState::Offering => next_state.set(CatanState::Menu),
+            TradeState::Accepting => {
+                action_writer.send(GameAct::TradeResponse(TradeResponse::Reject).into());
+                next_trade_state.set(TradeState::WaitingConfirm);
+            },
+            TradeState::Confirming => {
+                action_writer.send(GameAct::TradeConfirm(None).into());
+                next_trade_state.set(TradeState::WaitingConfirm);
+            },
+            _ => {},
+        },
+        _ => {},
     }
 }
 
-fn draw_monopoly(
-    mut painter: ShapePainter, catan: ResMut<Catan>, windows: Query<&Window>,
-    img_store: Res<ImageStore>,
+fn check_menu_click(
+    pointer: Pointer, keys: Res<ButtonInput<KeyCode>>, bindings: Res<CatanKeys>,
+    state: Res<State<CatanState>>, mut next_state: ResMut<NextState<CatanState>>,
+    mut next_trade_state: ResMut<NextState<TradeState>>,
+    mut next_card_state: ResMut<NextState<UseCardState>>, menu: ResMut<OperationMenu>,
+    catan: Res<Catan>, mut trade: ResMut<TradeBoard>,
+    mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let card_board_x_size = window.width();
-        let card_board_y_size = window.height() * 0.2;
-
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: TRADEBPARD_LAYER,
-        };
+    let mut selected = menu
+        .0
+        .iter()
+        .map(|entry| entry.operation)
+        .find(|operation| keys.just_pressed(bindings.operation(*operation)));
+    // the keyboard picks operations by key, its focus is on the board
+    if let Some(Vec2 { x, y }) = pointer.screen_click() {
+        for entry in menu.0.iter() {
+            if x > entry.translate.x - entry.size.x / 2.
+                && x < entry.translate.x + entry.size.x / 2.
+                && y > entry.translate.y - entry.size.y / 2.
+                && y < entry.translate.y + entry.size.y / 2.
+            {
+                selected = Some(entry.operation);
+            }
+        }
+    }
 
-        painter.translate(board_translate);
-        painter.with_children(|spawn_children| {
-            let config = spawn_children.config().clone();
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            let mut i = 0;
-            for (kind, img) in img_store.resource_img.iter() {
-                if *kind != TileKind::Dessert && *kind != TileKind::Empty {
-                    let size = Vec2 {
-                        x: card_size,
-                        y: card_size,
-                    };
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: xoffset + card_size * i as f32,
-                        y: 0.0,
-                        z: 0.1,
-                    });
-                    spawn_children.image(img.clone(), size);
-                    spawn_children.translate(Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.1,
-                    });
-                    spawn_children.image(
-                        img_store.number_img
-                            [catan.players[catan.me].inner.resources[*kind as usize]]
-                            .clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    i += 1;
+    if let Some(operation) = selected {
+        match operation {
+            Operation::BuildSettlement => {
+                if catan.players[catan.me].inner.can_build_settlement() {
+                    if state.eq(&CatanState::BuidSettlement) {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        next_state.set(CatanState::BuidSettlement);
+                    }
                 }
-            }
-        });
+            },
+            Operation::BuildCity => {
+                if catan.players[catan.me].inner.can_build_city() {
+                    if state.eq(&CatanState::BuildCity) {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        next_state.set(CatanState::BuildCity);
+                    }
+                }
+            },
+            Operation::BuildRoad => {
+                if catan.players[catan.me].inner.can_build_road() {
+                    if state.eq(&CatanState::BuildRoad) {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        next_state.set(CatanState::BuildRoad);
+                    }
+                }
+            },
+            Operation::Trade => {
+                if catan.players[catan.me].inner.can_trade() {
+                    if state.eq(&CatanState::Trade) {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        next_state.set(CatanState::Trade);
+                        trade.clear();
+                        next_trade_state.set(TradeState::Offering);
+                    }
+                }
+            },
+            Operation::BuyCard => {
+                let me = catan.me;
+                if catan.players[me].inner.can_buy_development_card() {
+                    action_writer.send(GameAct::BuyDevelopmentCard.into());
+                }
+            },
+            Operation::UseCard => {
+                if catan.players[catan.me].inner.can_use_development_card()
+                    && !catan.used_card
+                {
+                    if state.eq(&CatanState::UseDevelopmentCard) {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        next_card_state.set(UseCardState::SelectCard);
+                        next_state.set(CatanState::UseDevelopmentCard);
+                    }
+                }
+            },
+            Operation::EndTurn => {
+                next_state.set(CatanState::Wait);
+                action_writer.send(GameAct::EndTurn.into());
+            },
+            Operation::Undo => {
+                if !catan.undo.is_empty() {
+                    next_state.set(CatanState::Menu);
+                    action_writer.send(GameAct::Undo.into());
+                }
+            },
+        }
     }
 }
 
-fn check_development_card_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut catan: ResMut<Catan>, mut next_state: ResMut<NextState<CatanState>>,
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, States)]
+enum UseCardState {
+    #[default]
+    SelectCard,
+    Knight,
+    RoadBuilding,
+    YearOfPlenty,
+    Monopoly,
+}
+
+fn check_year_of_plenty_click(
+    layout: Res<Layout>, pointer: Pointer, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
     mut next_card_state: ResMut<NextState<UseCardState>>,
-    mut action_writer: ConsumableEventWriter<GameAction>,
+    mut action_writer: ConsumableEventWriter<GameAction>, img_store: Res<ImageStore>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-            let card_board_x_size = windows.iter().next().unwrap().width();
-            let card_board_y_size = windows.iter().next().unwrap().height() * 0.2;
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            for i in 0..catan.players[catan.me].inner.cards.len() {
-                if x > xoffset + card_size * i as f32 - card_size / 2.
-                    && x < xoffset + card_size * i as f32 + card_size / 2.
-                    && y > -card_board_y_size / 2.
-                    && y < card_board_y_size / 2.
-                    && catan.players[catan.me].inner.cards[i] > 0
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        let mut i = 0;
+        for (kind, _) in img_store.resource_img.iter() {
+            if *kind != TileKind::Dessert && *kind != TileKind::Empty {
+                let card = layout.card(i);
+                if x > card.x - card.z
+                    && x < card.x + card.z
+                    && y > card.y - card.z
+                    && y < card.y + card.z
                 {
-                    if i == DevCard::Knight as usize {
-                        next_card_state.set(UseCardState::Knight);
-                        catan.used_card = true;
-                        break;
-                    } else if i == DevCard::RoadBuilding as usize {
-                        next_card_state.set(UseCardState::RoadBuilding);
-                        catan.used_card = true;
-                        break;
-                    } else if i == DevCard::YearOfPlenty as usize {
-                        next_card_state.set(UseCardState::YearOfPlenty);
-                        catan.used_card = true;
-                        break;
-                    } else if i == DevCard::Monopoly as usize {
-                        next_card_state.set(UseCardState::Monopoly);
-                        catan.used_card = true;
-                        break;
-                    } else if i == DevCard::VictoryPoint as usize {
-                        action_writer.send(
-                            GameAct::UseDevelopmentCard((
-                                DevCard::VictoryPoint,
-                                DevelopmentCard::VictoryPoint,
-                            ))
-                            .into(),
-                        );
-                        next_state.set(CatanState::Menu);
-                        catan.used_card = true;
-                        break;
+                    if catan.selected_yop.is_none() {
+                        catan.selected_yop = Some(*kind);
+                    } else if catan.selected_yop.unwrap() != *kind {
+                        {
+                            action_writer.send(
+                                GameAct::UseDevelopmentCard((
+                                    DevCard::YearOfPlenty,
+                                    DevelopmentCard::YearOfPlenty(
+                                        catan.selected_yop.take().unwrap(),
+                                        *kind,
+                                    ),
+                                ))
+                                .into(),
+                            );
+                            next_card_state.set(UseCardState::SelectCard);
+                            next_state.set(CatanState::Menu);
+                        }
                     }
+                    break;
                 }
+                i += 1;
             }
         }
     }
 }
 
-fn draw_development_card(
-    mut painter: ShapePainter, catan: ResMut<Catan>, windows: Query<&Window>,
-    img_store: Res<ImageStore>,
+fn draw_year_of_plenty(
+    mut painter: ShapePainter, catan: Res<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let card_board_x_size = window.width();
-        let card_board_y_size = window.height() * 0.2;
-
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: TRADEBPARD_LAYER,
-        };
-
-        painter.translate(board_translate);
-        painter.with_children(|spawn_children| {
-            let config = spawn_children.config().clone();
-            let card_size = card_board_y_size * 0.9;
-            let xoffset = -card_board_x_size / 2. + card_size / 2.;
-            for (i, card) in img_store.card_img.iter().enumerate() {
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let card_board_x_size = layout.cards.width();
+    let card_board_y_size = layout.cards.height();
+
+    let board_translate = layout.cards.center().extend(config.overlay_layer);
+
+    painter.translate(board_translate);
+    painter.with_children(|spawn_children| {
+        let config = spawn_children.config().clone();
+        let card_size = card_board_y_size * 0.9;
+        let xoffset = -card_board_x_size / 2. + card_size / 2.;
+        let mut i = 0;
+        for (kind, img) in img_store.resource_img.iter() {
+            if *kind != TileKind::Dessert && *kind != TileKind::Empty {
                 let size = Vec2 {
                     x: card_size,
                     y: card_size,
@@ -1694,62 +2017,98 @@ fn draw_development_card(
                     y: 0.0,
                     z: 0.1,
                 });
-                spawn_children.image(card.clone(), size);
+                if catan.selected_yop.is_some() && catan.selected_yop.unwrap() == *kind {
+                    spawn_children.color = Color::rgb(1.0, 1.0, 1.0);
+                    spawn_children.rect(Vec2 {
+                        x: card_size,
+                        y: card_size,
+                    });
+                    spawn_children.translate(Vec3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 0.1,
+                    });
+                }
+                spawn_children.image(img.clone(), size);
                 spawn_children.translate(Vec3 {
                     x: 0.0,
                     y: 0.0,
                     z: 0.1,
                 });
                 spawn_children.image(
-                    img_store.number_img[catan.players[catan.me].inner.cards[i]].clone(),
+                    img_store.number_img
+                        [catan.players[catan.me].inner.resources[*kind as usize]]
+                        .clone(),
                     Vec2::new(size.x * 0.5, size.y * 0.5),
                 );
+                i += 1;
             }
-        });
-    }
+        }
+    });
 }
 
-fn draw_resource(
-    mut painter: ShapePainter, catan: ResMut<Catan>, windows: Query<&Window>,
-    img_store: Res<ImageStore>,
+fn check_monopoly_click(
+    layout: Res<Layout>, pointer: Pointer, mut next_state: ResMut<NextState<CatanState>>,
+    mut next_card_state: ResMut<NextState<UseCardState>>,
+    mut action_writer: ConsumableEventWriter<GameAction>, img_store: Res<ImageStore>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let operation_board_x_size = window.width().min(window.height()) * 0.5;
-        let operation_board_y_size = window.width().min(window.height()) * 0.1;
-
-        let board_translate = Vec3 {
-            x: 0.0,
-            y: -window.height() * 0.35,
-            z: 0.0,
-        };
-
-        painter.translate(board_translate);
-        painter.with_children(|spawn_children| {
-            let config = spawn_children.config().clone();
-            let operation_size = operation_board_x_size
-                / img_store
-                    .resource_img
-                    .iter()
-                    .filter(|res| res.0.is_resource())
-                    .count() as f32;
-            let size = (operation_size * 0.95).min(operation_board_y_size * 0.95);
-            let xoffset = -operation_board_x_size * 0.5 + operation_size * 0.5;
-            let mut i = 0;
-            for j in 0..catan.players[catan.me].inner.resources.len() {
-                let kind = &TileKind::try_from(j as u8).unwrap();
-                if !kind.is_resource() {
-                    continue;
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        let mut i = 0;
+        for (kind, _) in img_store.resource_img.iter() {
+            if *kind != TileKind::Dessert && *kind != TileKind::Empty {
+                let card = layout.card(i);
+                if x > card.x - card.z
+                    && x < card.x + card.z
+                    && y > card.y - card.z
+                    && y < card.y + card.z
+                {
+                    action_writer.send(
+                        GameAct::UseDevelopmentCard((
+                            DevCard::Monopoly,
+                            DevelopmentCard::Monopoly(*kind),
+                        ))
+                        .into(),
+                    );
+                    next_card_state.set(UseCardState::SelectCard);
+                    next_state.set(CatanState::Menu);
+                    break;
                 }
-                let res = img_store.resource_img.get(kind).unwrap();
-                let size = Vec2 { x: size, y: size };
+                i += 1;
+            }
+        }
+    }
+}
+
+fn draw_monopoly(
+    mut painter: ShapePainter, catan: ResMut<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
+) {
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let card_board_x_size = layout.cards.width();
+    let card_board_y_size = layout.cards.height();
+
+    let board_translate = layout.cards.center().extend(config.overlay_layer);
+
+    painter.translate(board_translate);
+    painter.with_children(|spawn_children| {
+        let config = spawn_children.config().clone();
+        let card_size = card_board_y_size * 0.9;
+        let xoffset = -card_board_x_size / 2. + card_size / 2.;
+        let mut i = 0;
+        for (kind, img) in img_store.resource_img.iter() {
+            if *kind != TileKind::Dessert && *kind != TileKind::Empty {
+                let size = Vec2 {
+                    x: card_size,
+                    y: card_size,
+                };
                 spawn_children.set_config(config.clone());
                 spawn_children.translate(Vec3 {
-                    x: xoffset + operation_size * i as f32,
+                    x: xoffset + card_size * i as f32,
                     y: 0.0,
                     z: 0.1,
                 });
-                spawn_children.image(res.clone(), size);
+                spawn_children.image(img.clone(), size);
                 spawn_children.translate(Vec3 {
                     x: 0.0,
                     y: 0.0,
@@ -1763,7 +2122,150 @@ fn draw_resource(
                 );
                 i += 1;
             }
-        });
+        }
+    });
+}
+
+fn check_development_card_click(
+    layout: Res<Layout>, pointer: Pointer, mut catan: ResMut<Catan>,
+    mut next_state: ResMut<NextState<CatanState>>,
+    mut next_card_state: ResMut<NextState<UseCardState>>,
+    mut action_writer: ConsumableEventWriter<GameAction>,
+) {
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        for i in 0..catan.players[catan.me].inner.cards.len() {
+            let card = layout.card(i);
+            if x > card.x - card.z
+                && x < card.x + card.z
+                && y > card.y - card.z
+                && y < card.y + card.z
+                && catan.players[catan.me].inner.cards[i] > 0
+            {
+                if i == DevCard::Knight as usize {
+                    next_card_state.set(UseCardState::Knight);
+                    catan.used_card = true;
+                    break;
+                } else if i == DevCard::RoadBuilding as usize {
+                    next_card_state.set(UseCardState::RoadBuilding);
+                    catan.used_card = true;
+                    break;
+                } else if i == DevCard::YearOfPlenty as usize {
+                    next_card_state.set(UseCardState::YearOfPlenty);
+                    catan.used_card = true;
+                    break;
+                } else if i == DevCard::Monopoly as usize {
+                    next_card_state.set(UseCardState::Monopoly);
+                    catan.used_card = true;
+                    break;
+                } else if i == DevCard::VictoryPoint as usize {
+                    action_writer.send(
+                        GameAct::UseDevelopmentCard((
+                            DevCard::VictoryPoint,
+                            DevelopmentCard::VictoryPoint,
+                        ))
+                        .into(),
+                    );
+                    next_state.set(CatanState::Menu);
+                    catan.used_card = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn draw_development_card(
+    mut painter: ShapePainter, catan: ResMut<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
+) {
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let card_board_x_size = layout.cards.width();
+    let card_board_y_size = layout.cards.height();
+
+    let board_translate = layout.cards.center().extend(config.overlay_layer);
+
+    painter.translate(board_translate);
+    painter.with_children(|spawn_children| {
+        let config = spawn_children.config().clone();
+        let card_size = card_board_y_size * 0.9;
+        let xoffset = -card_board_x_size / 2. + card_size / 2.;
+        for (i, card) in img_store.card_img.iter().enumerate() {
+            let size = Vec2 {
+                x: card_size,
+                y: card_size,
+            };
+            spawn_children.set_config(config.clone());
+            spawn_children.translate(Vec3 {
+                x: xoffset + card_size * i as f32,
+                y: 0.0,
+                z: 0.1,
+            });
+            spawn_children.image(card.clone(), size);
+            spawn_children.translate(Vec3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.1,
+            });
+            spawn_children.image(
+                img_store.number_img[catan.players[catan.me].inner.cards[i]].clone(),
+                Vec2::new(size.x * 0.5, size.y * 0.5),
+            );
+        }
+    });
+}
+
+// wood to stone, the order the hand and the bank show them in
+fn resource_kinds() -> impl Iterator<Item = TileKind> {
+    (0..TileKind::Max as u8)
+        .filter_map(|kind| TileKind::try_from(kind).ok())
+        .filter(|kind| kind.is_resource())
+}
+
+fn draw_resource(
+    mut painter: ShapePainter, catan: ResMut<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
+) {
+    let resources = &catan.players[catan.me].inner.resources;
+    for (i, kind) in resource_kinds().enumerate() {
+        let slot = layout.hand_slot(i);
+        painter.reset();
+        painter.render_layers = config.hud_layers();
+        painter.translate(slot.truncate().extend(0.1));
+        painter.image(
+            img_store.resource_img[&kind].clone(),
+            Vec2::splat(slot.z * 2.),
+        );
+        painter.translate(Vec3::new(0., 0., 0.1));
+        painter.image(
+            img_store.number_img[resources[kind as usize]].clone(),
+            Vec2::splat(slot.z),
+        );
+    }
+}
+
+fn draw_bank(
+    mut painter: ShapePainter, catan: Res<Catan>, layout: Res<Layout>,
+    img_store: Res<ImageStore>, config: Res<CatanConfig>,
+) {
+    let bank = layout.bank_slot(0);
+    painter.render_layers = config.hud_layers();
+    painter.translate(bank.truncate().extend(0.1));
+    painter.image(img_store.bank_img.clone(), Vec2::splat(bank.z * 2.));
+    for (i, kind) in resource_kinds().enumerate() {
+        let slot = layout.bank_slot(i + 1);
+        painter.reset();
+        painter.render_layers = config.hud_layers();
+        painter.translate(slot.truncate().extend(0.1));
+        painter.image(
+            img_store.resource_img[&kind].clone(),
+            Vec2::splat(slot.z * 2.),
+        );
+        painter.translate(Vec3::new(0., 0., 0.1));
+        painter.image(
+            img_store.number_img[catan.bank[kind as usize]].clone(),
+            Vec2::splat(slot.z),
+        );
     }
 }
 
@@ -1811,632 +2313,600 @@ impl TradeBoard {
 }
 
 fn check_trade_confirm_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut next_trade_state: ResMut<NextState<TradeState>>, trade: Res<TradeBoard>,
-    mut action_writer: ConsumableEventWriter<GameAction>,
+    pointer: Pointer, mut next_trade_state: ResMut<NextState<TradeState>>,
+    trade: Res<TradeBoard>, mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            for yes in trade.draw.response.iter() {
-                if x > yes.1.x - trade.draw.icon_size / 2.0
-                    && x < yes.1.x + trade.draw.icon_size / 2.0
-                    && y > yes.1.y - trade.draw.icon_size / 2.0
-                    && y < yes.1.y + trade.draw.icon_size / 2.0
-                {
-                    action_writer.send(GameAct::TradeConfirm(Some(*yes.0)).into());
-                    next_trade_state.set(TradeState::WaitingConfirm);
-                    return;
-                }
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        for yes in trade.draw.response.iter() {
+            if x > yes.1.x - trade.draw.icon_size / 2.0
+                && x < yes.1.x + trade.draw.icon_size / 2.0
+                && y > yes.1.y - trade.draw.icon_size / 2.0
+                && y < yes.1.y + trade.draw.icon_size / 2.0
+            {
+                action_writer.send(GameAct::TradeConfirm(Some(*yes.0)).into());
+                next_trade_state.set(TradeState::WaitingConfirm);
+                return;
             }
         }
     }
 }
 
 fn check_trade_offering_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut trade: ResMut<TradeBoard>, catan: ResMut<Catan>,
+    pointer: Pointer, mut trade: ResMut<TradeBoard>, catan: ResMut<Catan>,
     mut next_trade_state: ResMut<NextState<TradeState>>,
     mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            for (kind, (add, sub)) in trade.draw.offer.iter() {
-                if x > add.x - trade.draw.button_size
-                    && x < add.x + trade.draw.button_size
-                    && y > add.y - trade.draw.button_size
-                    && y < add.y + trade.draw.button_size
-                {
-                    let k = kind.clone();
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        for (kind, (add, sub)) in trade.draw.offer.iter() {
+            if x > add.x - trade.draw.button_size
+                && x < add.x + trade.draw.button_size
+                && y > add.y - trade.draw.button_size
+                && y < add.y + trade.draw.button_size
+            {
+                let k = kind.clone();
 
-                    if catan.players[catan.me].inner.resources[k as usize]
-                        > trade.resource.offer[k as usize] as usize
-                    {
-                        trade.resource.offer[k as usize] += 1;
-                        trade.resource.offer[k as usize] =
-                            trade.resource.offer[k as usize].min(20);
-                    }
-                    return;
-                }
-                if x > sub.x - trade.draw.button_size
-                    && x < sub.x + trade.draw.button_size
-                    && y > sub.y - trade.draw.button_size
-                    && y < sub.y + trade.draw.button_size
+                if catan.players[catan.me].inner.resources[k as usize]
+                    > trade.resource.offer[k as usize] as usize
                 {
-                    let k = kind.clone();
+                    trade.resource.offer[k as usize] += 1;
                     trade.resource.offer[k as usize] =
-                        trade.resource.offer[k as usize].saturating_sub(1);
-                    return;
-                }
-            }
-
-            for (kind, (add, sub)) in trade.draw.want.iter() {
-                if x > add.x - trade.draw.button_size
-                    && x < add.x + trade.draw.button_size
-                    && y > add.y - trade.draw.button_size
-                    && y < add.y + trade.draw.button_size
-                {
-                    let k = kind.clone();
-                    trade.resource.want[k as usize] += 1;
-                    trade.resource.want[k as usize] =
-                        trade.resource.want[k as usize].min(20);
-                    return;
-                }
-                if x > sub.x - trade.draw.button_size
-                    && x < sub.x + trade.draw.button_size
-                    && y > sub.y - trade.draw.button_size
-                    && y < sub.y + trade.draw.button_size
-                {
-                    let k = kind.clone();
-                    trade.resource.want[k as usize] =
-                        trade.resource.want[k as usize].saturating_sub(1);
-                    return;
+                        trade.resource.offer[k as usize].min(20);
                 }
-            }
-
-            if x > trade.draw.bank_yes.x - trade.draw.bank_yes.z
-                && x < trade.draw.bank_yes.x + trade.draw.bank_yes.z
-                && y > trade.draw.bank_yes.y - trade.draw.bank_yes.z
-                && y < trade.draw.bank_yes.y + trade.draw.bank_yes.z
-            {
-                action_writer.send(
-                    GameAct::TradeRequest(TradeRequest::new(
-                        trade
-                            .resource
-                            .offer
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        trade
-                            .resource
-                            .want
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        TradeTarget::Bank,
-                    ))
-                    .into(),
-                );
-                next_trade_state.set(TradeState::WaitingResponse);
                 return;
             }
-
-            if x > trade.draw.harbor_yes.x - trade.draw.harbor_yes.z
-                && x < trade.draw.harbor_yes.x + trade.draw.harbor_yes.z
-                && y > trade.draw.harbor_yes.y - trade.draw.harbor_yes.z
-                && y < trade.draw.harbor_yes.y + trade.draw.harbor_yes.z
+            if x > sub.x - trade.draw.button_size
+                && x < sub.x + trade.draw.button_size
+                && y > sub.y - trade.draw.button_size
+                && y < sub.y + trade.draw.button_size
             {
-                action_writer.send(
-                    GameAct::TradeRequest(TradeRequest::new(
-                        trade
-                            .resource
-                            .offer
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        trade
-                            .resource
-                            .want
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        TradeTarget::Harbor,
-                    ))
-                    .into(),
-                );
-                next_trade_state.set(TradeState::WaitingResponse);
+                let k = kind.clone();
+                trade.resource.offer[k as usize] =
+                    trade.resource.offer[k as usize].saturating_sub(1);
                 return;
             }
+        }
 
-            if x > trade.draw.player_yes.x - trade.draw.player_yes.z
-                && x < trade.draw.player_yes.x + trade.draw.player_yes.z
-                && y > trade.draw.player_yes.y - trade.draw.player_yes.z
-                && y < trade.draw.player_yes.y + trade.draw.player_yes.z
+        for (kind, (add, sub)) in trade.draw.want.iter() {
+            if x > add.x - trade.draw.button_size
+                && x < add.x + trade.draw.button_size
+                && y > add.y - trade.draw.button_size
+                && y < add.y + trade.draw.button_size
             {
-                action_writer.send(
-                    GameAct::TradeRequest(TradeRequest::new(
-                        trade
-                            .resource
-                            .offer
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        trade
-                            .resource
-                            .want
-                            .iter()
-                            .enumerate()
-                            .map(|(i, count)| {
-                                (TileKind::try_from(i as u8).unwrap(), *count as usize)
-                            })
-                            .collect(),
-                        TradeTarget::Player,
-                    ))
-                    .into(),
-                );
-                next_trade_state.set(TradeState::WaitingResponse);
+                let k = kind.clone();
+                trade.resource.want[k as usize] += 1;
+                trade.resource.want[k as usize] = trade.resource.want[k as usize].min(20);
                 return;
             }
-
-            if x > trade.draw.no.x - trade.draw.no.z
-                && x < trade.draw.no.x + trade.draw.no.z
-                && y > trade.draw.no.y - trade.draw.no.z
-                && y < trade.draw.no.y + trade.draw.no.z
+            if x > sub.x - trade.draw.button_size
+                && x < sub.x + trade.draw.button_size
+                && y > sub.y - trade.draw.button_size
+                && y < sub.y + trade.draw.button_size
             {
-                next_state.set(CatanState::Menu);
+                let k = kind.clone();
+                trade.resource.want[k as usize] =
+                    trade.resource.want[k as usize].saturating_sub(1);
                 return;
             }
         }
+
+        if x > trade.draw.bank_yes.x - trade.draw.bank_yes.z
+            && x < trade.draw.bank_yes.x + trade.draw.bank_yes.z
+            && y > trade.draw.bank_yes.y - trade.draw.bank_yes.z
+            && y < trade.draw.bank_yes.y + trade.draw.bank_yes.z
+        {
+            action_writer.send(
+                GameAct::TradeRequest(TradeRequest::new(
+                    trade
+                        .resource
+                        .offer
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    trade
+                        .resource
+                        .want
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    TradeTarget::Bank,
+                ))
+                .into(),
+            );
+            next_trade_state.set(TradeState::WaitingResponse);
+            return;
+        }
+
+        if x > trade.draw.harbor_yes.x - trade.draw.harbor_yes.z
+            && x < trade.draw.harbor_yes.x + trade.draw.harbor_yes.z
+            && y > trade.draw.harbor_yes.y - trade.draw.harbor_yes.z
+            && y < trade.draw.harbor_yes.y + trade.draw.harbor_yes.z
+        {
+            action_writer.send(
+                GameAct::TradeRequest(TradeRequest::new(
+                    trade
+                        .resource
+                        .offer
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    trade
+                        .resource
+                        .want
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    TradeTarget::Harbor,
+                ))
+                .into(),
+            );
+            next_trade_state.set(TradeState::WaitingResponse);
+            return;
+        }
+
+        if x > trade.draw.player_yes.x - trade.draw.player_yes.z
+            && x < trade.draw.player_yes.x + trade.draw.player_yes.z
+            && y > trade.draw.player_yes.y - trade.draw.player_yes.z
+            && y < trade.draw.player_yes.y + trade.draw.player_yes.z
+        {
+            action_writer.send(
+                GameAct::TradeRequest(TradeRequest::new(
+                    trade
+                        .resource
+                        .offer
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    trade
+                        .resource
+                        .want
+                        .iter()
+                        .enumerate()
+                        .map(|(i, count)| {
+                            (TileKind::try_from(i as u8).unwrap(), *count as usize)
+                        })
+                        .collect(),
+                    TradeTarget::Player,
+                ))
+                .into(),
+            );
+            next_trade_state.set(TradeState::WaitingResponse);
+            return;
+        }
+
+        if x > trade.draw.no.x - trade.draw.no.z
+            && x < trade.draw.no.x + trade.draw.no.z
+            && y > trade.draw.no.y - trade.draw.no.z
+            && y < trade.draw.no.y + trade.draw.no.z
+        {
+            next_state.set(CatanState::Menu);
+            return;
+        }
     }
 }
 
 fn check_trade_accepting_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    trade: Res<TradeBoard>, mut next_trade_state: ResMut<NextState<TradeState>>,
+    pointer: Pointer, trade: Res<TradeBoard>,
+    mut next_trade_state: ResMut<NextState<TradeState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            if x > trade.draw.player_yes.x - trade.draw.player_yes.z
-                && x < trade.draw.player_yes.x + trade.draw.player_yes.z
-                && y > trade.draw.player_yes.y - trade.draw.player_yes.z
-                && y < trade.draw.player_yes.y + trade.draw.player_yes.z
-            {
-                action_writer.send(GameAct::TradeResponse(TradeResponse::Accept).into());
-                next_trade_state.set(TradeState::WaitingConfirm);
-                return;
-            }
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        if x > trade.draw.player_yes.x - trade.draw.player_yes.z
+            && x < trade.draw.player_yes.x + trade.draw.player_yes.z
+            && y > trade.draw.player_yes.y - trade.draw.player_yes.z
+            && y < trade.draw.player_yes.y + trade.draw.player_yes.z
+        {
+            action_writer.send(GameAct::TradeResponse(TradeResponse::Accept).into());
+            next_trade_state.set(TradeState::WaitingConfirm);
+            return;
+        }
 
-            if x > trade.draw.no.x - trade.draw.no.z
-                && x < trade.draw.no.x + trade.draw.no.z
-                && y > trade.draw.no.y - trade.draw.no.z
-                && y < trade.draw.no.y + trade.draw.no.z
-            {
-                action_writer.send(GameAct::TradeResponse(TradeResponse::Accept).into());
-                next_trade_state.set(TradeState::WaitingConfirm);
-                return;
-            }
+        if x > trade.draw.no.x - trade.draw.no.z
+            && x < trade.draw.no.x + trade.draw.no.z
+            && y > trade.draw.no.y - trade.draw.no.z
+            && y < trade.draw.no.y + trade.draw.no.z
+        {
+            action_writer.send(GameAct::TradeResponse(TradeResponse::Reject).into());
+            next_trade_state.set(TradeState::WaitingConfirm);
+            return;
         }
     }
 }
 
 fn draw_trade(
-    mut painter: ShapePainter, catan: ResMut<Catan>, windows: Query<&Window>,
+    mut painter: ShapePainter, catan: ResMut<Catan>, layout: Res<Layout>,
     img_store: Res<ImageStore>, mut trade: ResMut<TradeBoard>,
-    state: Res<State<TradeState>>,
+    state: Res<State<TradeState>>, config: Res<CatanConfig>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let trade_board_size = window.width().min(window.height()) * 0.8;
-        painter.translate(Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: TRADEBPARD_LAYER,
-        });
-        painter
-            .rect(Vec2 {
-                x: trade_board_size,
-                y: trade_board_size,
-            })
-            .with_children(|spawn_children| {
-                let config = spawn_children.config().clone();
-                let trade_size = trade_board_size
-                    / img_store
-                        .resource_img
-                        .iter()
-                        .filter(|res| res.0.is_resource())
-                        .count() as f32;
-                let mut i = 0;
-                for j in 0..catan.players[catan.me].inner.resources.len() {
-                    let kind = &TileKind::try_from(j as u8).unwrap();
-                    if !kind.is_resource() {
-                        continue;
-                    }
-                    let res = img_store.resource_img.get(kind).unwrap();
-                    let size = Vec2 {
-                        x: trade_size,
-                        y: trade_size,
-                    };
-
-                    //offer
-                    let translate = Vec3 {
-                        x: -trade_board_size / 2. + trade_size / 2 as f32,
-                        y: trade_board_size / 2.
-                            - trade_size / 2.
-                            - trade_size * i as f32,
-                        z: 0.1,
-                    };
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let trade_board_size = layout.dialog.width();
+    painter.translate(layout.dialog.center().extend(config.overlay_layer));
+    painter
+        .rect(Vec2 {
+            x: trade_board_size,
+            y: trade_board_size,
+        })
+        .with_children(|spawn_children| {
+            let config = spawn_children.config().clone();
+            let trade_size = trade_board_size
+                / img_store
+                    .resource_img
+                    .iter()
+                    .filter(|res| res.0.is_resource())
+                    .count() as f32;
+            let mut i = 0;
+            for j in 0..catan.players[catan.me].inner.resources.len() {
+                let kind = &TileKind::try_from(j as u8).unwrap();
+                if !kind.is_resource() {
+                    continue;
+                }
+                let res = img_store.resource_img.get(kind).unwrap();
+                let size = Vec2 {
+                    x: trade_size,
+                    y: trade_size,
+                };
+
+                //offer
+                let translate = Vec3 {
+                    x: -trade_board_size / 2. + trade_size / 2 as f32,
+                    y: trade_board_size / 2. - trade_size / 2. - trade_size * i as f32,
+                    z: 0.1,
+                };
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(translate);
+                spawn_children.image(res.clone(), size);
+                spawn_children.translate(Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.1,
+                });
+                spawn_children.image(
+                    img_store.number_img[(catan.players[catan.me].inner.resources
+                        [*kind as usize])
+                        .min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+                if state.eq(&TradeState::Offering) {
+                    //add
                     spawn_children.set_config(config.clone());
-                    spawn_children.translate(translate);
-                    spawn_children.image(res.clone(), size);
-                    spawn_children.translate(Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.1,
-                    });
+                    spawn_children.translate(
+                        Vec3 {
+                            x: trade_size,
+                            y: trade_size / 4.,
+                            z: 0.,
+                        } + translate,
+                    );
                     spawn_children.image(
-                        img_store.number_img[(catan.players[catan.me].inner.resources
-                            [*kind as usize])
-                            .min(20)]
-                        .clone(),
+                        img_store.add.clone(),
                         Vec2::new(size.x * 0.5, size.y * 0.5),
                     );
-                    if state.eq(&TradeState::Offering) {
-                        //add
-                        spawn_children.set_config(config.clone());
-                        spawn_children.translate(
-                            Vec3 {
-                                x: trade_size,
-                                y: trade_size / 4.,
-                                z: 0.,
-                            } + translate,
-                        );
-                        spawn_children.image(
-                            img_store.add.clone(),
-                            Vec2::new(size.x * 0.5, size.y * 0.5),
-                        );
-                        let add_translate = spawn_children.transform.translation;
+                    let add_translate = spawn_children.transform.translation;
 
-                        //sub
-                        spawn_children.set_config(config.clone());
-                        spawn_children.translate(
-                            Vec3 {
-                                x: trade_size,
-                                y: -trade_size / 4.,
-                                z: 0.,
-                            } + translate,
-                        );
-                        spawn_children.image(
-                            img_store.sub.clone(),
-                            Vec2::new(size.x * 0.5, size.y * 0.5),
-                        );
-                        let sub_translate = spawn_children.transform.translation;
-                        trade
-                            .draw
-                            .offer
-                            .insert(*kind, (add_translate, sub_translate));
-                    }
-                    //count
+                    //sub
                     spawn_children.set_config(config.clone());
                     spawn_children.translate(
                         Vec3 {
-                            x: trade_size * 1.25,
-                            y: 0.,
-                            z: 0.1,
+                            x: trade_size,
+                            y: -trade_size / 4.,
+                            z: 0.,
                         } + translate,
                     );
                     spawn_children.image(
-                        img_store.number_img
-                            [(trade.resource.offer[*kind as usize] as usize).min(20)]
-                        .clone(),
+                        img_store.sub.clone(),
                         Vec2::new(size.x * 0.5, size.y * 0.5),
                     );
-
-                    //want
-                    let translate = Vec3 {
-                        x: trade_board_size / 2. - trade_size / 2 as f32,
-                        y: trade_board_size / 2.
-                            - trade_size / 2.
-                            - trade_size * i as f32,
+                    let sub_translate = spawn_children.transform.translation;
+                    trade
+                        .draw
+                        .offer
+                        .insert(*kind, (add_translate, sub_translate));
+                }
+                //count
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(
+                    Vec3 {
+                        x: trade_size * 1.25,
+                        y: 0.,
                         z: 0.1,
-                    };
+                    } + translate,
+                );
+                spawn_children.image(
+                    img_store.number_img
+                        [(trade.resource.offer[*kind as usize] as usize).min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+
+                //want
+                let translate = Vec3 {
+                    x: trade_board_size / 2. - trade_size / 2 as f32,
+                    y: trade_board_size / 2. - trade_size / 2. - trade_size * i as f32,
+                    z: 0.1,
+                };
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(translate);
+                spawn_children.image(res.clone(), size);
+                spawn_children.translate(Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.1,
+                });
+                spawn_children.image(
+                    img_store.number_img[(catan.players[catan.me].inner.resources
+                        [*kind as usize])
+                        .min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+                if state.eq(&TradeState::Offering) {
+                    //add
                     spawn_children.set_config(config.clone());
-                    spawn_children.translate(translate);
-                    spawn_children.image(res.clone(), size);
-                    spawn_children.translate(Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.1,
-                    });
+                    spawn_children.translate(
+                        Vec3 {
+                            x: -trade_size,
+                            y: trade_size / 4.,
+                            z: 0.,
+                        } + translate,
+                    );
                     spawn_children.image(
-                        img_store.number_img[(catan.players[catan.me].inner.resources
-                            [*kind as usize])
-                            .min(20)]
-                        .clone(),
+                        img_store.add.clone(),
                         Vec2::new(size.x * 0.5, size.y * 0.5),
                     );
-                    if state.eq(&TradeState::Offering) {
-                        //add
-                        spawn_children.set_config(config.clone());
-                        spawn_children.translate(
-                            Vec3 {
-                                x: -trade_size,
-                                y: trade_size / 4.,
-                                z: 0.,
-                            } + translate,
-                        );
-                        spawn_children.image(
-                            img_store.add.clone(),
-                            Vec2::new(size.x * 0.5, size.y * 0.5),
-                        );
-                        let add_translate = spawn_children.transform.translation;
+                    let add_translate = spawn_children.transform.translation;
 
-                        //sub
-                        spawn_children.set_config(config.clone());
-                        spawn_children.translate(
-                            Vec3 {
-                                x: -trade_size,
-                                y: -trade_size / 4.,
-                                z: 0.,
-                            } + translate,
-                        );
-                        spawn_children.image(
-                            img_store.sub.clone(),
-                            Vec2::new(size.x * 0.5, size.y * 0.5),
-                        );
-                        let sub_translate = spawn_children.transform.translation;
-                        trade
-                            .draw
-                            .want
-                            .insert(*kind, (add_translate, sub_translate));
-                    }
-                    //count
+                    //sub
                     spawn_children.set_config(config.clone());
                     spawn_children.translate(
                         Vec3 {
-                            x: -trade_size * 1.25,
-                            y: 0.,
-                            z: 0.1,
+                            x: -trade_size,
+                            y: -trade_size / 4.,
+                            z: 0.,
                         } + translate,
                     );
                     spawn_children.image(
-                        img_store.number_img
-                            [(trade.resource.want[*kind as usize] as usize).min(20)]
-                        .clone(),
+                        img_store.sub.clone(),
                         Vec2::new(size.x * 0.5, size.y * 0.5),
                     );
-                    trade.draw.button_size = trade_size * 0.25;
-                    i += 1;
+                    let sub_translate = spawn_children.transform.translation;
+                    trade
+                        .draw
+                        .want
+                        .insert(*kind, (add_translate, sub_translate));
                 }
-
-                if state.eq(&TradeState::Offering) {
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: trade_size * 1.5, // * 2.,
+                //count
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(
+                    Vec3 {
+                        x: -trade_size * 1.25,
+                        y: 0.,
                         z: 0.1,
-                    });
-                    if catan
-                        .inner
-                        .check_valid_local_trade(
-                            &Trade {
-                                from: catan.me,
-                                to: None,
-                                request: TradeRequest::new(
-                                    trade
-                                        .resource
-                                        .offer
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, count)| {
-                                            (
-                                                TileKind::try_from(i as u8).unwrap(),
-                                                *count as usize,
-                                            )
-                                        })
-                                        .collect(),
-                                    trade
-                                        .resource
-                                        .want
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, count)| {
-                                            (
-                                                TileKind::try_from(i as u8).unwrap(),
-                                                *count as usize,
-                                            )
-                                        })
-                                        .collect(),
-                                    TradeTarget::Bank,
-                                ),
-                            },
-                            &catan.players[catan.me].inner,
-                        )
-                        .is_ok()
-                    {
-                        spawn_children.image(
-                            img_store.bank_img.clone(),
-                            Vec2::new(trade_size, trade_size),
-                        );
-                    }
-                    trade.draw.bank_yes = spawn_children.transform.translation;
-                    trade.draw.bank_yes.z = trade_size / 2.;
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: -trade_size, // * 2.,
-                        z: 0.0,
-                    });
+                    } + translate,
+                );
+                spawn_children.image(
+                    img_store.number_img
+                        [(trade.resource.want[*kind as usize] as usize).min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+                trade.draw.button_size = trade_size * 0.25;
+                i += 1;
+            }
 
-                    if catan
-                        .inner
-                        .check_valid_local_trade(
-                            &Trade {
-                                from: catan.me,
-                                to: None,
-                                request: TradeRequest::new(
-                                    trade
-                                        .resource
-                                        .offer
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, count)| {
-                                            (
-                                                TileKind::try_from(i as u8).unwrap(),
-                                                *count as usize,
-                                            )
-                                        })
-                                        .collect(),
-                                    trade
-                                        .resource
-                                        .want
-                                        .iter()
-                                        .enumerate()
-                                        .map(|(i, count)| {
-                                            (
-                                                TileKind::try_from(i as u8).unwrap(),
-                                                *count as usize,
-                                            )
-                                        })
-                                        .collect(),
-                                    TradeTarget::Harbor,
-                                ),
-                            },
-                            &catan.players[catan.me].inner,
-                        )
-                        .is_ok()
-                    {
-                        spawn_children.image(
-                            img_store.harbor_img.clone(),
-                            Vec2::new(trade_size, trade_size),
-                        );
-                    }
-                    trade.draw.harbor_yes = spawn_children.transform.translation;
-                    trade.draw.harbor_yes.z = trade_size / 2.;
+            if state.eq(&TradeState::Offering) {
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: trade_size * 1.5, // * 2.,
+                    z: 0.1,
+                });
+                if catan
+                    .inner
+                    .check_valid_local_trade(
+                        &Trade {
+                            from: catan.me,
+                            to: None,
+                            request: TradeRequest::new(
+                                trade
+                                    .resource
+                                    .offer
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, count)| {
+                                        (
+                                            TileKind::try_from(i as u8).unwrap(),
+                                            *count as usize,
+                                        )
+                                    })
+                                    .collect(),
+                                trade
+                                    .resource
+                                    .want
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, count)| {
+                                        (
+                                            TileKind::try_from(i as u8).unwrap(),
+                                            *count as usize,
+                                        )
+                                    })
+                                    .collect(),
+                                TradeTarget::Bank,
+                            ),
+                        },
+                        &catan.players[catan.me].inner,
+                    )
+                    .is_ok()
+                {
+                    spawn_children.image(
+                        img_store.bank_img.clone(),
+                        Vec2::new(trade_size, trade_size),
+                    );
+                }
+                trade.draw.bank_yes = spawn_children.transform.translation;
+                trade.draw.bank_yes.z = trade_size / 2.;
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: -trade_size, // * 2.,
+                    z: 0.0,
+                });
 
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: -trade_size, // * 2.,
-                        z: 0.0,
-                    });
-                    spawn_children
-                        .image(img_store.yes.clone(), Vec2::new(trade_size, trade_size));
-                    trade.draw.player_yes = spawn_children.transform.translation;
-                    trade.draw.player_yes.z = trade_size / 2.;
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: -trade_size, // * 2.,
-                        z: 0.0,
-                    });
-                    spawn_children
-                        .image(img_store.no.clone(), Vec2::new(trade_size, trade_size));
-                    trade.draw.no = spawn_children.transform.translation;
-                    trade.draw.no.z = trade_size / 2.;
-                } else if state.eq(&TradeState::Accepting) {
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: trade_size / 2., // * 2.,
-                        z: 0.1,
-                    });
-                    spawn_children
-                        .image(img_store.yes.clone(), Vec2::new(trade_size, trade_size));
-                    trade.draw.player_yes = spawn_children.transform.translation;
-                    trade.draw.player_yes.z = trade_size / 2.;
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: -trade_size, // * 2.,
-                        z: 0.1,
-                    });
-                    spawn_children
-                        .image(img_store.no.clone(), Vec2::new(trade_size, trade_size));
-                    trade.draw.no = spawn_children.transform.translation;
-                    trade.draw.no.z = trade_size / 2.;
-                } else if state.eq(&TradeState::WaitingResponse)
-                    || state.eq(&TradeState::Confirming)
+                if catan
+                    .inner
+                    .check_valid_local_trade(
+                        &Trade {
+                            from: catan.me,
+                            to: None,
+                            request: TradeRequest::new(
+                                trade
+                                    .resource
+                                    .offer
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, count)| {
+                                        (
+                                            TileKind::try_from(i as u8).unwrap(),
+                                            *count as usize,
+                                        )
+                                    })
+                                    .collect(),
+                                trade
+                                    .resource
+                                    .want
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, count)| {
+                                        (
+                                            TileKind::try_from(i as u8).unwrap(),
+                                            *count as usize,
+                                        )
+                                    })
+                                    .collect(),
+                                TradeTarget::Harbor,
+                            ),
+                        },
+                        &catan.players[catan.me].inner,
+                    )
+                    .is_ok()
                 {
-                    let icon_size = trade_size * 0.8;
+                    spawn_children.image(
+                        img_store.harbor_img.clone(),
+                        Vec2::new(trade_size, trade_size),
+                    );
+                }
+                trade.draw.harbor_yes = spawn_children.transform.translation;
+                trade.draw.harbor_yes.z = trade_size / 2.;
 
-                    trade.draw.icon_size = icon_size;
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: 0.,
-                        z: 0.1,
-                    });
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: -trade_size, // * 2.,
+                    z: 0.0,
+                });
+                spawn_children
+                    .image(img_store.yes.clone(), Vec2::new(trade_size, trade_size));
+                trade.draw.player_yes = spawn_children.transform.translation;
+                trade.draw.player_yes.z = trade_size / 2.;
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: -trade_size, // * 2.,
+                    z: 0.0,
+                });
+                spawn_children
+                    .image(img_store.no.clone(), Vec2::new(trade_size, trade_size));
+                trade.draw.no = spawn_children.transform.translation;
+                trade.draw.no.z = trade_size / 2.;
+            } else if state.eq(&TradeState::Accepting) {
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: trade_size / 2., // * 2.,
+                    z: 0.1,
+                });
+                spawn_children
+                    .image(img_store.yes.clone(), Vec2::new(trade_size, trade_size));
+                trade.draw.player_yes = spawn_children.transform.translation;
+                trade.draw.player_yes.z = trade_size / 2.;
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: -trade_size, // * 2.,
+                    z: 0.1,
+                });
+                spawn_children
+                    .image(img_store.no.clone(), Vec2::new(trade_size, trade_size));
+                trade.draw.no = spawn_children.transform.translation;
+                trade.draw.no.z = trade_size / 2.;
+            } else if state.eq(&TradeState::WaitingResponse)
+                || state.eq(&TradeState::Confirming)
+            {
+                let icon_size = trade_size * 0.8;
 
-                    let i = 0;
-                    for id in 0..catan.players.len() {
-                        if id != catan.me {
-                            spawn_children.set_config(config.clone());
-                            spawn_children.translate(Vec3 {
-                                x: 0.,
-                                y: trade_size / 2.
-                                    - icon_size / 2.
-                                    - icon_size * i as f32,
-                                z: 0.1,
-                            });
-                            spawn_children.image(
-                                img_store.settlement_img[id].clone(),
-                                Vec2::new(icon_size, icon_size),
-                            );
-                            spawn_children.translate(Vec3 {
-                                x: 0.,
-                                y: -icon_size / 2.,
-                                z: 0.1,
-                            });
-                            match trade.resource.response.get(&id) {
-                                Some(TradeResponse::Accept) => {
-                                    trade
-                                        .draw
-                                        .response
-                                        .insert(id, spawn_children.transform.translation);
-
-                                    spawn_children.image(
-                                        img_store.yes.clone(),
-                                        Vec2::new(icon_size * 0.4, icon_size * 0.4),
-                                    );
-                                },
-
-                                Some(TradeResponse::Reject) => {
-                                    spawn_children.image(
-                                        img_store.no.clone(),
-                                        Vec2::new(icon_size * 0.4, icon_size * 0.4),
-                                    );
-                                },
-                                None => {},
-                            }
+                trade.draw.icon_size = icon_size;
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: 0.,
+                    z: 0.1,
+                });
+
+                let i = 0;
+                for id in 0..catan.players.len() {
+                    if id != catan.me {
+                        spawn_children.set_config(config.clone());
+                        spawn_children.translate(Vec3 {
+                            x: 0.,
+                            y: trade_size / 2. - icon_size / 2. - icon_size * i as f32,
+                            z: 0.1,
+                        });
+                        spawn_children.image(
+                            img_store.settlement_img[id].clone(),
+                            Vec2::new(icon_size, icon_size),
+                        );
+                        spawn_children.translate(Vec3 {
+                            x: 0.,
+                            y: -icon_size / 2.,
+                            z: 0.1,
+                        });
+                        match trade.resource.response.get(&id) {
+                            Some(TradeResponse::Accept) => {
+                                trade
+                                    .draw
+                                    .response
+                                    .insert(id, spawn_children.transform.translation);
+
+                                spawn_children.image(
+                                    img_store.yes.clone(),
+                                    Vec2::new(icon_size * 0.4, icon_size * 0.4),
+                                );
+                            },
+
+                            Some(TradeResponse::Reject) => {
+                                spawn_children.image(
+                                    img_store.no.clone(),
+                                    Vec2::new(icon_size * 0.4, icon_size * 0.4),
+                                );
+                            },
+                            None => {},
                         }
                     }
                 }
-            });
-    }
+            }
+        });
 }
 
 #[derive(Default)]
@@ -2462,211 +2932,194 @@ impl DropBoard {
         self.resource = DropBoardResource::default();
         self.draw = DropBoardDraw::default();
     }
+
+    fn drop_list(&self) -> Vec<(TileKind, usize)> {
+        self.resource
+            .drop
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (TileKind::try_from(i as u8).unwrap(), *count as usize))
+            .collect()
+    }
 }
 
 fn check_drop_click(
-    windows: Query<&Window>, mouse_button_input: Res<ButtonInput<MouseButton>>,
-    mut drop_board: ResMut<DropBoard>, mut catan: ResMut<Catan>,
+    pointer: Pointer, mut drop_board: ResMut<DropBoard>, mut catan: ResMut<Catan>,
     mut next_state: ResMut<NextState<CatanState>>,
     mut action_writer: ConsumableEventWriter<GameAction>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        // convert the mouse position to the window position
-        if let Some(mouse) = windows.iter().next().unwrap().cursor_position() {
-            let x = mouse.x - windows.iter().next().unwrap().width() / 2.;
-            let y = -(mouse.y - windows.iter().next().unwrap().height() / 2.);
-
-            for (kind, (add, sub)) in drop_board.draw.drop.iter() {
-                if x > add.x - drop_board.draw.button_size
-                    && x < add.x + drop_board.draw.button_size
-                    && y > add.y - drop_board.draw.button_size
-                    && y < add.y + drop_board.draw.button_size
-                {
-                    let k = kind.clone();
+    if let Some(Vec2 { x, y }) = pointer.click() {
+        for (kind, (add, sub)) in drop_board.draw.drop.iter() {
+            if x > add.x - drop_board.draw.button_size
+                && x < add.x + drop_board.draw.button_size
+                && y > add.y - drop_board.draw.button_size
+                && y < add.y + drop_board.draw.button_size
+            {
+                let k = kind.clone();
 
-                    if catan.players[catan.me].inner.resources[k as usize]
-                        > drop_board.resource.drop[k as usize] as usize
-                    {
-                        drop_board.resource.drop[k as usize] += 1;
-                        drop_board.resource.drop[k as usize] =
-                            drop_board.resource.drop[k as usize].min(20);
-                    }
-                    return;
-                }
-                if x > sub.x - drop_board.draw.button_size
-                    && x < sub.x + drop_board.draw.button_size
-                    && y > sub.y - drop_board.draw.button_size
-                    && y < sub.y + drop_board.draw.button_size
+                if catan.players[catan.me].inner.resources[k as usize]
+                    > drop_board.resource.drop[k as usize] as usize
                 {
-                    let k = kind.clone();
+                    drop_board.resource.drop[k as usize] += 1;
                     drop_board.resource.drop[k as usize] =
-                        drop_board.resource.drop[k as usize].saturating_sub(1);
-                    return;
+                        drop_board.resource.drop[k as usize].min(20);
                 }
+                return;
+            }
+            if x > sub.x - drop_board.draw.button_size
+                && x < sub.x + drop_board.draw.button_size
+                && y > sub.y - drop_board.draw.button_size
+                && y < sub.y + drop_board.draw.button_size
+            {
+                let k = kind.clone();
+                drop_board.resource.drop[k as usize] =
+                    drop_board.resource.drop[k as usize].saturating_sub(1);
+                return;
+            }
+        }
 
-                if x > drop_board.draw.yes.x - drop_board.draw.yes.z
-                    && x < drop_board.draw.yes.x + drop_board.draw.yes.z
-                    && y > drop_board.draw.yes.y - drop_board.draw.yes.z
-                    && y < drop_board.draw.yes.y + drop_board.draw.yes.z
-                    && drop_board.resource.drop.iter().sum::<u8>() as usize
-                        == catan.drop_cnt
-                {
-                    action_writer.send(
-                        GameAct::DropResource(
-                            drop_board
-                                .resource
-                                .drop
-                                .iter()
-                                .enumerate()
-                                .map(|(i, count)| {
-                                    (
-                                        TileKind::try_from(i as u8).unwrap(),
-                                        *count as usize,
-                                    )
-                                })
-                                .collect(),
-                        )
-                        .into(),
-                    );
-                    if catan.current_turn == catan.me {
-                        next_state.set(CatanState::Menu);
-                    } else {
-                        next_state.set(CatanState::Wait);
-                    }
-                    drop_board.clear();
-                    catan.drop_cnt = 0;
-                    return;
-                }
+        let drop = drop_board.drop_list();
+        if x > drop_board.draw.yes.x - drop_board.draw.yes.z
+            && x < drop_board.draw.yes.x + drop_board.draw.yes.z
+            && y > drop_board.draw.yes.y - drop_board.draw.yes.z
+            && y < drop_board.draw.yes.y + drop_board.draw.yes.z
+            && catan.players[catan.me]
+                .inner
+                .check_valid_drop(&drop, catan.drop_cnt)
+                .is_ok()
+        {
+            action_writer.send(GameAct::DropResource(drop).into());
+            if catan.current_turn == catan.me {
+                next_state.set(CatanState::Menu);
+            } else {
+                next_state.set(CatanState::Wait);
             }
+            drop_board.clear();
+            catan.drop_cnt = 0;
         }
     }
 }
 
 fn draw_drop_resource(
-    mut painter: ShapePainter, catan: ResMut<Catan>, windows: Query<&Window>,
+    mut painter: ShapePainter, catan: ResMut<Catan>, layout: Res<Layout>,
     img_store: Res<ImageStore>, mut drop_board: ResMut<DropBoard>,
+    config: Res<CatanConfig>,
 ) {
-    for window in windows.iter() {
-        painter.color = Color::rgb(0.0, 0.0, 0.0);
-        let trade_board_size = window.width().min(window.height()) * 0.8;
-        painter.translate(Vec3 {
-            x: 0.0,
-            y: 0.0,
-            z: TRADEBPARD_LAYER,
-        });
-        painter
-            .rect(Vec2 {
-                x: trade_board_size,
-                y: trade_board_size,
-            })
-            .with_children(|spawn_children| {
-                let config = spawn_children.config().clone();
-                let drop_size = trade_board_size
-                    / img_store
-                        .resource_img
-                        .iter()
-                        .filter(|res| res.0.is_resource())
-                        .count() as f32;
-                let mut i = 0;
-                for j in 0..catan.players[catan.me].inner.resources.len() {
-                    let kind = &TileKind::try_from(j as u8).unwrap();
-                    if !kind.is_resource() {
-                        continue;
-                    }
-                    let res = img_store.resource_img.get(kind).unwrap();
-                    let size = Vec2 {
-                        x: drop_size,
-                        y: drop_size,
-                    };
-
-                    //offer
-                    let translate = Vec3 {
-                        x: -trade_board_size / 2. + drop_size / 2 as f32,
-                        y: trade_board_size / 2. - drop_size / 2. - drop_size * i as f32,
-                        z: 0.1,
-                    };
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(translate);
-                    spawn_children.image(res.clone(), size);
-                    spawn_children.translate(Vec3 {
-                        x: 0.0,
-                        y: 0.0,
-                        z: 0.1,
-                    });
-                    spawn_children.image(
-                        img_store.number_img[(catan.players[catan.me].inner.resources
-                            [*kind as usize])
-                            .min(20)]
-                        .clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    //add
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(
-                        Vec3 {
-                            x: drop_size,
-                            y: drop_size / 4.,
-                            z: 0.,
-                        } + translate,
-                    );
-                    spawn_children.image(
-                        img_store.add.clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    let add_translate = spawn_children.transform.translation;
-
-                    //sub
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(
-                        Vec3 {
-                            x: drop_size,
-                            y: -drop_size / 4.,
-                            z: 0.,
-                        } + translate,
-                    );
-                    spawn_children.image(
-                        img_store.sub.clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    let sub_translate = spawn_children.transform.translation;
-                    drop_board
-                        .draw
-                        .drop
-                        .insert(*kind, (add_translate, sub_translate));
-
-                    //count
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(
-                        Vec3 {
-                            x: drop_size * 1.25,
-                            y: 0.,
-                            z: 0.1,
-                        } + translate,
-                    );
-                    spawn_children.image(
-                        img_store.number_img
-                            [(drop_board.resource.drop[*kind as usize] as usize).min(20)]
-                        .clone(),
-                        Vec2::new(size.x * 0.5, size.y * 0.5),
-                    );
-                    drop_board.draw.button_size = drop_size * 0.25;
-                    i += 1;
+    painter.render_layers = config.hud_layers();
+    painter.color = Color::rgb(0.0, 0.0, 0.0);
+    let trade_board_size = layout.dialog.width();
+    painter.translate(layout.dialog.center().extend(config.overlay_layer));
+    painter
+        .rect(Vec2 {
+            x: trade_board_size,
+            y: trade_board_size,
+        })
+        .with_children(|spawn_children| {
+            let config = spawn_children.config().clone();
+            let drop_size = trade_board_size
+                / img_store
+                    .resource_img
+                    .iter()
+                    .filter(|res| res.0.is_resource())
+                    .count() as f32;
+            let mut i = 0;
+            for j in 0..catan.players[catan.me].inner.resources.len() {
+                let kind = &TileKind::try_from(j as u8).unwrap();
+                if !kind.is_resource() {
+                    continue;
                 }
-                if drop_board.resource.drop.iter().sum::<u8>() as usize == catan.drop_cnt
-                {
-                    spawn_children.set_config(config.clone());
-                    spawn_children.translate(Vec3 {
-                        x: 0.,
-                        y: -drop_size, // * 2.,
+                let res = img_store.resource_img.get(kind).unwrap();
+                let size = Vec2 {
+                    x: drop_size,
+                    y: drop_size,
+                };
+
+                //offer
+                let translate = Vec3 {
+                    x: -trade_board_size / 2. + drop_size / 2 as f32,
+                    y: trade_board_size / 2. - drop_size / 2. - drop_size * i as f32,
+                    z: 0.1,
+                };
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(translate);
+                spawn_children.image(res.clone(), size);
+                spawn_children.translate(Vec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.1,
+                });
+                spawn_children.image(
+                    img_store.number_img[(catan.players[catan.me].inner.resources
+                        [*kind as usize])
+                        .min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+                //add
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(
+                    Vec3 {
+                        x: drop_size,
+                        y: drop_size / 4.,
+                        z: 0.,
+                    } + translate,
+                );
+                spawn_children
+                    .image(img_store.add.clone(), Vec2::new(size.x * 0.5, size.y * 0.5));
+                let add_translate = spawn_children.transform.translation;
+
+                //sub
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(
+                    Vec3 {
+                        x: drop_size,
+                        y: -drop_size / 4.,
+                        z: 0.,
+                    } + translate,
+                );
+                spawn_children
+                    .image(img_store.sub.clone(), Vec2::new(size.x * 0.5, size.y * 0.5));
+                let sub_translate = spawn_children.transform.translation;
+                drop_board
+                    .draw
+                    .drop
+                    .insert(*kind, (add_translate, sub_translate));
+
+                //count
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(
+                    Vec3 {
+                        x: drop_size * 1.25,
+                        y: 0.,
                         z: 0.1,
-                    });
-                    spawn_children
-                        .image(img_store.yes.clone(), Vec2::new(drop_size, drop_size));
-                    drop_board.draw.yes = spawn_children.transform.translation;
-                    drop_board.draw.yes.z = drop_size / 2.;
-                }
-            });
-    }
+                    } + translate,
+                );
+                spawn_children.image(
+                    img_store.number_img
+                        [(drop_board.resource.drop[*kind as usize] as usize).min(20)]
+                    .clone(),
+                    Vec2::new(size.x * 0.5, size.y * 0.5),
+                );
+                drop_board.draw.button_size = drop_size * 0.25;
+                i += 1;
+            }
+            if catan.players[catan.me]
+                .inner
+                .check_valid_drop(&drop_board.drop_list(), catan.drop_cnt)
+                .is_ok()
+            {
+                spawn_children.set_config(config.clone());
+                spawn_children.translate(Vec3 {
+                    x: 0.,
+                    y: -drop_size, // * 2.,
+                    z: 0.1,
+                });
+                spawn_children
+                    .image(img_store.yes.clone(), Vec2::new(drop_size, drop_size));
+                drop_board.draw.yes = spawn_children.transform.translation;
+                drop_board.draw.yes.z = drop_size / 2.;
+            }
+        });
 }
 
 #[derive(Default, Resource)]
@@ -2690,93 +3143,94 @@ struct ImageStore {
 
 fn load_img(
     asset_server: Res<AssetServer>, mut image_store: ResMut<ImageStore>,
-    platform: Res<Platform>,
+    config: Res<CatanConfig>,
 ) {
     image_store.operation_img.insert(
         Operation::Trade,
-        asset_server.load(platform.load_asset("catan/trade.png")),
+        asset_server.load(config.asset("trade.png")),
     );
     image_store.operation_img.insert(
         Operation::BuyCard,
-        asset_server.load(platform.load_asset("catan/buy_card.png")),
+        asset_server.load(config.asset("buy_card.png")),
     );
     image_store.operation_img.insert(
         Operation::UseCard,
-        asset_server.load(platform.load_asset("catan/use_card.png")),
+        asset_server.load(config.asset("use_card.png")),
     );
     image_store.operation_img.insert(
         Operation::EndTurn,
-        asset_server.load(platform.load_asset("catan/end_turn.png")),
+        asset_server.load(config.asset("end_turn.png")),
     );
+    image_store
+        .operation_img
+        .insert(Operation::Undo, asset_server.load(config.asset("no.png")));
 
     image_store.resource_img.insert(
         TileKind::Brick,
-        asset_server.load(platform.load_asset("catan/brick.png")),
+        asset_server.load(config.asset("brick.png")),
     );
     image_store.resource_img.insert(
         TileKind::Grain,
-        asset_server.load(platform.load_asset("catan/grain.png")),
+        asset_server.load(config.asset("grain.png")),
     );
     image_store.resource_img.insert(
         TileKind::Stone,
-        asset_server.load(platform.load_asset("catan/stone.png")),
-    );
-    image_store.resource_img.insert(
-        TileKind::Wood,
-        asset_server.load(platform.load_asset("catan/wood.png")),
-    );
-    image_store.resource_img.insert(
-        TileKind::Wool,
-        asset_server.load(platform.load_asset("catan/wool.png")),
+        asset_server.load(config.asset("stone.png")),
     );
+    image_store
+        .resource_img
+        .insert(TileKind::Wood, asset_server.load(config.asset("wood.png")));
+    image_store
+        .resource_img
+        .insert(TileKind::Wool, asset_server.load(config.asset("wool.png")));
     image_store.resource_img.insert(
         TileKind::Dessert,
-        asset_server.load(platform.load_asset("catan/dessert.png")),
+        asset_server.load(config.asset("dessert.png")),
     );
 
     for i in 0..image_store.number_img.len() {
         image_store.number_img[i] =
-            asset_server.load(platform.load_asset(format!("catan/{}.png", i).as_str()));
+            asset_server.load(config.asset(&format!("{}.png", i)));
     }
 
     for i in 0..image_store.road_img.len() {
-        image_store.road_img[i] = asset_server
-            .load(platform.load_asset(format!("catan/road_{}.png", i).as_str()));
+        image_store.road_img[i] =
+            asset_server.load(config.asset(&format!("road_{}.png", i)));
     }
 
     for i in 0..image_store.settlement_img.len() {
-        image_store.settlement_img[i] = asset_server
-            .load(platform.load_asset(format!("catan/settlement_{}.png", i).as_str()));
+        image_store.settlement_img[i] =
+            asset_server.load(config.asset(&format!("settlement_{}.png", i)));
     }
 
     for i in 0..image_store.dice_img.len() {
-        image_store.dice_img[i] = asset_server
-            .load(platform.load_asset(format!("catan/dice{}.png", i + 1).as_str()));
+        image_store.dice_img[i] =
+            asset_server.load(config.asset(&format!("dice{}.png", i + 1)));
     }
 
     for i in 0..image_store.city_img.len() {
-        image_store.city_img[i] = asset_server
-            .load(platform.load_asset(format!("catan/city_{}.png", i).as_str()));
+        image_store.city_img[i] =
+            asset_server.load(config.asset(&format!("city_{}.png", i)));
     }
 
     image_store.card_img[DevCard::Knight as usize] =
-        asset_server.load(platform.load_asset("catan/knight.png"));
+        asset_server.load(config.asset("knight.png"));
     image_store.card_img[DevCard::VictoryPoint as usize] =
-        asset_server.load(platform.load_asset("catan/victory_point.png"));
+        asset_server.load(config.asset("victory_point.png"));
     image_store.card_img[DevCard::RoadBuilding as usize] =
-        asset_server.load(platform.load_asset("catan/road_building.png"));
+        asset_server.load(config.asset("road_building.png"));
     image_store.card_img[DevCard::YearOfPlenty as usize] =
-        asset_server.load(platform.load_asset("catan/year_of_plenty.png"));
+        asset_server.load(config.asset("year_of_plenty.png"));
     image_store.card_img[DevCard::Monopoly as usize] =
-        asset_server.load(platform.load_asset("catan/monopoly.png"));
-
-    image_store.robber_img = asset_server.load(platform.load_asset("catan/robber.png"));
-    image_store.yes = asset_server.load(platform.load_asset("catan/yes.png"));
-    image_store.no = asset_server.load(platform.load_asset("catan/no.png"));
-    image_store.add = asset_server.load(platform.load_asset("catan/add.png"));
-    image_store.sub = asset_server.load(platform.load_asset("catan/sub.png"));
-    image_store.bank_img = asset_server.load(platform.load_asset("catan/bank.png"));
-    image_store.harbor_img = asset_server.load(platform.load_asset("catan/harbor.png"));
+        asset_server.load(config.asset("monopoly.png"));
+
+    image_store.robber_img = asset_server.load(config.asset("robber.png"));
+    image_store.yes = asset_server.load(config.asset("yes.png"));
+    image_store.no = asset_server.load(config.asset("no.png"));
+    image_store.add = asset_server.load(config.asset("add.png"));
+    image_store.sub = asset_server.load(config.asset("sub.png"));
+    image_store.bank_img = asset_server.load(config.asset("bank.png"));
+    image_store.harbor_img = asset_server.load(config.asset("harbor.png"));
 }
 
 #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
@@ -2786,6 +3240,29 @@ enum CatanLoadState {
     Loading,
     Initialzing,
     Loaded,
+    // the server does not speak our protocol
+    Rejected,
+}
+
+#[derive(Resource, Default)]
+struct Rejection(String);
+
+fn show_rejection(mut commands: Commands, rejection: Res<Rejection>) {
+    commands.spawn((
+        CatanScreen,
+        Text2dBundle {
+            text: Text::from_section(
+                rejection.0.clone(),
+                TextStyle {
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_xyz(0.0, 0.0, 10.0),
+            ..default()
+        },
+    ));
 }
 
 fn image_ready(asset_server: &Res<AssetServer>, img: Handle<Image>) -> bool {
@@ -2869,86 +3346,788 @@ fn loading(
         return;
     }
 
-    if !image_ready(&asset_server, img_store.bank_img.clone()) {
-        return;
+    if !image_ready(&asset_server, img_store.bank_img.clone()) {
+        return;
+    }
+
+    if !image_ready(&asset_server, img_store.harbor_img.clone()) {
+        return;
+    }
+
+    for event in event_reader.read() {
+        info!("event: {:?}", event.deref());
+        match event.consume().into() {
+            GameMsg::GameStart(start) => {
+                let catan = Catan::new(start.clone());
+
+                let settlment_img = img_store.settlement_img[catan.me as usize].clone();
+                let city_img = img_store.city_img[catan.me as usize].clone();
+                let road_img = img_store.road_img[catan.me as usize].clone();
+
+                img_store
+                    .operation_img
+                    .insert(Operation::BuildSettlement, settlment_img);
+                img_store
+                    .operation_img
+                    .insert(Operation::BuildCity, city_img);
+                img_store
+                    .operation_img
+                    .insert(Operation::BuildRoad, road_img);
+
+                commands.insert_resource(catan);
+                commands.insert_resource(Animations::default());
+                next_state.set(CatanLoadState::Initialzing);
+                break;
+            },
+            _ => {
+                unreachable!("unexpected event")
+            },
+        }
+    }
+}
+
+#[derive(Event, Debug)]
+struct GameEvent(GameMsg);
+
+impl Deref for GameEvent {
+    type Target = GameMsg;
+    fn deref(&self) -> &GameMsg {
+        &self.0
+    }
+}
+
+impl Into<GameMsg> for GameEvent {
+    fn into(self) -> GameMsg {
+        self.0
+    }
+}
+
+impl From<GameMsg> for GameEvent {
+    fn from(msg: GameMsg) -> Self {
+        GameEvent(msg)
+    }
+}
+
+#[derive(Event, Debug)]
+struct GameAction(GameAct);
+
+impl Deref for GameAction {
+    type Target = GameAct;
+    fn deref(&self) -> &GameAct {
+        &self.0
+    }
+}
+
+impl Into<GameAct> for GameAction {
+    fn into(self) -> GameAct {
+        self.0
+    }
+}
+
+impl From<GameAct> for GameAction {
+    fn from(act: GameAct) -> Self {
+        GameAction(act)
+    }
+}
+
+// what a history entry points at, outlined on the board while it is hovered
+#[derive(Debug, Clone, PartialEq)]
+enum Highlight {
+    Roads(Vec<Line>),
+    Point(Coordinate),
+    Tile(Coordinate),
+    // every tile with this number
+    Dice(usize),
+}
+
+#[derive(Debug, Clone)]
+struct HistoryEntry {
+    text: String,
+    highlight: Option<Highlight>,
+}
+
+#[derive(Resource, Default)]
+struct History {
+    entries: Vec<HistoryEntry>,
+    hovered: Option<usize>,
+}
+
+impl History {
+    fn highlight(&self) -> Option<&Highlight> {
+        self.hovered
+            .and_then(|i| self.entries.get(i))
+            .and_then(|entry| entry.highlight.as_ref())
+    }
+}
+
+fn card_name(card: DevCard) -> &'static str {
+    match card {
+        DevCard::Knight => "Knight",
+        DevCard::VictoryPoint => "Victory Point",
+        DevCard::RoadBuilding => "Road Building",
+        DevCard::Monopoly => "Monopoly",
+        DevCard::YearOfPlenty => "Year of Plenty",
+        DevCard::Max => unreachable!("not a card"),
+    }
+}
+
+fn resource_list(resources: &[(TileKind, usize)]) -> String {
+    resources
+        .iter()
+        .map(|(kind, count)| format!("{} {:?}", count, kind))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// read before the message is applied, so the board still shows what it was
+fn history_entry(catan: &Catan, msg: &GameMsg) -> Option<HistoryEntry> {
+    let entry = |text: String, highlight: Option<Highlight>| {
+        Some(HistoryEntry { text, highlight })
+    };
+    match msg {
+        GameMsg::PlayerFirstRoll((player, (dice1, dice2))) => entry(
+            format!(
+                "{} rolled {} for the first turn",
+                catan.name(*player),
+                dice1 + dice2
+            ),
+            None,
+        ),
+        GameMsg::PlayerInit(player) => entry(
+            format!("{} is placing a settlement", catan.name(*player)),
+            None,
+        ),
+        GameMsg::PlayerTurn(player) => {
+            entry(format!("{}'s turn", catan.name(*player)), None)
+        },
+        GameMsg::PlayerRollDice((dice1, dice2)) => {
+            let sum = (dice1 + dice2) as usize;
+            entry(
+                format!("{} rolled {}", catan.name(catan.current_turn), sum),
+                Some(Highlight::Dice(sum)),
+            )
+        },
+        GameMsg::PlayerBuildRoad(build) => entry(
+            format!("{} built a road", catan.name(build.player)),
+            Some(Highlight::Roads(vec![build.road])),
+        ),
+        GameMsg::PlayerBuildSettlement(build) => entry(
+            format!("{} built a settlement", catan.name(build.player)),
+            Some(Highlight::Point(build.point)),
+        ),
+        GameMsg::PlayerBuildCity(build) => entry(
+            format!("{} built a city", catan.name(build.player)),
+            Some(Highlight::Point(build.point)),
+        ),
+        GameMsg::PlayerUndo((player, build)) => match build {
+            Build::Road(road) => entry(
+                format!("{} took back a road", catan.name(*player)),
+                Some(Highlight::Roads(vec![*road])),
+            ),
+            Build::Settlement(point) => entry(
+                format!("{} took back a settlement", catan.name(*player)),
+                Some(Highlight::Point(*point)),
+            ),
+            Build::City(point) => entry(
+                format!("{} took back a city", catan.name(*player)),
+                Some(Highlight::Point(*point)),
+            ),
+        },
+        GameMsg::PlayerBuyDevelopmentCard(buy) => match buy.card {
+            Some(card) => entry(
+                format!(
+                    "{} bought a development card ({})",
+                    catan.name(buy.player),
+                    card_name(card)
+                ),
+                None,
+            ),
+            None => entry(
+                format!("{} bought a development card", catan.name(buy.player)),
+                None,
+            ),
+        },
+        GameMsg::PlayerUseDevelopmentCard(use_card) => {
+            let name = catan.name(use_card.player);
+            match &use_card.usage {
+                DevelopmentCard::Knight(select) => entry(
+                    format!("{} played a Knight", name),
+                    Some(Highlight::Tile(select.coord)),
+                ),
+                DevelopmentCard::VictoryPoint => {
+                    entry(format!("{} revealed a Victory Point", name), None)
+                },
+                DevelopmentCard::RoadBuilding(roads) => entry(
+                    format!("{} played Road Building", name),
+                    Some(Highlight::Roads(roads.to_vec())),
+                ),
+                DevelopmentCard::Monopoly(kind) => {
+                    entry(format!("{} played Monopoly on {:?}", name, kind), None)
+                },
+                DevelopmentCard::YearOfPlenty(kind1, kind2) => entry(
+                    format!(
+                        "{} played Year of Plenty for {:?} and {:?}",
+                        name, kind1, kind2
+                    ),
+                    None,
+                ),
+            }
+        },
+        GameMsg::PlayerSelectRobber(select_robber) => entry(
+            format!("{} moved the robber", catan.name(select_robber.player)),
+            Some(Highlight::Tile(select_robber.coord)),
+        ),
+        GameMsg::PlayerStealResource(steal) => {
+            let thief = catan.name(steal.player);
+            let target = catan.name(steal.target);
+            match steal.kind {
+                Some(kind) => {
+                    entry(format!("{} stole {:?} from {}", thief, kind, target), None)
+                },
+                None => entry(format!("{} stole from {}", thief, target), None),
+            }
+        },
+        // bank and harbor trades go through at once, PlayerTrade tells about them
+        GameMsg::PlayerTradeRequest((player, request))
+            if *request.target() == TradeTarget::Player =>
+        {
+            entry(
+                format!(
+                    "{} offers {} for {}",
+                    catan.name(*player),
+                    resource_list(request.from()),
+                    resource_list(request.to())
+                ),
+                None,
+            )
+        },
+        GameMsg::PlayerTradeResponse((player, resp)) => {
+            let answer = match resp {
+                TradeResponse::Accept => "accepted",
+                TradeResponse::Reject => "rejected",
+            };
+            entry(
+                format!("{} {} the trade", catan.name(*player), answer),
+                None,
+            )
+        },
+        GameMsg::PlayerTrade(Some(trade)) => {
+            let with = match trade.to {
+                Some(to) => catan.name(to),
+                None => match trade.request.target() {
+                    TradeTarget::Harbor => "a harbor".to_string(),
+                    _ => "the bank".to_string(),
+                },
+            };
+            entry(
+                format!(
+                    "{} traded {} for {} with {}",
+                    catan.name(trade.from),
+                    resource_list(trade.request.from()),
+                    resource_list(trade.request.to()),
+                    with
+                ),
+                None,
+            )
+        },
+        GameMsg::PlayerTrade(None) => entry("The trade was called off".to_string(), None),
+        GameMsg::PlayerOfferResources(offer) if offer.count > 0 => entry(
+            format!(
+                "{} received {} {:?}",
+                catan.name(offer.player),
+                offer.count,
+                offer.kind
+            ),
+            None,
+        ),
+        GameMsg::PlayerOfferResources(offer) if offer.count < 0 => entry(
+            format!(
+                "{} gave up {} {:?}",
+                catan.name(offer.player),
+                -offer.count,
+                offer.kind
+            ),
+            None,
+        ),
+        GameMsg::PlayerDropResources((player, count)) => entry(
+            format!("{} has to discard {}", catan.name(*player), count),
+            None,
+        ),
+        _ => None,
+    }
+}
+
+#[derive(Component)]
+struct HistoryPanel;
+
+// newest entries first, scrolled by moving the whole column up
+#[derive(Component, Default)]
+struct HistoryList {
+    position: f32,
+    shown: usize,
+}
+
+#[derive(Component)]
+struct HistoryItem(usize);
+
+// ui nodes are placed from the top left corner of the window
+fn history_style(style: &mut Style, layout: &Layout) {
+    style.left = Val::Px(layout.history.min.x + layout.window.x / 2. + 8.0);
+    style.top = Val::Px(layout.window.y / 2. - layout.history.max.y + 8.0);
+    style.width = Val::Px((layout.history.width() - 16.0).max(0.));
+    style.height = Val::Px((layout.history.height() - 16.0).max(0.));
+}
+
+fn spawn_history(
+    mut commands: Commands, mut history: ResMut<History>,
+    panels: Query<Entity, With<HistoryPanel>>, layout: Res<Layout>,
+) {
+    // a reconnect loads the game again
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *history = History::default();
+
+    let mut style = Style {
+        position_type: PositionType::Absolute,
+        overflow: Overflow::clip_y(),
+        ..default()
+    };
+    history_style(&mut style, &layout);
+    commands
+        .spawn((
+            CatanScreen,
+            HistoryPanel,
+            Interaction::default(),
+            NodeBundle {
+                style,
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                HistoryList::default(),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(0.0),
+                        width: Val::Percent(100.0),
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(4.0)),
+                        row_gap: Val::Px(2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn update_history(
+    mut commands: Commands, mut history: ResMut<History>,
+    mut lists: Query<(Entity, &mut HistoryList)>,
+    mut items: Query<(&Interaction, &HistoryItem, &mut Text)>,
+) {
+    for (entity, mut list) in lists.iter_mut() {
+        for index in list.shown..history.entries.len() {
+            let item = commands
+                .spawn((
+                    HistoryItem(index),
+                    Interaction::default(),
+                    TextBundle::from_section(
+                        history.entries[index].text.clone(),
+                        TextStyle {
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                ))
+                .id();
+            commands.entity(entity).insert_children(0, &[item]);
+        }
+        list.shown = history.entries.len();
+    }
+
+    let hovered = items
+        .iter()
+        .find(|(interaction, _, _)| **interaction != Interaction::None)
+        .map(|(_, item, _)| item.0);
+    if history.hovered != hovered {
+        history.hovered = hovered;
+    }
+    for (_, item, mut text) in items.iter_mut() {
+        let color = if Some(item.0) == hovered {
+            Color::YELLOW
+        } else {
+            Color::WHITE
+        };
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+fn scroll_history(
+    mut wheel: EventReader<MouseWheel>,
+    panels: Query<(&Interaction, &Node), With<HistoryPanel>>,
+    mut lists: Query<(&mut HistoryList, &mut Style, &Node)>,
+) {
+    let mut delta = 0.;
+    for event in wheel.read() {
+        delta += match event.unit {
+            MouseScrollUnit::Line => event.y * 20.,
+            MouseScrollUnit::Pixel => event.y,
+        };
+    }
+    let Ok((interaction, panel)) = panels.get_single() else {
+        return;
+    };
+    // the wheel only scrolls while the mouse is over the panel
+    if delta == 0. || *interaction == Interaction::None {
+        return;
+    }
+    for (mut list, mut style, node) in lists.iter_mut() {
+        let max = (node.size().y - panel.size().y).max(0.);
+        list.position = (list.position + delta).clamp(-max, 0.);
+        style.top = Val::Px(list.position);
+    }
+}
+
+// hidden again once its timer runs out
+#[derive(Component)]
+struct AlertBanner(Timer);
+
+// across the top of the board, the text centred in it
+fn alert_style(style: &mut Style, layout: &Layout) {
+    style.left = Val::Px(layout.board.min.x + layout.window.x / 2.);
+    style.top = Val::Px(layout.window.y / 2. - layout.board.max.y + 16.0);
+    style.width = Val::Px(layout.board.width());
+}
+
+fn spawn_alert(
+    mut commands: Commands, banners: Query<Entity, With<AlertBanner>>,
+    layout: Res<Layout>,
+) {
+    for entity in banners.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let mut style = Style {
+        position_type: PositionType::Absolute,
+        justify_content: JustifyContent::Center,
+        ..default()
+    };
+    alert_style(&mut style, &layout);
+    commands
+        .spawn((
+            CatanScreen,
+            AlertBanner(Timer::from_seconds(4.0, TimerMode::Once)),
+            NodeBundle {
+                style,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 28.0,
+                        color: Color::YELLOW,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    padding: UiRect::axes(Val::Px(16.0), Val::Px(8.0)),
+                    ..default()
+                })
+                .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+            );
+        });
+}
+
+fn show_alert(
+    mut commands: Commands, mut alerts: EventReader<CatanAlert>, time: Res<Time>,
+    config: Res<CatanConfig>, mut pitches: ResMut<Assets<Pitch>>,
+    mut banners: Query<(&mut AlertBanner, &mut Visibility, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if let Some(alert) = alerts.read().last() {
+        commands.spawn(PitchBundle {
+            source: pitches.add(alert.cue.pitch()),
+            settings: PlaybackSettings::DESPAWN
+                .with_volume(Volume::new(config.cue_volume)),
+        });
+        for (mut banner, mut visibility, children) in banners.iter_mut() {
+            banner.0.reset();
+            *visibility = Visibility::Inherited;
+            for child in children.iter() {
+                if let Ok(mut text) = texts.get_mut(*child) {
+                    text.sections[0].value = alert.text.clone();
+                }
+            }
+        }
     }
-
-    if !image_ready(&asset_server, img_store.harbor_img.clone()) {
-        return;
+    for (mut banner, mut visibility, _) in banners.iter_mut() {
+        if banner.0.tick(time.delta()).just_finished() {
+            *visibility = Visibility::Hidden;
+        }
     }
+}
 
-    for event in event_reader.read() {
-        info!("event: {:?}", event.deref());
-        match event.consume().into() {
-            GameMsg::GameStart(start) => {
-                let catan = Catan::new(start.clone());
-
-                let settlment_img = img_store.settlement_img[catan.me as usize].clone();
-                let city_img = img_store.city_img[catan.me as usize].clone();
-                let road_img = img_store.road_img[catan.me as usize].clone();
+fn draw_highlight(
+    painter: &mut ShapePainter, catan: &ResMut<Catan>, highlight: &Highlight,
+) {
+    let config = painter.config().clone();
+    let radius = catan.radius.unwrap();
+
+    painter.reset();
+    painter.color = Color::YELLOW;
+    painter.hollow = true;
+    painter.thickness = radius * 0.08;
+    let lift = Vec3::new(0., 0., 0.5);
+    match highlight {
+        Highlight::Roads(roads) => {
+            painter.thickness = radius * 0.15;
+            for road in roads.iter() {
+                painter.line(
+                    catan.points[road.start.x][road.start.y] + lift,
+                    catan.points[road.end.x][road.end.y] + lift,
+                );
+            }
+        },
+        Highlight::Point(point) => {
+            painter.translate(catan.points[point.x][point.y] + lift);
+            painter.circle(radius * 0.4);
+        },
+        Highlight::Tile(tile) => {
+            painter.translate(catan.tiles[tile.x][tile.y] + lift);
+            painter.circle(radius * 0.8);
+        },
+        Highlight::Dice(number) => {
+            for tile in catan.inner.dice_map().get(number).into_iter().flatten() {
+                painter.reset();
+                painter.translate(catan.tiles[tile.x][tile.y] + lift);
+                painter.circle(radius * 0.8);
+            }
+        },
+    }
+    painter.set_config(config);
+}
 
-                img_store
-                    .operation_img
-                    .insert(Operation::BuildSettlement, settlment_img);
-                img_store
-                    .operation_img
-                    .insert(Operation::BuildCity, city_img);
-                img_store
-                    .operation_img
-                    .insert(Operation::BuildRoad, road_img);
+// how a message shows on the board
+#[derive(Debug, Clone)]
+enum Animation {
+    // the dice tumble before landing on the roll
+    Dice,
+    // a card flies to the player from each tile that produced it, else from the bank
+    Resource {
+        kind: TileKind,
+        player: usize,
+        tiles: Vec<Coordinate>,
+    },
+    // the robber hops over to its new tile
+    Robber {
+        from: Coordinate,
+        to: Coordinate,
+    },
+    // a ring closes in on where the build goes
+    Build(Build),
+}
 
-                commands.insert_resource(catan);
-                next_state.set(CatanLoadState::Initialzing);
-                break;
-            },
-            _ => {
-                unreachable!("unexpected event")
-            },
+impl Animation {
+    fn seconds(&self) -> f32 {
+        match self {
+            Animation::Dice => 0.6,
+            Animation::Resource { .. } => 0.4,
+            Animation::Robber { .. } => 0.5,
+            Animation::Build(_) => 0.35,
         }
     }
 }
 
-#[derive(Event, Debug)]
-struct GameEvent(GameMsg);
+// messages wait for the animation before them, and are applied once their own
+// has played, so the board never runs ahead of what was shown
+#[derive(Resource, Default)]
+struct Animations {
+    playing: Option<(Animation, Timer)>,
+    // the oldest message waiting played its animation already
+    played: bool,
+    // offers straight after a roll come from the tiles that rolled it
+    after_roll: bool,
+}
 
-impl Deref for GameEvent {
-    type Target = GameMsg;
-    fn deref(&self) -> &GameMsg {
-        &self.0
+impl Animations {
+    fn play(&mut self, animation: Animation, speed: f32) {
+        let timer = Timer::from_seconds(animation.seconds() / speed, TimerMode::Once);
+        self.playing = Some((animation, timer));
     }
-}
 
-impl Into<GameMsg> for GameEvent {
-    fn into(self) -> GameMsg {
-        self.0
+    // eased, so things set off and land gently
+    fn progress(&self) -> Option<(&Animation, f32)> {
+        let (animation, timer) = self.playing.as_ref()?;
+        let t = timer.fraction();
+        Some((animation, t * t * (3. - 2. * t)))
     }
-}
 
-impl From<GameMsg> for GameEvent {
-    fn from(msg: GameMsg) -> Self {
-        GameEvent(msg)
+    fn robber_moving(&self) -> bool {
+        matches!(self.playing, Some((Animation::Robber { .. }, _)))
     }
-}
 
-#[derive(Event, Debug)]
-struct GameAction(GameAct);
+    // faces that look random while the dice tumble
+    fn tumbling_dice(&self) -> Option<(u8, u8)> {
+        let Some((Animation::Dice, timer)) = &self.playing else {
+            return None;
+        };
+        let step = (timer.elapsed_secs() * 15.) as usize;
+        Some(((step * 5 % 6 + 1) as u8, ((step + 3) % 6 + 1) as u8))
+    }
+}
 
-impl Deref for GameAction {
-    type Target = GameAct;
-    fn deref(&self) -> &GameAct {
-        &self.0
+// read before the message is applied, like history_entry
+fn animation_for(catan: &Catan, after_roll: bool, msg: &GameMsg) -> Option<Animation> {
+    match msg {
+        GameMsg::PlayerFirstRoll(_) | GameMsg::PlayerRollDice(_) => Some(Animation::Dice),
+        GameMsg::PlayerOfferResources(offer) if offer.count > 0 => {
+            let number = (catan.dice.0 + catan.dice.1) as usize;
+            let tiles = if after_roll {
+                catan
+                    .inner
+                    .dice_map()
+                    .get(&number)
+                    .into_iter()
+                    .flatten()
+                    .filter(|tile| {
+                        **tile != catan.inner.robber()
+                            && catan.inner.tile(**tile).kind() == offer.kind
+                            && catan.inner.tile_get_points(**tile).iter().any(|point| {
+                                catan.inner.point(*point).owner() == Some(offer.player)
+                            })
+                    })
+                    .copied()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            Some(Animation::Resource {
+                kind: offer.kind,
+                player: offer.player,
+                tiles,
+            })
+        },
+        GameMsg::PlayerSelectRobber(select_robber)
+            if select_robber.coord != catan.inner.robber() =>
+        {
+            Some(Animation::Robber {
+                from: catan.inner.robber(),
+                to: select_robber.coord,
+            })
+        },
+        GameMsg::PlayerBuildRoad(build) => {
+            Some(Animation::Build(Build::Road(build.road)))
+        },
+        GameMsg::PlayerBuildSettlement(build) => {
+            Some(Animation::Build(Build::Settlement(build.point)))
+        },
+        GameMsg::PlayerBuildCity(build) => {
+            Some(Animation::Build(Build::City(build.point)))
+        },
+        _ => None,
     }
 }
 
-impl Into<GameAct> for GameAction {
-    fn into(self) -> GameAct {
-        self.0
+fn play_animation(time: Res<Time>, mut animations: ResMut<Animations>) {
+    let Some((_, timer)) = animations.playing.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        animations.playing = None;
+        animations.played = true;
     }
 }
 
-impl From<GameAct> for GameAction {
-    fn from(act: GameAct) -> Self {
-        GameAction(act)
+fn draw_animation(
+    mut painter: ShapePainter, catan: Res<Catan>, layout: Res<Layout>,
+    animations: Res<Animations>, img_store: Res<ImageStore>, config: Res<CatanConfig>,
+    cameras: Query<&Transform, (With<Camera2d>, Without<HudCamera>)>,
+) {
+    let (Some((animation, t)), Some(radius)) = (animations.progress(), catan.radius)
+    else {
+        return;
+    };
+    // lifts things off the board halfway through
+    let hop = (t * PI).sin();
+    match animation {
+        // draw_player_board shows the tumbling faces
+        Animation::Dice => {},
+        Animation::Resource {
+            kind,
+            player,
+            tiles,
+        } => {
+            let Ok(camera) = cameras.get_single() else {
+                return;
+            };
+            let i = resource_kinds().position(|each| each == *kind).unwrap_or(0);
+            let to = if *player == catan.me {
+                layout.hand_slot(i)
+            } else {
+                layout.players.center().extend(layout.players.height() / 4.)
+            };
+            // tiles are in board coordinates, the cards fly over the hud
+            let from: Vec<Vec3> = if tiles.is_empty() {
+                vec![layout.bank_slot(i + 1)]
+            } else {
+                tiles
+                    .iter()
+                    .map(|tile| {
+                        let at = catan.tiles[tile.x][tile.y].truncate();
+                        ((at - camera.translation.truncate()) / camera.scale.x)
+                            .extend(radius * 0.6 / camera.scale.x)
+                    })
+                    .collect()
+            };
+            for from in from {
+                let at = from.truncate().lerp(to.truncate(), t)
+                    + Vec2::new(0., hop * layout.touch);
+                painter.reset();
+                painter.render_layers = config.hud_layers();
+                painter.translate(at.extend(config.overlay_layer));
+                painter.image(
+                    img_store.resource_img[kind].clone(),
+                    Vec2::splat((from.z + (to.z - from.z) * t) * 2.),
+                );
+            }
+        },
+        Animation::Robber { from, to } => {
+            let from = catan.tiles[from.x][from.y];
+            let to = catan.tiles[to.x][to.y];
+            painter.translate(from.lerp(to, t) + Vec3::new(0., radius * 0.5 * hop, 0.5));
+            painter.image(
+                img_store.robber_img.clone(),
+                Vec2::splat(radius * 2. * (1. + 0.2 * hop)),
+            );
+        },
+        Animation::Build(build) => {
+            let at = match build {
+                Build::Road(road) => {
+                    (catan.points[road.start.x][road.start.y]
+                        + catan.points[road.end.x][road.end.y])
+                        / 2.
+                },
+                Build::Settlement(point) | Build::City(point) => {
+                    catan.points[point.x][point.y]
+                },
+            };
+            painter.translate(at + Vec3::new(0., 0., 0.5));
+            painter.hollow = true;
+            painter.thickness = radius * 0.08;
+            painter.color = Color::rgba(1.0, 1.0, 1.0, t);
+            painter.circle(radius * (1.2 - 0.9 * t));
+        },
     }
 }
 
@@ -2957,20 +4136,54 @@ fn process_event(
     trade_state: Res<State<TradeState>>, mut next_state: ResMut<NextState<CatanState>>,
     mut next_trade_state: ResMut<NextState<TradeState>>,
     mut event_reader: ConsumableEventReader<GameEvent>,
-    mut action_writer: ConsumableEventWriter<GameAction>,
+    mut action_writer: ConsumableEventWriter<GameAction>, mut history: ResMut<History>,
+    mut alerts: EventWriter<CatanAlert>, mut animations: ResMut<Animations>,
+    config: Res<CatanConfig>,
 ) {
     for event in event_reader.read() {
+        // the message stays queued until its animation has played
+        if animations.playing.is_some() {
+            break;
+        }
+        let msg: &GameMsg = event.deref();
+        if !animations.played && config.animation_speed > 0. {
+            if let Some(animation) = animation_for(&catan, animations.after_roll, msg) {
+                animations.play(animation, config.animation_speed);
+                break;
+            }
+        }
+        animations.played = false;
+        animations.after_roll = match msg {
+            GameMsg::PlayerRollDice(_) => true,
+            GameMsg::PlayerOfferResources(_) | GameMsg::BankResources(_) => {
+                animations.after_roll
+            },
+            _ => false,
+        };
         info!("event: {:?}", event.deref());
+        if let Some(entry) = history_entry(&catan, event.deref()) {
+            history.entries.push(entry);
+        }
         match event.consume().into() {
             GameMsg::PlayerInit(player) => {
                 if player == catan.me {
+                    alerts.send(CatanAlert {
+                        text: "Place a settlement and a road".to_string(),
+                        cue: Cue::Turn,
+                    });
                     next_state.set(CatanState::InitSettlement);
                     break;
                 }
             },
             GameMsg::PlayerTurn(player) => {
                 catan.current_turn = player;
+                catan.undo.clear();
+                catan.road_hands.clear();
                 if player == catan.me {
+                    alerts.send(CatanAlert {
+                        text: "Your turn".to_string(),
+                        cue: Cue::Turn,
+                    });
                     next_state.set(CatanState::Menu);
                     catan.used_card = false;
                     break;
@@ -2979,38 +4192,40 @@ fn process_event(
                     break;
                 }
             },
+            GameMsg::PlayerFirstRoll((_, (dice1, dice2))) => {
+                catan.dice = (dice1, dice2);
+            },
             GameMsg::PlayerRollDice((dice1, dice2)) => {
                 catan.dice = (dice1, dice2);
             },
             GameMsg::PlayerBuildRoad(build) => {
+                // the two setup roads are free, every later one was paid for
+                let paid = catan.players[build.player].inner.roads.len() >= 2;
                 catan.inner.add_road(build.player, build.road);
                 catan.players[build.player].inner.add_road(build.road);
-                if build.player != catan.me {
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Brick as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Wood as usize]
-                        .saturating_sub(1);
+                if build.player == catan.me {
+                    catan.undo.push(Build::Road(build.road));
+                } else if paid {
+                    let hand = &mut catan.players[build.player].inner;
+                    let before = hand.resources;
+                    for (kind, count) in Build::Road(build.road).cost() {
+                        hand.remove_resource(Some(*kind), *count);
+                    }
+                    catan.road_hands.push(before);
                 }
             },
             GameMsg::PlayerBuildSettlement(build) => {
                 catan.inner.add_settlement(build.player, build.point);
                 if build.player == catan.me {
                     catan.players[build.player].inner.settlement_left -= 1;
-                } else {
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Brick as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Grain as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Wood as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Wool as usize]
-                        .saturating_sub(1);
+                    catan.undo.push(Build::Settlement(build.point));
+                } else if catan.players[build.player].inner.roads.len() >= 2 {
+                    // setup settlements come before their road and are free
+                    for (kind, count) in Build::Settlement(build.point).cost() {
+                        catan.players[build.player]
+                            .inner
+                            .remove_resource(Some(*kind), *count);
+                    }
                 }
             },
             GameMsg::PlayerBuildCity(build) => {
@@ -3019,17 +4234,18 @@ fn process_event(
                 if build.player == catan.me {
                     catan.players[build.player].inner.city_left -= 1;
                     catan.players[build.player].inner.settlement_left += 1;
+                    catan.undo.push(Build::City(build.point));
                 } else {
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Grain as usize]
-                        .saturating_sub(2);
-                    let _ = catan.players[build.player].inner.resources
-                        [TileKind::Stone as usize]
-                        .saturating_sub(3);
+                    for (kind, count) in Build::City(build.point).cost() {
+                        catan.players[build.player]
+                            .inner
+                            .remove_resource(Some(*kind), *count);
+                    }
                 }
             },
             GameMsg::PlayerBuyDevelopmentCard(buy) => {
                 assert_eq!(catan.current_turn, buy.player);
+                catan.undo.clear();
                 if buy.player == catan.me {
                     catan.players[buy.player].inner.resources
                         [TileKind::Stone as usize] -= 1;
@@ -3040,19 +4256,17 @@ fn process_event(
                     catan.players[buy.player].inner.add_card(buy.card);
                 } else {
                     catan.players[buy.player].inner.add_card(None);
-                    let _ = catan.players[buy.player].inner.resources
-                        [TileKind::Grain as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[buy.player].inner.resources
-                        [TileKind::Wool as usize]
-                        .saturating_sub(1);
-                    let _ = catan.players[buy.player].inner.resources
-                        [TileKind::Stone as usize]
-                        .saturating_sub(1);
+                    for kind in [TileKind::Grain, TileKind::Wool, TileKind::Stone] {
+                        catan.players[buy.player]
+                            .inner
+                            .remove_resource(Some(kind), 1);
+                    }
                 }
             },
             GameMsg::PlayerUseDevelopmentCard(use_card) => {
                 assert_eq!(catan.current_turn, use_card.player);
+                // the roads of a road building card came just before, they stay
+                catan.undo.clear();
                 if use_card.player == catan.me {
                     catan.players[use_card.player]
                         .inner
@@ -3062,6 +4276,14 @@ fn process_event(
                 }
 
                 match use_card.usage {
+                    DevelopmentCard::RoadBuilding(_) if use_card.player != catan.me => {
+                        // the two roads just before were free after all
+                        let paid = catan.road_hands.len().saturating_sub(2);
+                        if let Some(hand) = catan.road_hands.get(paid).copied() {
+                            catan.players[use_card.player].inner.resources = hand;
+                        }
+                        catan.road_hands.truncate(paid);
+                    },
                     DevelopmentCard::Monopoly(kind) => {
                         for i in 0..catan.players.len() {
                             if i != use_card.player {
@@ -3087,11 +4309,77 @@ fn process_event(
 
                 if let DevelopmentCard::Monopoly(_) = use_card.usage {}
             },
+            GameMsg::PlayerUndo((player, build)) => {
+                match build {
+                    Build::Road(road) => {
+                        catan.inner.remove_road(road);
+                        catan.players[player].inner.remove_road(road);
+                    },
+                    Build::Settlement(point) => {
+                        catan.inner.remove_settlement(point);
+                        if player == catan.me {
+                            catan.players[player].inner.settlement_left += 1;
+                        }
+                    },
+                    Build::City(point) => {
+                        catan.inner.remove_city(point);
+                        if player == catan.me {
+                            catan.players[player].inner.city_left += 1;
+                            catan.players[player].inner.settlement_left -= 1;
+                        }
+                    },
+                }
+                let before = match build {
+                    Build::Road(_) if player != catan.me => catan.road_hands.pop(),
+                    _ => None,
+                };
+                if player == catan.me {
+                    catan.undo.pop();
+                }
+                if let Some(hand) = before {
+                    catan.players[player].inner.resources = hand;
+                } else {
+                    for (kind, count) in build.cost() {
+                        catan.players[player]
+                            .inner
+                            .add_resource(Some(*kind), *count);
+                    }
+                }
+            },
             GameMsg::PlayerSelectRobber(select_robber) => {
                 assert_eq!(catan.current_turn, select_robber.player);
                 catan.inner.set_robber(select_robber.coord);
             },
+            GameMsg::PlayerStartSteal(candidates) => {
+                if catan.current_turn == catan.me {
+                    if candidates.is_empty() {
+                        next_state.set(CatanState::Menu);
+                    } else {
+                        catan.stealing_candidate = candidates;
+                        next_state.set(CatanState::Stealing);
+                    }
+                    break;
+                }
+            },
+            GameMsg::PlayerStealResource(steal) => {
+                // other players only learn that a card moved, not which
+                if steal.kind.is_some()
+                    || catan.players[steal.target].inner.resources_count() > 0
+                {
+                    catan.players[steal.target]
+                        .inner
+                        .remove_resource(steal.kind, 1);
+                    catan.players[steal.player]
+                        .inner
+                        .add_resource(steal.kind, 1);
+                }
+                if steal.player == catan.me {
+                    next_state.set(CatanState::Menu);
+                    break;
+                }
+            },
             GameMsg::PlayerTradeRequest((player, trade_req)) => {
+                catan.undo.clear();
                 if player != catan.me {
                     for offer in trade_req.from() {
                         trade.resource.offer[offer.0 as usize] = offer.1 as u8;
@@ -3110,6 +4398,11 @@ fn process_event(
                         }
                         trade.resource.want[want.0 as usize] = want.1 as u8;
                     }
+                    // one we could not afford was rejected above without asking
+                    alerts.send(CatanAlert {
+                        text: format!("{} offers a trade", catan.name(player)),
+                        cue: Cue::Trade,
+                    });
                     next_trade_state.set(TradeState::Accepting);
                     next_state.set(CatanState::Trade);
                     break;
@@ -3135,18 +4428,21 @@ fn process_event(
             },
             GameMsg::PlayerTrade(trade) => match trade {
                 Some(trade) => {
+                    catan.undo.clear();
                     for (kind, count) in trade.request.from() {
-                        catan.players[trade.from].inner.resources[*kind as usize] -=
-                            count;
+                        catan.players[trade.from]
+                            .inner
+                            .remove_resource(Some(*kind), *count);
                         if let Some(to) = trade.to {
-                            catan.players[to].inner.resources[*kind as usize] += count;
+                            catan.players[to].inner.add_resource(Some(*kind), *count);
                         }
                     }
                     for (kind, count) in trade.request.to() {
-                        catan.players[trade.from].inner.resources[*kind as usize] +=
-                            count;
+                        catan.players[trade.from]
+                            .inner
+                            .add_resource(Some(*kind), *count);
                         if let Some(to) = trade.to {
-                            catan.players[to].inner.resources[*kind as usize] -= count;
+                            catan.players[to].inner.remove_resource(Some(*kind), *count);
                         }
                     }
                     if catan.current_turn == catan.me {
@@ -3167,12 +4463,12 @@ fn process_event(
             },
             GameMsg::PlayerEndTurn(_) => {},
             GameMsg::PlayerOfferResources(offer) => {
-                catan.players[offer.player].inner.resources[offer.kind as usize] =
-                    (catan.players[offer.player].inner.resources[offer.kind as usize]
-                        as isize
-                        + offer.count)
-                        .min(20)
-                        .max(0) as usize;
+                let hand = &mut catan.players[offer.player].inner;
+                if offer.count < 0 {
+                    hand.remove_resource(Some(offer.kind), offer.count.unsigned_abs());
+                } else {
+                    hand.add_resource(Some(offer.kind), offer.count as usize);
+                }
             },
             GameMsg::PlayerStartSelectRobber() => {
                 if catan.current_turn == catan.me {
@@ -3181,11 +4477,59 @@ fn process_event(
             },
             GameMsg::PlayerDropResources((player, count)) => {
                 if player == catan.me {
+                    alerts.send(CatanAlert {
+                        text: format!("Discard {} resources", count),
+                        cue: Cue::Discard,
+                    });
                     catan.drop_cnt = count;
                     next_state.set(CatanState::DropResource);
                     break;
                 }
             },
+            GameMsg::BankResources(bank) => {
+                catan.bank = bank;
+            },
+            GameMsg::PlayerDiscarding(players) => {
+                catan.discarding = players;
+                if !catan.discarding.is_empty() {
+                    next_state.set(CatanState::Wait);
+                } else if catan.current_turn == catan.me {
+                    next_state.set(CatanState::Menu);
+                }
+            },
+            GameMsg::Checksum(expected) => {
+                let players = catan.players.iter().map(|p| &p.inner).collect::<Vec<_>>();
+                let local = checksum(&catan.inner, &catan.bank, &players, catan.me);
+                if local != expected {
+                    warn!(
+                        "state diverged from the server ({:x} != {:x}), asking for a resync",
+                        local, expected
+                    );
+                    action_writer.send(GameAct::Resync.into());
+                }
+            },
+            GameMsg::Resync(view) => {
+                let me = catan.me;
+                if catan.players[me].inner != view.start.players[me] {
+                    warn!(
+                        "hand was {:?}, the server has {:?}",
+                        catan.players[me].inner, view.start.players[me]
+                    );
+                }
+                if catan.bank != view.start.bank {
+                    warn!(
+                        "bank was {:?}, the server has {:?}",
+                        catan.bank, view.start.bank
+                    );
+                }
+                catan.inner = view.board();
+                catan.bank = view.start.bank;
+                for (player, inner) in catan.players.iter_mut().zip(view.start.players) {
+                    player.inner = inner;
+                }
+                catan.current_turn = view.current;
+                catan.road_hands.clear();
+            },
             _ => {
                 unreachable!("unexpected event")
             },
@@ -3194,182 +4538,270 @@ fn process_event(
 }
 
 fn process_action(
-    mut action_reader: ConsumableEventReader<GameAction>, client: ResMut<NetworkClt>,
+    mut action_reader: ConsumableEventReader<GameAction>,
+    mut output: EventWriter<CatanOutput>,
 ) {
     for action in action_reader.read() {
         info!("action: {:?}", action.deref());
-        client.send(ClientMsg::Catan(action.consume().into()));
+        output.send(CatanOutput(action.consume().into()));
     }
 }
 
-fn client_process_event(
-    mut client: ResMut<NetworkClt>, mut next_state: ResMut<NextState<CatanLoadState>>,
-    mut event_writer: ConsumableEventWriter<GameEvent>,
+fn process_input(
+    mut input: EventReader<CatanInput>, state: Res<State<CatanLoadState>>,
+    mut next_state: ResMut<NextState<CatanLoadState>>,
+    mut event_writer: ConsumableEventWriter<GameEvent>, mut rejection: ResMut<Rejection>,
 ) {
-    while let Some(client_event) = client.try_next() {
-        match client_event {
-            NetworkClientEvent::Report(connection_report) => match connection_report {
-                bevy_simplenet::ClientReport::Connected => {
+    for input in input.read() {
+        match input {
+            CatanInput::Connected => {
+                if *state.get() == CatanLoadState::Connecting {
                     next_state.set(CatanLoadState::Loading);
-                },
-                bevy_simplenet::ClientReport::Disconnected
-                | bevy_simplenet::ClientReport::ClosedByServer(_)
-                | bevy_simplenet::ClientReport::ClosedBySelf => {
+                }
+            },
+            CatanInput::Disconnected => {
+                if *state.get() != CatanLoadState::Rejected {
                     next_state.set(CatanLoadState::Connecting);
-                },
-                bevy_simplenet::ClientReport::IsDead(aborted_reqs) => {
-                    info!("client dead: {:?}", aborted_reqs);
-                    panic!("client dead");
-                },
+                }
             },
-            NetworkClientEvent::Msg(message) => match message {
-                boardgame_common::network::ServerMsg::Catan(msg) => {
-                    event_writer.send(msg.into());
-                },
-                _ => {
-                    info!("unexpected message: {:?}", message);
-                },
+            CatanInput::Rejected(reason) => {
+                rejection.0 = reason.clone();
+                next_state.set(CatanLoadState::Rejected);
+            },
+            CatanInput::Message(msg) => {
+                event_writer.send(msg.clone().into());
             },
-            _ => continue,
         }
     }
 }
 
-pub fn catan_run() {
-    App::new()
-        .add_persistent_consumable_event::<GameEvent>()
-        .add_persistent_consumable_event::<GameAction>()
-        .init_state::<CatanState>()
-        .init_state::<CatanLoadState>()
-        .init_state::<TradeState>()
-        .init_state::<UseCardState>()
-        .init_resource::<Events<GameEvent>>()
-        .insert_resource(AssetMetaCheck::Never)
-        .insert_resource(
-            #[cfg(target_family = "wasm")]
-            {
-                Platform {
-                    asset_srv_addr: "http://boardgame.studio:9000/assets".to_string(),
-                }
-            },
-            #[cfg(not(target_family = "wasm"))]
-            {
-                Platform {}
-            },
-        )
-        .insert_resource(NetworkClt::from(new_client()))
-        .insert_resource(ImageStore::default())
-        .insert_resource(TradeBoard::default())
-        .insert_resource(DropBoard::default())
-        .insert_resource(OperationMenu([
-            OperationEntry {
-                operation: Operation::BuildSettlement,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::BuildCity,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::BuildRoad,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::Trade,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::BuyCard,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::UseCard,
-                ..default()
-            },
-            OperationEntry {
-                operation: Operation::EndTurn,
+// everything spawned for the game, gone when leaving it
+#[derive(Component)]
+struct CatanScreen;
+
+fn enter_catan(mut commands: Commands, config: Res<CatanConfig>) {
+    commands.insert_resource(TradeBoard::default());
+    commands.insert_resource(DropBoard::default());
+    commands.spawn((
+        CatanScreen,
+        HudCamera,
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
                 ..default()
             },
-        ]))
-        .add_plugins((
-            #[cfg(target_family = "wasm")]
-            WebAssetPlugin::default(),
-            WindowResizePlugin,
-            CameraPlugin,
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resolution: WindowResolution::new(1000., 1000.)
-                        .with_scale_factor_override(1.0),
-                    title: "Catan".to_string(),
+            ..default()
+        },
+        RenderLayers::layer(config.hud_layer),
+    ));
+}
+
+fn leave_catan(
+    mut commands: Commands, screens: Query<Entity, With<CatanScreen>>,
+    mut cameras: Query<&mut Transform, (With<Camera2d>, Without<HudCamera>)>,
+    mut events: ConsumableEventReader<GameEvent>,
+    mut actions: ConsumableEventReader<GameAction>,
+    mut load_state: ResMut<NextState<CatanLoadState>>,
+    mut catan_state: ResMut<NextState<CatanState>>,
+    mut trade_state: ResMut<NextState<TradeState>>,
+    mut card_state: ResMut<NextState<UseCardState>>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the other screens share the camera the board was zoomed with
+    for mut camera in cameras.iter_mut() {
+        camera.translation.x = 0.;
+        camera.translation.y = 0.;
+        camera.scale = Vec3::ONE;
+    }
+    commands.insert_resource(Rejection::default());
+    commands.insert_resource(History::default());
+    for event in events.read() {
+        event.consume();
+    }
+    for action in actions.read() {
+        action.consume();
+    }
+    commands.remove_resource::<Catan>();
+    load_state.set(CatanLoadState::default());
+    catan_state.set(CatanState::default());
+    trade_state.set(TradeState::default());
+    card_state.set(UseCardState::default());
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct CatanSystems;
+
+// runs while the app is in the given state, and starts over on every entry
+pub struct CatanPlugin<S> {
+    pub state: S,
+}
+
+impl<S: States> Plugin for CatanPlugin<S> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CatanInput>()
+            .add_event::<CatanOutput>()
+            .add_event::<CatanAlert>()
+            .init_resource::<CatanConfig>()
+            .init_resource::<Rejection>()
+            .init_resource::<History>()
+            .init_resource::<CatanKeys>()
+            .init_resource::<Focus>()
+            .init_resource::<Layout>()
+            .init_resource::<Gesture>()
+            .init_resource::<Animations>()
+            .add_persistent_consumable_event::<GameEvent>()
+            .add_persistent_consumable_event::<GameAction>()
+            .init_state::<CatanState>()
+            .init_state::<CatanLoadState>()
+            .init_state::<TradeState>()
+            .init_state::<UseCardState>()
+            .init_resource::<Events<GameEvent>>()
+            .insert_resource(ImageStore::default())
+            .insert_resource(TradeBoard::default())
+            .insert_resource(DropBoard::default())
+            .insert_resource(OperationMenu([
+                OperationEntry {
+                    operation: Operation::BuildSettlement,
                     ..default()
-                }),
-                ..default()
-            }),
-        ))
-        .add_plugins(bevy_framepace::FramepacePlugin)
-        .add_plugins(Shape2dPlugin::default())
-        .add_systems(Startup, limit_frame)
-        .add_systems(Startup, load_img)
-        .add_systems(Update, client_process_event)
-        .add_systems(Update, loading.run_if(in_state(CatanLoadState::Loading)))
-        .add_systems(
-            Update,
-            intialize_game.run_if(in_state(CatanLoadState::Initialzing)),
-        )
-        .add_systems(
-            Update,
-            (
-                process_action,
-                (
-                    process_event.run_if(in_state(CatanState::Menu)),
-                    process_event.run_if(in_state(CatanState::Wait)),
-                    process_event.run_if(in_state(CatanState::Trade)),
-                ),
-                check_init_settlement.run_if(in_state(CatanState::InitSettlement)),
-                check_init_road.run_if(in_state(CatanState::InitRoad)),
-                check_build_road.run_if(in_state(CatanState::BuildRoad)),
-                check_build_settlement.run_if(in_state(CatanState::BuidSettlement)),
-                check_build_city.run_if(in_state(CatanState::BuildCity)),
-                check_select_robber.run_if(in_state(CatanState::SelectRobber)),
-                check_steal_target.run_if(in_state(CatanState::Stealing)),
-                draw_board,
-                draw_player_board,
-                draw_resource,
-                (draw_drop_resource, check_drop_click)
-                    .run_if(in_state(CatanState::DropResource)),
-                draw_steal_target.run_if(in_state(CatanState::Stealing)),
-                (draw_menu, check_menu_click)
-                    .run_if(not(in_state(CatanState::Wait)))
-                    .run_if(not(in_state(CatanState::InitRoad)))
-                    .run_if(not(in_state(CatanState::InitSettlement)))
-                    .run_if(not(in_state(CatanState::SelectRobber)))
-                    .run_if(not(in_state(CatanState::Stealing)))
-                    .run_if(not(in_state(CatanState::DropResource))),
-                (
-                    draw_trade,
-                    check_trade_offering_click.run_if(in_state(TradeState::Offering)),
-                    check_trade_accepting_click.run_if(in_state(TradeState::Accepting)),
-                    check_trade_confirm_click.run_if(in_state(TradeState::Confirming)),
-                )
-                    .run_if(in_state(CatanState::Trade)),
+                },
+                OperationEntry {
+                    operation: Operation::BuildCity,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::BuildRoad,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::Trade,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::BuyCard,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::UseCard,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::EndTurn,
+                    ..default()
+                },
+                OperationEntry {
+                    operation: Operation::Undo,
+                    ..default()
+                },
+            ]))
+            .add_plugins(Shape2dPlugin::default())
+            .configure_sets(Update, CatanSystems.run_if(in_state(self.state.clone())))
+            .add_systems(OnEnter(self.state.clone()), (enter_catan, load_img))
+            .add_systems(OnExit(self.state.clone()), leave_catan)
+            .add_systems(Update, process_input.in_set(CatanSystems))
+            .add_systems(Update, update_layout.in_set(CatanSystems))
+            .add_systems(
+                Update,
+                loading
+                    .run_if(in_state(CatanLoadState::Loading))
+                    .in_set(CatanSystems),
+            )
+            .add_systems(OnEnter(CatanLoadState::Rejected), show_rejection)
+            .add_systems(
+                OnEnter(CatanLoadState::Loaded),
+                (spawn_history, spawn_alert),
+            )
+            .add_systems(
+                Update,
+                intialize_game
+                    .run_if(in_state(CatanLoadState::Initialzing))
+                    .in_set(CatanSystems),
+            )
+            .add_systems(
+                Update,
+                update_view
+                    .after(update_layout)
+                    .run_if(in_state(CatanLoadState::Loaded))
+                    .in_set(CatanSystems),
+            )
+            .add_systems(
+                Update,
+                update_focus
+                    .after(update_view)
+                    .run_if(in_state(CatanLoadState::Loaded))
+                    .in_set(CatanSystems),
+            )
+            .add_systems(
+                Update,
                 (
-                    (draw_development_card, check_development_card_click)
-                        .run_if(in_state(UseCardState::SelectCard)),
-                    (draw_monopoly, check_monopoly_click)
-                        .run_if(in_state(UseCardState::Monopoly)),
-                    (draw_year_of_plenty, check_year_of_plenty_click)
-                        .run_if(in_state(UseCardState::YearOfPlenty)),
-                    check_knight_select_robber.run_if(in_state(UseCardState::Knight)),
-                    check_knight_steal_target
-                        .run_if(in_state(UseCardState::KnightStealing)),
-                    check_road_building_build_road
-                        .run_if(in_state(UseCardState::RoadBuilding)),
+                    process_action,
+                    (
+                        process_event.run_if(in_state(CatanState::Menu)),
+                        process_event.run_if(in_state(CatanState::Wait)),
+                        process_event.run_if(in_state(CatanState::Trade)),
+                    ),
+                    check_init_settlement.run_if(in_state(CatanState::InitSettlement)),
+                    check_init_road.run_if(in_state(CatanState::InitRoad)),
+                    check_build_road.run_if(in_state(CatanState::BuildRoad)),
+                    check_build_settlement.run_if(in_state(CatanState::BuidSettlement)),
+                    check_build_city.run_if(in_state(CatanState::BuildCity)),
+                    check_select_robber.run_if(in_state(CatanState::SelectRobber)),
+                    check_steal_target.run_if(in_state(CatanState::Stealing)),
+                    draw_board,
+                    draw_player_board,
+                    draw_resource,
+                    draw_bank,
+                    (draw_drop_resource, check_drop_click)
+                        .run_if(in_state(CatanState::DropResource)),
+                    draw_steal_target.run_if(in_state(CatanState::Stealing)),
+                    (draw_menu, check_menu_click)
+                        .run_if(not(in_state(CatanState::Wait)))
+                        .run_if(not(in_state(CatanState::InitRoad)))
+                        .run_if(not(in_state(CatanState::InitSettlement)))
+                        .run_if(not(in_state(CatanState::SelectRobber)))
+                        .run_if(not(in_state(CatanState::Stealing)))
+                        .run_if(not(in_state(CatanState::DropResource))),
+                    (
+                        draw_trade,
+                        check_trade_offering_click.run_if(in_state(TradeState::Offering)),
+                        check_trade_accepting_click
+                            .run_if(in_state(TradeState::Accepting)),
+                        check_trade_confirm_click
+                            .run_if(in_state(TradeState::Confirming)),
+                    )
+                        .run_if(in_state(CatanState::Trade)),
+                    (
+                        (draw_development_card, check_development_card_click)
+                            .run_if(in_state(UseCardState::SelectCard)),
+                        (draw_monopoly, check_monopoly_click)
+                            .run_if(in_state(UseCardState::Monopoly)),
+                        (draw_year_of_plenty, check_year_of_plenty_click)
+                            .run_if(in_state(UseCardState::YearOfPlenty)),
+                        check_knight_select_robber.run_if(in_state(UseCardState::Knight)),
+                        check_road_building_build_road
+                            .run_if(in_state(UseCardState::RoadBuilding)),
+                    )
+                        .run_if(in_state(CatanState::UseDevelopmentCard)),
+                    (
+                        update_player_text,
+                        update_history,
+                        scroll_history,
+                        show_alert,
+                    ),
+                    (draw_focus, check_cancel),
                 )
-                    .run_if(in_state(CatanState::UseDevelopmentCard)),
-                update_player_text,
-                change_state,
+                    .after(update_focus)
+                    .run_if(in_state(CatanLoadState::Loaded))
+                    .in_set(CatanSystems),
             )
-                .run_if(in_state(CatanLoadState::Loaded)),
-        )
-        .run();
+            .add_systems(
+                Update,
+                (play_animation, draw_animation)
+                    .after(update_focus)
+                    .run_if(in_state(CatanLoadState::Loaded))
+                    .in_set(CatanSystems),
+            );
+    }
 }
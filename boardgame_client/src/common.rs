@@ -1,5 +1,79 @@
-use bevy::prelude::*;
-use boardgame_common::network::NetworkClient;
+use bevy::{audio::Volume, prelude::*};
+use boardgame_common::{
+    catan,
+    codec::Encoding,
+    network::{
+        new_client_at, ClientMsg, Handshake, NetworkClient, NetworkClientEvent,
+        ServerMsg, DEFAULT_SERVER,
+    },
+    snake,
+};
+
+#[cfg(target_family = "wasm")]
+use crate::catan::CatanAlert;
+use crate::catan::{CatanInput, CatanOutput};
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+pub(crate) enum AppState {
+    #[default]
+    Menu,
+    Catan,
+    Snake,
+}
+
+impl AppState {
+    // the name the server hosts the game under
+    pub fn game(&self) -> Option<&'static str> {
+        match self {
+            AppState::Menu => None,
+            AppState::Catan => Some(catan::NAME),
+            AppState::Snake => Some(snake::NAME),
+        }
+    }
+
+    pub fn from_game(name: &str) -> Option<Self> {
+        match name {
+            catan::NAME => Some(AppState::Catan),
+            snake::NAME => Some(AppState::Snake),
+            _ => None,
+        }
+    }
+}
+
+// where the game runs, picked in the menu together with the game
+#[derive(Resource, Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub(crate) enum Host {
+    #[default]
+    Server,
+    // in this process against bots, native only
+    Local,
+}
+
+pub(crate) const SERVERS: [(&str, &str); 2] = [
+    ("boardgame.studio", DEFAULT_SERVER),
+    ("localhost", "ws://127.0.0.1:9001/ws"),
+];
+pub(crate) const FRAME_RATES: [f64; 3] = [10.0, 30.0, 60.0];
+pub(crate) const VOLUMES: [f32; 4] = [1.0, 0.7, 0.4, 0.1];
+
+// picked in the menu, the first three index the lists above
+#[derive(Resource, Default)]
+pub(crate) struct Settings {
+    pub server: usize,
+    pub frame_rate: usize,
+    pub volume: usize,
+    pub muted: bool,
+}
+
+impl Settings {
+    pub fn playback_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            VOLUMES[self.volume]
+        }
+    }
+}
 
 pub(crate) struct CameraPlugin;
 
@@ -57,6 +131,229 @@ impl std::ops::DerefMut for NetworkClt {
     }
 }
 
+// skips the menu, from the page query or the first argument when native
+pub(crate) fn selected_game() -> Option<String> {
+    launch_option("game=", 1)
+}
+
+// the rule preset asked for when joining, the server picks one otherwise
+fn selected_preset() -> Option<String> {
+    launch_option("rules=", 2)
+}
+
+#[cfg(target_family = "wasm")]
+fn launch_option(key: &str, _arg: usize) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(key))
+        .map(|value| value.to_string())
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn launch_option(_key: &str, arg: usize) -> Option<String> {
+    std::env::args().nth(arg)
+}
+
+// what the server told us so far, shared by every game
+#[derive(Resource, Default)]
+pub(crate) struct ServerLink {
+    pub server: usize,
+    pub connected: bool,
+    pub welcome: Option<Handshake>,
+    pub rejected: Option<String>,
+    // sent once per connection, the server hands the seat back on disconnect
+    pub joined: Option<&'static str>,
+}
+
+impl ServerLink {
+    pub fn encoding(&self) -> Encoding {
+        match &self.welcome {
+            Some(welcome) => welcome.encoding(),
+            None => Encoding::Plain,
+        }
+    }
+}
+
+// every message from the server, games pick the ones they understand
+#[derive(Event, Debug, Clone)]
+pub(crate) struct ServerEvent(pub ServerMsg);
+
+pub(crate) struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerLink>()
+            .init_resource::<Host>()
+            .add_event::<ServerEvent>()
+            .add_systems(Startup, connect)
+            .add_systems(PreUpdate, (apply_settings, poll_network, join_game).chain())
+            .add_systems(
+                Update,
+                bridge_catan
+                    .run_if(in_state(AppState::Catan))
+                    .run_if(resource_equals(Host::Server)),
+            )
+            .add_systems(OnEnter(AppState::Menu), leave_game);
+    }
+}
+
+fn reconnect(
+    commands: &mut Commands, client: Option<Res<NetworkClt>>, server: usize,
+) -> ServerLink {
+    if let Some(client) = client {
+        client.close();
+    }
+    commands.insert_resource(NetworkClt::from(new_client_at(SERVERS[server].1)));
+    ServerLink {
+        server,
+        ..default()
+    }
+}
+
+fn connect(
+    mut commands: Commands, settings: Res<Settings>, mut link: ResMut<ServerLink>,
+) {
+    *link = reconnect(&mut commands, None, settings.server);
+}
+
+fn apply_settings(
+    mut commands: Commands, settings: Res<Settings>, client: Option<Res<NetworkClt>>,
+    mut link: ResMut<ServerLink>,
+    mut framepace: ResMut<bevy_framepace::FramepaceSettings>,
+    mut global_volume: ResMut<GlobalVolume>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    framepace.limiter =
+        bevy_framepace::Limiter::from_framerate(FRAME_RATES[settings.frame_rate]);
+    // sounds already playing keep the volume they started with
+    global_volume.volume = Volume::new(settings.playback_volume());
+    if settings.server != link.server {
+        *link = reconnect(&mut commands, client, settings.server);
+    }
+}
+
+// back in the menu, a fresh connection frees the seat of the last game
+fn leave_game(
+    mut commands: Commands, client: Option<Res<NetworkClt>>, mut link: ResMut<ServerLink>,
+) {
+    if link.joined.is_some() {
+        let server = link.server;
+        *link = reconnect(&mut commands, client, server);
+    }
+}
+
+fn poll_network(
+    client: Option<ResMut<NetworkClt>>, mut link: ResMut<ServerLink>,
+    mut events: EventWriter<ServerEvent>,
+) {
+    // replaced this frame, the new client shows up next frame
+    let Some(mut client) = client else {
+        return;
+    };
+    while let Some(client_event) = client.try_next() {
+        match client_event {
+            NetworkClientEvent::Report(connection_report) => match connection_report {
+                bevy_simplenet::ClientReport::Connected => {
+                    link.connected = true;
+                },
+                bevy_simplenet::ClientReport::Disconnected
+                | bevy_simplenet::ClientReport::ClosedByServer(_)
+                | bevy_simplenet::ClientReport::ClosedBySelf => {
+                    link.connected = false;
+                    link.welcome = None;
+                    link.joined = None;
+                },
+                bevy_simplenet::ClientReport::IsDead(aborted_reqs) => {
+                    info!("client dead: {:?}", aborted_reqs);
+                    panic!("client dead");
+                },
+            },
+            NetworkClientEvent::Msg(message) => {
+                match &message {
+                    ServerMsg::Welcome(welcome) => {
+                        info!(
+                            "server protocol {}, {:?}",
+                            welcome.version, welcome.features
+                        );
+                        link.welcome = Some(welcome.clone());
+                    },
+                    ServerMsg::Rejected(reason) => {
+                        info!("rejected by server: {}", reason);
+                        link.rejected = Some(reason.clone());
+                    },
+                    _ => {},
+                }
+                events.send(ServerEvent(message));
+            },
+            _ => continue,
+        }
+    }
+}
+
+fn join_game(
+    client: Option<Res<NetworkClt>>, state: Res<State<AppState>>, host: Res<Host>,
+    mut link: ResMut<ServerLink>,
+) {
+    let (Some(client), Some(game)) = (client, state.get().game()) else {
+        return;
+    };
+    if *host == Host::Local {
+        return;
+    }
+    if link.welcome.is_some() && link.joined.is_none() {
+        client.send(ClientMsg::JoinWith((game.to_owned(), selected_preset())));
+        link.joined = Some(game);
+    }
+}
+
+// the catan plugin knows nothing about the network, this feeds it
+fn bridge_catan(
+    client: Option<Res<NetworkClt>>, link: Res<ServerLink>,
+    mut server_events: EventReader<ServerEvent>, mut input: EventWriter<CatanInput>,
+    mut output: EventReader<CatanOutput>,
+) {
+    if link.is_changed() {
+        match (link.connected, link.joined) {
+            (true, Some(catan::NAME)) => {
+                input.send(CatanInput::Connected);
+            },
+            (false, _) => {
+                input.send(CatanInput::Disconnected);
+            },
+            _ => {},
+        }
+    }
+    for ServerEvent(message) in server_events.read() {
+        match message {
+            ServerMsg::Catan(msg) => {
+                input.send(CatanInput::Message(msg.clone()));
+            },
+            ServerMsg::Compact(msg) => match msg.clone().unpack() {
+                Ok(msg) => {
+                    input.send(CatanInput::Message(msg));
+                },
+                Err(err) => {
+                    info!("invalid compact message: {}", err);
+                },
+            },
+            ServerMsg::Rejected(reason) => {
+                input.send(CatanInput::Rejected(reason.clone()));
+            },
+            _ => continue,
+        }
+    }
+    let Some(client) = client else {
+        return;
+    };
+    for CatanOutput(act) in output.read() {
+        client.send(ClientMsg::encode(act.clone(), link.encoding()));
+    }
+}
+
 pub struct WindowResizePlugin;
 
 impl Plugin for WindowResizePlugin {
@@ -101,3 +398,67 @@ fn handle_browser_resize(
         }
     }
 }
+
+// a game waiting on the player in a background tab blinks the tab title
+pub struct TitleFlashPlugin;
+
+impl Plugin for TitleFlashPlugin {
+    #[cfg(target_family = "wasm")]
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, flash_title);
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn build(&self, _app: &mut App) {}
+}
+
+#[cfg(target_family = "wasm")]
+struct TitleFlash {
+    // the page title to put back, set while flashing
+    title: Option<String>,
+    text: String,
+    shown: bool,
+    timer: Timer,
+}
+
+#[cfg(target_family = "wasm")]
+impl Default for TitleFlash {
+    fn default() -> Self {
+        Self {
+            title: None,
+            text: String::new(),
+            shown: false,
+            timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn flash_title(
+    mut alerts: EventReader<CatanAlert>, time: Res<Time>, mut flash: Local<TitleFlash>,
+) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    if let Some(alert) = alerts.read().last() {
+        if document.hidden() {
+            if flash.title.is_none() {
+                flash.title = Some(document.title());
+            }
+            flash.text = alert.text.clone();
+        }
+    }
+    let Some(title) = flash.title.clone() else {
+        return;
+    };
+    if !document.hidden() {
+        document.set_title(&title);
+        flash.title = None;
+        return;
+    }
+    if flash.timer.tick(time.delta()).just_finished() {
+        flash.shown = !flash.shown;
+        let shown = if flash.shown { &flash.text } else { &title };
+        document.set_title(shown);
+    }
+}
@@ -1,15 +1,21 @@
-use std::time::Duration;
+use bevy::prelude::*;
+use boardgame_common::{
+    network::{ClientMsg, ServerMsg},
+    snake::element::{Direction, SnakeAct, SnakeBoard, SnakeMsg},
+};
 
-use bevy::{prelude::*, time::common_conditions::on_timer, window::WindowResolution};
-use rand::random;
-
-use crate::common::{CameraPlugin, WindowResizePlugin};
+use crate::common::{AppState, NetworkClt, ServerEvent, ServerLink};
 
+// by seat, the own snake is drawn with the head color on top
+const SNAKE_COLORS: [Color; 4] = [
+    Color::rgb(0.2, 0.7, 0.2),
+    Color::rgb(0.2, 0.4, 0.9),
+    Color::rgb(0.9, 0.6, 0.1),
+    Color::rgb(0.7, 0.2, 0.7),
+];
+const COLOR_NAMES: [&str; 4] = ["green", "blue", "orange", "purple"];
 const SNAKE_HEAD_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
 const FOOD_COLOR: Color = Color::rgb(1.0, 0.0, 1.0);
-const SNAKE_SEGMENT_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
-const ARENA_WIDTH: u32 = 10;
-const ARENA_HEIGHT: u32 = 10;
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
 struct Position {
@@ -17,25 +23,6 @@ struct Position {
     y: i32,
 }
 
-#[derive(PartialEq, Copy, Clone)]
-enum Direction {
-    Left,
-    Up,
-    Right,
-    Down,
-}
-
-impl Direction {
-    fn opposite(self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
-        }
-    }
-}
-
 #[derive(Component)]
 struct Size {
     width: f32,
@@ -50,175 +37,189 @@ impl Size {
     }
 }
 
+// everything drawn from the last board, respawned when a new one arrives
 #[derive(Component)]
-struct SnakeHead {
-    direction: Option<Direction>,
-}
+struct Cell;
 
 #[derive(Component)]
-struct SnakeSegment;
+struct StatusText;
 
+// the server owns the game, the client only shows its latest board
 #[derive(Default, Resource)]
-struct LastTailPosition(Option<Position>);
-
-#[derive(Default, Resource)]
-struct SnakeSegments(Vec<Entity>);
-
-fn spawn_segment(commands: &mut Commands, position: Position) -> Entity {
-    commands
-        .spawn((
-            SnakeSegment,
-            position,
-            Size::square(0.65),
-            SpriteBundle {
-                sprite: Sprite {
-                    color: SNAKE_SEGMENT_COLOR,
-                    ..default()
-                },
-                ..default()
-            },
-        ))
-        .id()
+struct SnakeView {
+    seat: Option<usize>,
+    board: Option<SnakeBoard>,
+    status: String,
+    changed: bool,
 }
 
-fn spawn_snake(mut commands: Commands, mut snake: ResMut<SnakeSegments>) {
-    *snake = SnakeSegments(vec![
-        commands
-            .spawn((
-                SnakeHead { direction: None },
-                SnakeSegment,
-                Position { x: 3, y: 3 },
-                Size::square(0.8),
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: SNAKE_HEAD_COLOR,
-                        ..default()
-                    },
-                    transform: Transform {
-                        scale: Vec3::new(10.0, 10.0, 10.0),
-                        ..default()
-                    },
-                    ..default()
-                },
-            ))
-            .id(),
-        spawn_segment(&mut commands, Position { x: 3, y: 2 }),
-    ]);
-}
-
-fn snake_movement(
-    snake: Res<SnakeSegments>, mut tail: ResMut<LastTailPosition>,
-    mut positions: Query<&mut Position, With<SnakeSegment>>,
-    mut heads: Query<(Entity, &SnakeHead)>,
-) {
-    let mut tail_pos = None;
-    for head in heads.iter_mut() {
-        for id in snake.0.iter() {
-            let mut pos = positions.get_mut(*id).unwrap();
-
-            if *id == head.0 {
-                if let Some(dir) = head.1.direction {
-                    tail_pos = Some(*pos);
-                    match dir {
-                        Direction::Left => pos.x -= 1,
-                        Direction::Right => pos.x += 1,
-                        Direction::Down => pos.y -= 1,
-                        Direction::Up => pos.y += 1,
-                    }
-                }
-            } else if let Some(tail) = tail_pos {
-                let tmp = *pos;
-                *pos = tail;
-                tail_pos = Some(tmp);
-            }
+impl SnakeView {
+    fn arena(&self) -> (f32, f32) {
+        match &self.board {
+            Some(board) => (board.width as f32, board.height as f32),
+            None => (1.0, 1.0),
         }
-        tail.0 = tail_pos;
     }
-}
-
-fn snake_movement_input(
-    keyboard_input: Res<ButtonInput<KeyCode>>, mut heads: Query<&mut SnakeHead>,
-) {
-    for mut head in heads.iter_mut() {
-        let dir = if keyboard_input.pressed(KeyCode::ArrowLeft) {
-            Direction::Left
-        } else if keyboard_input.pressed(KeyCode::ArrowRight) {
-            Direction::Right
-        } else if keyboard_input.pressed(KeyCode::ArrowDown) {
-            Direction::Down
-        } else if keyboard_input.pressed(KeyCode::ArrowUp) {
-            Direction::Up
-        } else {
-            return;
-        };
 
-        match head.direction {
-            Some(d) if d == dir.opposite() => return,
-            _ => head.direction = Some(dir),
-        }
+    fn set_status(&mut self, status: String) {
+        self.status = status;
+        self.changed = true;
     }
 }
 
-#[derive(Event)]
-struct GrowthEvent;
-
-fn snake_eating(
-    mut commands: Commands, mut growth_tx: EventWriter<GrowthEvent>,
-    food_pos: Query<(Entity, &Position), With<Food>>,
-    head_pos: Query<&Position, With<SnakeHead>>,
+fn client_process_event(
+    mut server_events: EventReader<ServerEvent>, link: Res<ServerLink>,
+    mut view: ResMut<SnakeView>,
 ) {
-    for food in food_pos.iter() {
-        for head in head_pos.iter() {
-            if food.1 == head {
-                commands.entity(food.0).despawn();
-                growth_tx.send(GrowthEvent);
-            }
+    if link.is_changed() && view.seat.is_none() {
+        let status = match (link.connected, &link.rejected) {
+            (_, Some(reason)) => reason.clone(),
+            (true, None) => "Waiting for players".to_string(),
+            (false, None) => "Connecting".to_string(),
+        };
+        view.set_status(status);
+    }
+    for ServerEvent(message) in server_events.read() {
+        let ServerMsg::Snake(message) = message else {
+            continue;
+        };
+        match message.clone() {
+            SnakeMsg::GameStart(seat, board) => {
+                view.seat = Some(seat);
+                view.board = Some(board);
+                view.set_status(format!("You are {}", COLOR_NAMES[seat]));
+            },
+            SnakeMsg::Board(board) => {
+                view.board = Some(board);
+                view.changed = true;
+            },
+            SnakeMsg::GameOver(winner) => {
+                let status = match winner {
+                    Some(winner) if Some(winner) == view.seat => "You won".to_string(),
+                    Some(winner) => format!("{} won", COLOR_NAMES[winner]),
+                    None => "Draw".to_string(),
+                };
+                view.set_status(format!("{}, Esc for the menu", status));
+            },
         }
     }
 }
 
-fn snake_growth(
-    mut commands: Commands, last_tail_position: Res<LastTailPosition>,
-    mut segments: ResMut<SnakeSegments>, mut growth_reader: EventReader<GrowthEvent>,
+fn snake_movement_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>, client: Res<NetworkClt>,
+    view: Res<SnakeView>,
 ) {
-    for _ in growth_reader.read() {
-        segments
-            .0
-            .push(spawn_segment(&mut commands, last_tail_position.0.unwrap()));
+    let (Some(seat), Some(board)) = (view.seat, &view.board) else {
+        return;
+    };
+    if !board.snakes[seat].alive {
+        return;
     }
+    let dir = if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        Direction::Left
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        Direction::Right
+    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        Direction::Down
+    } else if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        Direction::Up
+    } else {
+        return;
+    };
+    // the server ignores turning back as well, this only saves the message
+    if dir == board.snakes[seat].direction.opposite() {
+        return;
+    }
+    client.send(ClientMsg::Snake(SnakeAct::Turn(dir)));
 }
 
-#[derive(Component)]
-struct Food;
-
-fn food_spawner(mut commands: Commands) {
+fn spawn_cell(commands: &mut Commands, x: usize, y: usize, size: f32, color: Color) {
     commands.spawn((
-        Food,
+        Cell,
         Position {
-            x: (random::<f32>() * ARENA_WIDTH as f32) as i32,
-            y: (random::<f32>() * ARENA_HEIGHT as f32) as i32,
+            x: x as i32,
+            y: y as i32,
         },
-        Size::square(0.8),
+        Size::square(size),
         SpriteBundle {
-            sprite: Sprite {
-                color: FOOD_COLOR,
-                ..default()
-            },
-            transform: Transform {
-                scale: Vec3::new(10.0, 10.0, 10.0),
+            sprite: Sprite { color, ..default() },
+            ..default()
+        },
+    ));
+}
+
+fn enter_snake(mut commands: Commands, mut view: ResMut<SnakeView>) {
+    *view = SnakeView {
+        status: "Connecting".to_string(),
+        changed: true,
+        ..default()
+    };
+    commands.insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)));
+    commands.spawn((
+        StatusText,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 24.0,
+                color: Color::WHITE,
                 ..default()
             },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
             ..default()
-        },
+        }),
     ));
 }
 
-fn size_scaling(windows: Query<&Window>, mut q: Query<(&Size, &mut Transform)>) {
+fn draw_board(
+    mut commands: Commands, mut view: ResMut<SnakeView>,
+    cells: Query<Entity, With<Cell>>, mut status: Query<&mut Text, With<StatusText>>,
+) {
+    if !view.changed {
+        return;
+    }
+    view.changed = false;
+    for mut text in status.iter_mut() {
+        text.sections[0].value = view.status.clone();
+    }
+    for cell in cells.iter() {
+        commands.entity(cell).despawn();
+    }
+    let Some(board) = &view.board else {
+        return;
+    };
+    for food in board.food.iter() {
+        spawn_cell(&mut commands, food.x, food.y, 0.8, FOOD_COLOR);
+    }
+    for (i, snake) in board.snakes.iter().enumerate() {
+        if !snake.alive {
+            continue;
+        }
+        let color = SNAKE_COLORS[i % SNAKE_COLORS.len()];
+        for segment in snake.body.iter().skip(1) {
+            spawn_cell(&mut commands, segment.x, segment.y, 0.65, color);
+        }
+        let head = snake.head();
+        let head_color = match view.seat == Some(i) {
+            true => SNAKE_HEAD_COLOR,
+            false => color,
+        };
+        spawn_cell(&mut commands, head.x, head.y, 0.8, head_color);
+    }
+}
+
+fn size_scaling(
+    windows: Query<&Window>, view: Res<SnakeView>, mut q: Query<(&Size, &mut Transform)>,
+) {
+    let (arena_width, arena_height) = view.arena();
     for window in windows.iter() {
         for (sprite_size, mut transform) in q.iter_mut() {
             transform.scale = Vec3::new(
-                sprite_size.width / ARENA_WIDTH as f32 * window.width() as f32,
-                sprite_size.height / ARENA_HEIGHT as f32 * window.height() as f32,
+                sprite_size.width / arena_width * window.width() as f32,
+                sprite_size.height / arena_height * window.height() as f32,
                 1.0,
             );
         }
@@ -227,17 +228,19 @@ fn size_scaling(windows: Query<&Window>, mut q: Query<(&Size, &mut Transform)>)
 }
 
 fn position_translation(
-    windows: Query<&Window>, mut q: Query<(&Position, &mut Transform)>,
+    windows: Query<&Window>, view: Res<SnakeView>,
+    mut q: Query<(&Position, &mut Transform)>,
 ) {
     fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
         let tile_size = bound_window / bound_game;
         pos / bound_game * bound_window - (bound_window / 2.) + (tile_size / 2.)
     }
+    let (arena_width, arena_height) = view.arena();
     for window in windows.iter() {
         for (pos, mut transform) in q.iter_mut() {
             transform.translation = Vec3::new(
-                convert(pos.x as f32, window.width() as f32, ARENA_WIDTH as f32),
-                convert(pos.y as f32, window.height() as f32, ARENA_HEIGHT as f32),
+                convert(pos.x as f32, window.width() as f32, arena_width),
+                convert(pos.y as f32, window.height() as f32, arena_height),
                 0.0,
             );
         }
@@ -245,40 +248,33 @@ fn position_translation(
     }
 }
 
-pub fn greedy_snake_run() {
-    App::new()
-        .add_event::<GrowthEvent>()
-        .insert_resource(SnakeSegments::default())
-        .insert_resource(LastTailPosition::default())
-        .add_plugins((
-            WindowResizePlugin,
-            CameraPlugin,
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    resolution: WindowResolution::new(500., 500.)
-                        .with_scale_factor_override(1.0),
-                    title: "Snake Game".to_string(),
-                    ..default()
-                }),
-                ..default()
-            }),
-        ))
-        .add_systems(Startup, spawn_snake)
-        .add_systems(
-            Update,
-            food_spawner.run_if(on_timer(Duration::from_secs_f32(1.))),
-        )
-        .add_systems(
-            Update,
-            (
-                snake_movement_input,
-                snake_movement.run_if(on_timer(Duration::from_secs_f32(1.))),
-                snake_eating.after(snake_movement),
-                snake_growth.after(snake_eating),
-            ),
-        )
-        .add_systems(PostUpdate, position_translation)
-        .add_systems(PostUpdate, size_scaling)
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.04)))
-        .run();
+fn leave_snake(
+    mut commands: Commands, screens: Query<Entity, Or<(With<Cell>, With<StatusText>)>>,
+    mut view: ResMut<SnakeView>,
+) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn();
+    }
+    *view = SnakeView::default();
+    commands.insert_resource(ClearColor::default());
+}
+
+pub(crate) struct SnakePlugin;
+
+impl Plugin for SnakePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SnakeView::default())
+            .add_systems(OnEnter(AppState::Snake), enter_snake)
+            .add_systems(OnExit(AppState::Snake), leave_snake)
+            .add_systems(
+                Update,
+                (client_process_event, snake_movement_input, draw_board)
+                    .chain()
+                    .run_if(in_state(AppState::Snake)),
+            )
+            .add_systems(
+                PostUpdate,
+                (position_translation, size_scaling).run_if(in_state(AppState::Snake)),
+            );
+    }
 }
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use boardgame_common::{catan::rules::CatanRules, game::Game};
+use boardgame_server::{
+    bot::CatanBot,
+    game::{CatanBoard, CatanConfig, CatanGame},
+    local::{local_seat, LocalSeat},
+};
+use tokio::{runtime::Runtime, sync::mpsc::error::TryRecvError};
+
+use crate::{
+    catan::{CatanInput, CatanOutput},
+    common::{AppState, Host},
+};
+
+const BOTS: usize = 2;
+
+// the server engine running inside the client, we play the first seat
+#[derive(Resource)]
+struct LocalGame {
+    runtime: Runtime,
+    seat: LocalSeat<CatanGame>,
+}
+
+fn start_local_game(mut commands: Commands, mut input: EventWriter<CatanInput>) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_time()
+        .build()
+        .expect("failed to start the local game runtime");
+
+    let (player, seat) = local_seat::<CatanGame>("you");
+    let mut players = vec![player];
+    for i in 0..BOTS {
+        let (player, seat) = local_seat::<CatanGame>(&format!("bot {}", i + 1));
+        players.push(player);
+        runtime.spawn(CatanBot::default().play(seat));
+    }
+    let config = CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Balanced,
+    };
+    runtime.spawn(async move {
+        let outcome = CatanGame::run(players, config).await;
+        info!("local game over: {:?}", outcome);
+    });
+
+    commands.insert_resource(LocalGame { runtime, seat });
+    input.send(CatanInput::Connected);
+}
+
+fn bridge_local_game(
+    mut commands: Commands, game: Option<ResMut<LocalGame>>,
+    mut input: EventWriter<CatanInput>, mut output: EventReader<CatanOutput>,
+) {
+    let Some(mut game) = game else {
+        return;
+    };
+    for CatanOutput(act) in output.read() {
+        let _ = game.seat.actions.send(act.clone());
+    }
+    loop {
+        match game.seat.messages.try_recv() {
+            Ok(msg) => {
+                input.send(CatanInput::Message(msg));
+            },
+            Err(TryRecvError::Empty) => break,
+            // the game is over or the engine gave up on a seat
+            Err(TryRecvError::Disconnected) => {
+                input.send(CatanInput::Rejected("The local game has ended".to_string()));
+                commands.remove_resource::<LocalGame>();
+                break;
+            },
+        }
+    }
+}
+
+fn stop_local_game(world: &mut World) {
+    if let Some(game) = world.remove_resource::<LocalGame>() {
+        game.runtime.shutdown_background();
+    }
+}
+
+pub(crate) struct LocalPlugin;
+
+impl Plugin for LocalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::Catan),
+            start_local_game.run_if(resource_equals(Host::Local)),
+        )
+        .add_systems(
+            Update,
+            bridge_local_game
+                .run_if(in_state(AppState::Catan))
+                .run_if(resource_equals(Host::Local)),
+        )
+        .add_systems(OnExit(AppState::Catan), stop_local_game);
+    }
+}
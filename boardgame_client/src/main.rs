@@ -1,6 +1,19 @@
+use bevy::{asset::AssetMetaCheck, prelude::*, window::WindowResolution};
+#[cfg(target_family = "wasm")]
+use bevy_web_asset::WebAssetPlugin;
+
+use catan::{CatanConfig, CatanPlugin};
+use common::{
+    AppState, CameraPlugin, NetworkPlugin, Platform, Settings, TitleFlashPlugin,
+    WindowResizePlugin,
+};
+
 mod catan;
 mod common;
 mod greedy_snake;
+#[cfg(not(target_family = "wasm"))]
+mod local;
+mod menu;
 
 fn main() {
     #[cfg(target_family = "wasm")]
@@ -8,6 +21,58 @@ fn main() {
         console_error_panic_hook::set_once();
         // tracing_wasm::set_as_global_default();
     }
-    catan::catan_run();
-    // greedy_snake::greedy_snake_run();
+    let state = common::selected_game()
+        .and_then(|game| AppState::from_game(&game))
+        .unwrap_or_default();
+
+    let platform = {
+        #[cfg(target_family = "wasm")]
+        {
+            Platform {
+                asset_srv_addr: "http://boardgame.studio:9000/assets".to_string(),
+            }
+        }
+        #[cfg(not(target_family = "wasm"))]
+        {
+            Platform {}
+        }
+    };
+
+    App::new()
+        .insert_state(state)
+        .insert_resource(AssetMetaCheck::Never)
+        .insert_resource(CatanConfig {
+            asset_root: platform.load_asset("catan"),
+            ..default()
+        })
+        .insert_resource(platform)
+        .init_resource::<Settings>()
+        .add_plugins((
+            #[cfg(target_family = "wasm")]
+            WebAssetPlugin::default(),
+            WindowResizePlugin,
+            CameraPlugin,
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    // sizes are in logical pixels, the hud scales with the display
+                    resolution: WindowResolution::new(1000., 1000.),
+                    title: "Board Games".to_string(),
+                    ..default()
+                }),
+                ..default()
+            }),
+        ))
+        .add_plugins(bevy_framepace::FramepacePlugin)
+        .add_plugins((
+            NetworkPlugin,
+            TitleFlashPlugin,
+            menu::MenuPlugin,
+            CatanPlugin {
+                state: AppState::Catan,
+            },
+            greedy_snake::SnakePlugin,
+            #[cfg(not(target_family = "wasm"))]
+            local::LocalPlugin,
+        ))
+        .run();
 }
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+
+use crate::common::{
+    AppState, Host, ServerLink, Settings, FRAME_RATES, SERVERS, VOLUMES,
+};
+
+const BUTTON_COLOR: Color = Color::rgb(0.2, 0.5, 0.5);
+const BUTTON_HOVER_COLOR: Color = Color::rgb(0.3, 0.6, 0.6);
+
+#[derive(Component)]
+struct MenuScreen;
+
+#[derive(Component)]
+struct ConnectionText;
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Play(AppState, Host),
+    Server,
+    FrameRate,
+    Volume,
+    Sound,
+}
+
+impl MenuButton {
+    fn label(&self, settings: &Settings) -> String {
+        match self {
+            MenuButton::Play(AppState::Catan, Host::Server) => "Catan".to_string(),
+            MenuButton::Play(AppState::Catan, Host::Local) => "Catan vs bots".to_string(),
+            MenuButton::Play(AppState::Snake, _) => "Snake".to_string(),
+            MenuButton::Play(AppState::Menu, _) => unreachable!("menu is not a game"),
+            MenuButton::Server => format!("Server: {}", SERVERS[settings.server].0),
+            MenuButton::FrameRate => {
+                format!("Frame rate: {}", FRAME_RATES[settings.frame_rate])
+            },
+            MenuButton::Volume => {
+                format!("Volume: {}%", (VOLUMES[settings.volume] * 100.0).round())
+            },
+            MenuButton::Sound => match settings.muted {
+                true => "Sound: off".to_string(),
+                false => "Sound: on".to_string(),
+            },
+        }
+    }
+}
+
+fn text_style(font_size: f32) -> TextStyle {
+    TextStyle {
+        font_size,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: MenuButton, settings: &Settings) {
+    parent
+        .spawn((
+            button,
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(280.0),
+                    height: Val::Px(50.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BUTTON_COLOR.into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                button.label(settings),
+                text_style(24.0),
+            ));
+        });
+}
+
+fn spawn_menu(mut commands: Commands, settings: Res<Settings>) {
+    commands
+        .spawn((
+            MenuScreen,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Board Games", text_style(48.0)));
+            parent.spawn((
+                ConnectionText,
+                TextBundle::from_section("", text_style(20.0)),
+            ));
+            let mut games = vec![
+                (AppState::Catan, Host::Server),
+                (AppState::Snake, Host::Server),
+            ];
+            // the browser build has no runtime to host a game on
+            #[cfg(not(target_family = "wasm"))]
+            games.insert(1, (AppState::Catan, Host::Local));
+            for (game, host) in games {
+                spawn_button(parent, MenuButton::Play(game, host), &settings);
+            }
+            parent.spawn(TextBundle::from_section("Settings", text_style(32.0)));
+            spawn_button(parent, MenuButton::Server, &settings);
+            spawn_button(parent, MenuButton::FrameRate, &settings);
+            spawn_button(parent, MenuButton::Volume, &settings);
+            spawn_button(parent, MenuButton::Sound, &settings);
+        });
+}
+
+fn despawn_menu(mut commands: Commands, screens: Query<Entity, With<MenuScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn check_menu_click(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut settings: ResMut<Settings>, mut host: ResMut<Host>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::Play(game, game_host) => {
+                *host = *game_host;
+                next_state.set(*game);
+            },
+            MenuButton::Server => settings.server = (settings.server + 1) % SERVERS.len(),
+            MenuButton::FrameRate => {
+                settings.frame_rate = (settings.frame_rate + 1) % FRAME_RATES.len();
+            },
+            MenuButton::Volume => settings.volume = (settings.volume + 1) % VOLUMES.len(),
+            MenuButton::Sound => settings.muted = !settings.muted,
+        }
+    }
+}
+
+fn update_menu(
+    settings: Res<Settings>, link: Res<ServerLink>,
+    mut buttons: Query<(&Interaction, &MenuButton, &Children, &mut BackgroundColor)>,
+    mut connection: Query<&mut Text, With<ConnectionText>>,
+    mut labels: Query<&mut Text, Without<ConnectionText>>,
+) {
+    for (interaction, button, children, mut color) in buttons.iter_mut() {
+        *color = match interaction {
+            Interaction::None => BUTTON_COLOR.into(),
+            _ => BUTTON_HOVER_COLOR.into(),
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = labels.get_mut(*child) {
+                text.sections[0].value = button.label(&settings);
+            }
+        }
+    }
+
+    let server = SERVERS[settings.server].0;
+    let status = match (&link.rejected, link.connected) {
+        (Some(reason), _) => reason.clone(),
+        (None, true) => format!("Connected to {}", server),
+        (None, false) => format!("Connecting to {}", server),
+    };
+    for mut text in connection.iter_mut() {
+        text.sections[0].value = status.clone();
+    }
+}
+
+fn leave_on_escape(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(AppState::Menu);
+    }
+}
+
+pub(crate) struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Menu), spawn_menu)
+            .add_systems(OnExit(AppState::Menu), despawn_menu)
+            .add_systems(
+                Update,
+                (check_menu_click, update_menu).run_if(in_state(AppState::Menu)),
+            )
+            .add_systems(
+                Update,
+                leave_on_escape.run_if(not(in_state(AppState::Menu))),
+            );
+    }
+}
@@ -0,0 +1,47 @@
+// prints how many bytes each message of the recorded game takes on the wire,
+// plain and compact, run with `cargo run -p boardgame_common --example wire_sizes`
+use std::collections::BTreeMap;
+
+use bincode::Options;
+use boardgame_common::{catan::element::GameMsg, codec::Encoding, network::ServerMsg};
+
+// the transport encodes messages with the default bincode options
+fn wire_size(msg: &ServerMsg) -> usize {
+    bincode::DefaultOptions::new().serialize(msg).unwrap().len()
+}
+
+fn variant(msg: &GameMsg) -> String {
+    format!("{:?}", msg)
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap()
+        .to_owned()
+}
+
+fn main() {
+    let game: Vec<GameMsg> =
+        serde_json::from_str(include_str!("../fixtures/recorded_game.json")).unwrap();
+
+    let mut sizes: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+    for msg in game.iter() {
+        let entry = sizes.entry(variant(msg)).or_default();
+        entry.0 += 1;
+        entry.1 += wire_size(&ServerMsg::encode(msg.clone(), Encoding::Plain));
+        entry.2 += wire_size(&ServerMsg::encode(msg.clone(), Encoding::Compact));
+    }
+
+    println!(
+        "{:<28}{:>8}{:>10}{:>10}",
+        "message", "count", "plain", "compact"
+    );
+    for (name, (count, plain, compact)) in sizes.iter() {
+        println!("{:<28}{:>8}{:>10}{:>10}", name, count, plain, compact);
+    }
+    println!(
+        "{:<28}{:>8}{:>10}{:>10}",
+        "total",
+        game.len(),
+        sizes.values().map(|s| s.1).sum::<usize>(),
+        sizes.values().map(|s| s.2).sum::<usize>()
+    );
+}
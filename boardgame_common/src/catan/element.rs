@@ -175,6 +175,10 @@ impl CatanCommon {
         &self.roads
     }
 
+    pub fn points(&self) -> &Vec<Vec<Point>> {
+        &self.points
+    }
+
     pub fn set_robber(&mut self, coord: Coordinate) {
         self.robber = coord;
     }
@@ -200,12 +204,25 @@ impl CatanCommon {
         self.points[point.x][point.y].set_owner(player);
     }
 
+    pub fn remove_road(&mut self, road: Line) -> Option<usize> {
+        self.roads.remove(&road)
+    }
+
+    pub fn remove_settlement(&mut self, point: Coordinate) {
+        self.points[point.x][point.y] = Point::default();
+    }
+
+    // back to a settlement
+    pub fn remove_city(&mut self, point: Coordinate) {
+        self.points[point.x][point.y].city = false;
+    }
+
     pub fn tile_get_points(&self, coord: Coordinate) -> [Coordinate; 6] {
         let mut points = [Default::default(); 6];
         let x = coord.x;
         let y = coord.y;
 
-        if x % 2 == 0 {
+        if x.is_multiple_of(2) {
             points[0] = Coordinate::new(x, 2 * y);
             points[1] = Coordinate::new(x, 2 * y + 1);
             points[2] = Coordinate::new(x, 2 * y + 2);
@@ -228,83 +245,39 @@ impl CatanCommon {
         let x = coord.x;
         let y = coord.y;
 
-        if x % 2 == 0 && y % 2 == 0 {
-            points[0] = if y >= 1 {
-                Some(Coordinate::new(x, y - 1))
-            } else {
-                None
-            };
-            points[1] = if y < self.points[0].len() - 1 {
-                Some(Coordinate::new(x, y + 1))
-            } else {
-                None
-            };
+        points[0] = if y >= 1 {
+            Some(Coordinate::new(x, y - 1))
+        } else {
+            None
+        };
+        points[1] = if y < self.points[0].len() - 1 {
+            Some(Coordinate::new(x, y + 1))
+        } else {
+            None
+        };
+        // the third neighbour is in the next row when x and y are both even or
+        // both odd, in the row before otherwise
+        if x.is_multiple_of(2) == y.is_multiple_of(2) {
             points[2] = if x < self.points.len() - 1 {
                 Some(Coordinate::new(x + 1, y))
             } else {
                 None
             };
-        } else if x % 2 == 0 && y % 2 == 1 {
-            points[0] = if y >= 1 {
-                Some(Coordinate::new(x, y - 1))
-            } else {
-                None
-            };
-            points[1] = if y < self.points[0].len() - 1 {
-                Some(Coordinate::new(x, y + 1))
-            } else {
-                None
-            };
-            points[2] = if x >= 1 {
-                Some(Coordinate::new(x - 1, y))
-            } else {
-                None
-            };
-        } else if x % 2 == 1 && y % 2 == 0 {
-            points[0] = if y >= 1 {
-                Some(Coordinate::new(x, y - 1))
-            } else {
-                None
-            };
-            points[1] = if y < self.points[0].len() - 1 {
-                Some(Coordinate::new(x, y + 1))
-            } else {
-                None
-            };
+        } else {
             points[2] = if x >= 1 {
                 Some(Coordinate::new(x - 1, y))
             } else {
                 None
             };
-        } else {
-            points[0] = if y >= 1 {
-                Some(Coordinate::new(x, y - 1))
-            } else {
-                None
-            };
-            points[1] = if y < self.points[0].len() - 1 {
-                Some(Coordinate::new(x, y + 1))
-            } else {
-                None
-            };
-            points[2] = if x < self.points.len() - 1 {
-                Some(Coordinate::new(x + 1, y))
-            } else {
-                None
-            };
         }
         points
     }
 
     pub fn point_valid(&self, point: Coordinate) -> bool {
-        for p in self.ponint_get_tile(point) {
-            if let Some(p) = p {
-                if !self.tile(p).is_empty() {
-                    return true;
-                }
-            }
-        }
-        return false;
+        self.ponint_get_tile(point)
+            .into_iter()
+            .flatten()
+            .any(|p| !self.tile(p).is_empty())
     }
 
     pub fn ponint_get_tile(&self, coord: Coordinate) -> [Option<Coordinate>; 3] {
@@ -316,7 +289,7 @@ impl CatanCommon {
             return tiles;
         }
 
-        if y % 2 == 0 {
+        if y.is_multiple_of(2) {
             if x % 2 == 1 {
                 tiles[0] = if x >= 1 && y >= 2 {
                     Some(Coordinate::new(x - 1, (y - 2) / 2))
@@ -516,16 +489,20 @@ impl PlayerCommon {
                 continue;
             }
             visited[i] = true;
-            let length = 1 + if current.is_none() {
-                self.longest_road(visited, Some(self.roads[i].start))
-                    .max(self.longest_road(visited, Some(self.roads[i].end)))
-            } else if self.roads[i].end == current.unwrap() {
-                self.longest_road(visited, Some(self.roads[i].start))
-            } else if self.roads[i].start == current.unwrap() {
-                self.longest_road(visited, Some(self.roads[i].end))
-            } else {
-                visited[i] = false;
-                continue;
+            let length = 1 + match current {
+                None => self
+                    .longest_road(visited, Some(self.roads[i].start))
+                    .max(self.longest_road(visited, Some(self.roads[i].end))),
+                Some(current) if self.roads[i].end == current => {
+                    self.longest_road(visited, Some(self.roads[i].start))
+                },
+                Some(current) if self.roads[i].start == current => {
+                    self.longest_road(visited, Some(self.roads[i].end))
+                },
+                _ => {
+                    visited[i] = false;
+                    continue;
+                },
             };
 
             if length > longest {
@@ -587,6 +564,38 @@ impl PlayerCommon {
         self.cards.iter().sum()
     }
 
+    pub fn discard_count(&self, hand_limit: usize) -> usize {
+        let sum = self.resources_count();
+        if sum > hand_limit {
+            sum / 2
+        } else {
+            0
+        }
+    }
+
+    pub fn check_valid_drop(
+        &self, drop: &[(TileKind, usize)], count: usize,
+    ) -> Result<(), String> {
+        let mut total = [0; TileKind::Max as usize];
+        for (kind, cnt) in drop {
+            if *cnt == 0 {
+                continue;
+            }
+            if !kind.is_resource() {
+                return Err(format!("{:?} can not be dropped", kind));
+            }
+            total[*kind as usize] += cnt;
+            if self.resources[*kind as usize] < total[*kind as usize] {
+                return Err(format!("Not enough {:?} to drop", kind));
+            }
+        }
+
+        if total.iter().sum::<usize>() != count {
+            return Err(format!("Must drop exactly {} resources", count));
+        }
+        Ok(())
+    }
+
     pub fn add_card(&mut self, card: Option<DevCard>) {
         match card {
             Some(card) => self.cards[card as usize] += 1,
@@ -601,9 +610,35 @@ impl PlayerCommon {
         }
     }
 
+    // another player's hand keeps cards of unknown kind in the Empty slot, like
+    // cards[0] holds development cards nobody has seen
+    pub fn add_resource(&mut self, kind: Option<TileKind>, count: usize) {
+        self.resources[kind.unwrap_or(TileKind::Empty) as usize] += count;
+    }
+
+    // takes the known cards of the kind first, then cards of unknown kind
+    pub fn remove_resource(&mut self, kind: Option<TileKind>, count: usize) {
+        let mut left = count;
+        for slot in kind.into_iter().chain([TileKind::Empty]) {
+            let taken = self.resources[slot as usize].min(left);
+            self.resources[slot as usize] -= taken;
+            left -= taken;
+        }
+        if left > 0 {
+            // whatever was known about the rest of the hand no longer holds
+            let total = self.resources_count().saturating_sub(left);
+            self.resources = Default::default();
+            self.resources[TileKind::Empty as usize] = total;
+        }
+    }
+
     pub fn add_road(&mut self, road: Line) {
         self.roads.push(road);
     }
+
+    pub fn remove_road(&mut self, road: Line) {
+        self.roads.retain(|r| *r != road);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -691,6 +726,29 @@ pub struct BuildCity {
     pub point: Coordinate,
 }
 
+// a build the current player can still take back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Build {
+    Road(Line),
+    Settlement(Coordinate),
+    City(Coordinate),
+}
+
+impl Build {
+    pub fn cost(&self) -> &'static [(TileKind, usize)] {
+        match self {
+            Build::Road(_) => &[(TileKind::Brick, 1), (TileKind::Wood, 1)],
+            Build::Settlement(_) => &[
+                (TileKind::Brick, 1),
+                (TileKind::Grain, 1),
+                (TileKind::Wool, 1),
+                (TileKind::Wood, 1),
+            ],
+            Build::City(_) => &[(TileKind::Stone, 3), (TileKind::Grain, 2)],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BuyDevelopmentCard {
     pub player: usize,
@@ -723,10 +781,17 @@ pub struct Trade {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SelectRobber {
     pub player: usize,
-    pub target: Option<usize>,
     pub coord: Coordinate,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StealResource {
+    pub player: usize,
+    pub target: usize,
+    // only revealed to the thief and the victim
+    pub kind: Option<TileKind>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OfferResources {
     pub player: usize,
@@ -746,10 +811,14 @@ pub enum GameAct {
     TradeRequest(TradeRequest),
     TradeResponse(TradeResponse),
     TradeConfirm(Option<usize>),
-    SelectRobber((Option<usize>, Coordinate)),
+    SelectRobber(Coordinate),
     DropResource(Vec<(TileKind, usize)>),
     StealResource(usize),
     EndTurn,
+    // asks for a GameView after a checksum mismatch
+    Resync,
+    // takes back the last build of the turn, see GameMsg::PlayerUndo
+    Undo,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -758,13 +827,55 @@ pub struct GameStart {
     pub harbor: Vec<(Line, TileKind)>,
     pub robber: Coordinate,
     pub dice_map: HashMap<usize, Vec<Coordinate>>,
+    pub bank: [usize; TileKind::Max as usize],
     pub players: Vec<PlayerCommon>,
     pub you: usize,
+    // older recordings have no names, clients number the seats then
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+
+// a running game as one player may know it, the hands of the others only tell
+// how many cards they hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameView {
+    pub start: GameStart,
+    pub roads: Vec<(Line, usize)>,
+    // owner and whether it is a city
+    pub buildings: Vec<(Coordinate, usize, bool)>,
+    pub current: usize,
+}
+
+impl GameView {
+    // the board on the same grid of points a client builds from a GameStart
+    pub fn board(&self) -> CatanCommon {
+        let rows = self.start.tile.len() + 1;
+        let columns = 2 * self.start.tile[0].len() + 1;
+        let mut board = CatanCommon::new(
+            self.start.tile.clone(),
+            vec![vec![Point::default(); columns]; rows],
+            self.roads
+                .iter()
+                .map(|(road, owner)| (*road, *owner))
+                .collect(),
+            self.start.harbor.clone(),
+            self.start.dice_map.clone(),
+            self.start.robber,
+        );
+        for (point, owner, city) in self.buildings.iter() {
+            match city {
+                true => board.add_city(*owner, *point),
+                false => board.add_settlement(*owner, *point),
+            }
+        }
+        board
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameMsg {
     GameStart(GameStart),
+    PlayerFirstRoll((usize, (u8, u8))),
     PlayerInit(usize),
     PlayerTurn(usize),
     PlayerRollDice((u8, u8)),
@@ -775,10 +886,43 @@ pub enum GameMsg {
     PlayerUseDevelopmentCard(UseDevelopmentCard),
     PlayerStartSelectRobber(),
     PlayerSelectRobber(SelectRobber),
+    PlayerStartSteal(Vec<usize>),
+    PlayerStealResource(StealResource),
     PlayerTradeRequest((usize, TradeRequest)),
     PlayerTradeResponse((usize, TradeResponse)),
     PlayerTrade(Option<Trade>),
     PlayerOfferResources(OfferResources),
     PlayerDropResources((usize, usize)),
+    PlayerDiscarding(Vec<usize>),
+    BankResources([usize; TileKind::Max as usize]),
     PlayerEndTurn(usize),
+    // see checksum in catan::state, sent with every new turn
+    Checksum(u64),
+    Resync(GameView),
+    // the build is removed and its cost refunded
+    PlayerUndo((usize, Build)),
+}
+
+impl GameMsg {
+    // the message as a player may see it, bought cards and stolen resources
+    // are only shown to those involved
+    pub fn seen_by(&self, player: usize) -> GameMsg {
+        match self {
+            GameMsg::PlayerBuyDevelopmentCard(buy) if buy.player != player => {
+                GameMsg::PlayerBuyDevelopmentCard(BuyDevelopmentCard {
+                    card: None,
+                    ..buy.clone()
+                })
+            },
+            GameMsg::PlayerStealResource(steal)
+                if steal.player != player && steal.target != player =>
+            {
+                GameMsg::PlayerStealResource(StealResource {
+                    kind: None,
+                    ..steal.clone()
+                })
+            },
+            msg => msg.clone(),
+        }
+    }
 }
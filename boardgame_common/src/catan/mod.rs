@@ -1 +1,5 @@
 pub mod element;
+pub mod rules;
+pub mod state;
+
+pub const NAME: &str = "catan";
@@ -0,0 +1,62 @@
+use super::element::DevCard;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatanRules {
+    pub win_score: usize,
+    // players holding more cards than this drop half of them on a seven
+    pub hand_limit: usize,
+    // players under this score can not be robbed
+    pub friendly_robber: Option<usize>,
+    pub max_trade_requests: usize,
+    pub dev_cards: Vec<(DevCard, usize)>,
+    pub bank_resource: usize,
+    // draw dice from a shuffled deck of all 36 outcomes instead of rolling
+    pub balanced_dice: bool,
+    pub first_player_roll: bool,
+}
+
+impl Default for CatanRules {
+    fn default() -> Self {
+        Self {
+            win_score: 10,
+            hand_limit: 7,
+            friendly_robber: None,
+            max_trade_requests: 3,
+            dev_cards: vec![
+                (DevCard::Knight, 14),
+                (DevCard::VictoryPoint, 5),
+                (DevCard::RoadBuilding, 2),
+                (DevCard::Monopoly, 2),
+                (DevCard::YearOfPlenty, 2),
+            ],
+            bank_resource: 19,
+            balanced_dice: false,
+            first_player_roll: true,
+        }
+    }
+}
+
+impl CatanRules {
+    pub const PRESETS: [&'static str; 4] = ["standard", "friendly", "balanced", "quick"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Self::default()),
+            "friendly" => Some(Self {
+                friendly_robber: Some(3),
+                ..Default::default()
+            }),
+            "balanced" => Some(Self {
+                balanced_dice: true,
+                ..Default::default()
+            }),
+            "quick" => Some(Self {
+                win_score: 8,
+                hand_limit: 9,
+                max_trade_requests: 5,
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    }
+}
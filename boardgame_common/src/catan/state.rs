@@ -0,0 +1,1181 @@
+use std::{collections::HashMap, fmt};
+
+use rand::{prelude::SliceRandom, thread_rng};
+
+use super::{element::*, rules::CatanRules};
+use crate::element::{Coordinate, Line};
+
+pub enum GameUpdate {
+    HitDice(usize),
+    OfferResources(OfferResources),
+    BuildRoad(BuildRoad),
+    BuildSettlement(BuildSettlement),
+    BuildCity(BuildCity),
+    BuyDevelopmentCard(BuyDevelopmentCard),
+    UseDevelopmentCard(UseDevelopmentCard),
+    Trade(Option<Trade>),
+    SelectRobber(SelectRobber),
+    StealResource(StealResource),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatanError {
+    // the game is waiting on someone else
+    NotYourTurn(usize),
+    // the action does not fit what the game is waiting for
+    Unexpected(GameAct),
+    Invalid(String),
+}
+
+impl fmt::Display for CatanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatanError::NotYourTurn(player) => {
+                write!(f, "Not the turn of player {}", player)
+            },
+            CatanError::Unexpected(act) => write!(f, "Unexpected action {:?}", act),
+            CatanError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<String> for CatanError {
+    fn from(err: String) -> Self {
+        CatanError::Invalid(err)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CatanPlayer {
+    pub base: PlayerCommon,
+    pub knight_count: usize,
+}
+
+// what the game waits for next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Phase {
+    // counts the setup placements over both rounds, each is a settlement then a road
+    Setup(usize),
+    // the players still owing cards after a seven, with how many
+    Discard(Vec<(usize, usize)>),
+    Robber,
+    Steal,
+    Action,
+    // the current player's own answer is always a reject
+    TradeResponse(TradeRequest, Vec<Option<TradeResponse>>),
+    TradeConfirm(TradeRequest, Vec<Option<TradeResponse>>),
+    Over(usize),
+}
+
+// the whole game without any waiting, actions go in and the messages they cause
+// come out, the server and the client both run it
+pub struct CatanState {
+    pub inner: CatanCommon,
+    pub dev_cards: Vec<DevCard>,
+    pub players: Vec<CatanPlayer>,
+    pub names: Vec<String>,
+    pub is_initialized: bool,
+    pub longest_road: Option<(usize, usize)>,
+    pub most_knights: Option<(usize, usize)>,
+    // nothing is hidden here yet, see GameMsg::seen_by
+    pub broadcast: Vec<GameMsg>,
+    pub setup_settlement: Option<(usize, Coordinate)>,
+    pub steal_candidates: Vec<usize>,
+    pub bank: [usize; TileKind::Max as usize],
+    synced_bank: [usize; TileKind::Max as usize],
+    pub rules: CatanRules,
+    pub starts: Vec<Vec<(Coordinate, Line)>>,
+    pub phase: Phase,
+    pub current_player: usize,
+    dice_deck: Vec<(u8, u8)>,
+    order: Vec<usize>,
+    card_used: bool,
+    trade_requests: usize,
+    // builds of this turn that can still be taken back, the last one first
+    undo: Vec<Build>,
+}
+
+impl CatanState {
+    pub fn new(
+        inner: CatanCommon, starts: Vec<Vec<(Coordinate, Line)>>, players: usize,
+        rules: CatanRules,
+    ) -> Self {
+        let mut dev_cards = Vec::new();
+        for (card, count) in rules.dev_cards.iter() {
+            for _ in 0..*count {
+                dev_cards.push(*card);
+            }
+        }
+        dev_cards.shuffle(&mut thread_rng());
+
+        let mut bank = [0; TileKind::Max as usize];
+        for (i, count) in bank.iter_mut().enumerate() {
+            if TileKind::try_from(i as u8).unwrap().is_resource() {
+                *count = rules.bank_resource;
+            }
+        }
+
+        Self {
+            inner,
+            dev_cards,
+            players: vec![CatanPlayer::default(); players],
+            names: (1..=players).map(|i| format!("Player {}", i)).collect(),
+            is_initialized: false,
+            longest_road: None,
+            most_knights: None,
+            broadcast: Vec::new(),
+            setup_settlement: None,
+            steal_candidates: Vec::new(),
+            bank,
+            synced_bank: bank,
+            rules,
+            starts,
+            phase: Phase::Setup(0),
+            current_player: 0,
+            dice_deck: Vec::new(),
+            order: (0..players).collect(),
+            card_used: false,
+            trade_requests: 0,
+            undo: Vec::new(),
+        }
+    }
+
+    pub fn checksum(&self, viewer: usize) -> u64 {
+        let players = self.players.iter().map(|p| &p.base).collect::<Vec<_>>();
+        checksum(&self.inner, &self.bank, &players, viewer)
+    }
+
+    pub fn view(&self, viewer: usize) -> GameView {
+        let GameMsg::GameStart(mut start) = self.game_start(viewer) else {
+            unreachable!()
+        };
+        for (i, player) in start.players.iter_mut().enumerate() {
+            if i != viewer {
+                let resources = player.resources_count();
+                let cards = player.card_count();
+                player.resources = Default::default();
+                player.resources[TileKind::Empty as usize] = resources;
+                player.cards = Default::default();
+                player.cards[0] = cards;
+            }
+        }
+        let buildings = owned_points(&self.inner)
+            .map(|(coord, point)| (coord, point.owner.unwrap(), point.city))
+            .collect();
+        GameView {
+            start,
+            roads: self
+                .inner
+                .roads()
+                .iter()
+                .map(|(road, owner)| (*road, *owner))
+                .collect(),
+            buildings,
+            current: self.current_player,
+        }
+    }
+
+    pub fn game_start(&self, player: usize) -> GameMsg {
+        GameMsg::GameStart(GameStart {
+            tile: self.inner.tiles().clone(),
+            harbor: self.inner.harbors().clone(),
+            robber: self.inner.robber(),
+            dice_map: self.inner.dice_map().clone(),
+            bank: self.bank,
+            players: self.players.iter().map(|p| p.base.clone()).collect(),
+            you: player,
+            names: self.names.clone(),
+        })
+    }
+
+    // picks the first player and either places the map starts or asks for the
+    // first setup placement, fails on starts the board does not allow
+    pub fn begin(&mut self) -> Result<Vec<GameMsg>, String> {
+        if self.rules.first_player_roll {
+            self.current_player = self.roll_first_player();
+        }
+        self.order = self.setup_order();
+        if (3..=4).contains(&self.players.len())
+            && self.starts.len() >= self.players.len()
+        {
+            self.preplace_starts(&self.order.clone())
+                .map_err(|err| format!("Invalid start: {}", err))?;
+            self.is_initialized = true;
+            self.start_turn();
+        } else {
+            self.next_setup(0);
+        }
+        Ok(self.drain())
+    }
+
+    // the players the game is waiting on
+    pub fn expects(&self) -> Vec<usize> {
+        match &self.phase {
+            Phase::Setup(step) => vec![self.setup_player(*step).0],
+            Phase::Discard(drops) => drops.iter().map(|(player, _)| *player).collect(),
+            Phase::TradeResponse(_, responses) => responses
+                .iter()
+                .enumerate()
+                .filter(|(_, response)| response.is_none())
+                .map(|(player, _)| player)
+                .collect(),
+            Phase::Over(_) => Vec::new(),
+            _ => vec![self.current_player],
+        }
+    }
+
+    pub fn apply(
+        &mut self, player: usize, act: GameAct,
+    ) -> Result<Vec<GameMsg>, CatanError> {
+        if !self.expects().contains(&player) {
+            return Err(CatanError::NotYourTurn(player));
+        }
+        if let Err(err) = self.act(player, act) {
+            // a rejected action leaves nothing to send
+            self.broadcast.clear();
+            return Err(err);
+        }
+        Ok(self.drain())
+    }
+
+    // the message asking a player again after a rejected action, if any
+    pub fn prompt(&self, player: usize) -> Option<GameMsg> {
+        match &self.phase {
+            Phase::Setup(step) if player == self.setup_player(*step).0 => {
+                Some(GameMsg::PlayerInit(player))
+            },
+            Phase::Discard(drops) => drops
+                .iter()
+                .find(|(p, _)| *p == player)
+                .map(|drop| GameMsg::PlayerDropResources(*drop)),
+            Phase::Robber if player == self.current_player => {
+                Some(GameMsg::PlayerStartSelectRobber())
+            },
+            Phase::Steal if player == self.current_player => {
+                Some(GameMsg::PlayerStartSteal(self.steal_candidates.clone()))
+            },
+            _ => None,
+        }
+    }
+
+    // the discard ran out of time, whatever is still owed is dropped at random
+    pub fn timeout(&mut self) -> Vec<GameMsg> {
+        if let Phase::Discard(drops) = self.phase.clone() {
+            for (player, count) in drops {
+                let drop = self.random_drop(player, count);
+                self.drop_resources(player, count, drop).unwrap();
+            }
+            self.collect_drops(Vec::new());
+        }
+        self.drain()
+    }
+
+    // everything queued since the last call, with the bank when it moved
+    pub fn drain(&mut self) -> Vec<GameMsg> {
+        if self.synced_bank != self.bank {
+            self.synced_bank = self.bank;
+            self.broadcast.push(GameMsg::BankResources(self.bank));
+        }
+        self.broadcast.drain(..).collect()
+    }
+
+    fn act(&mut self, player: usize, act: GameAct) -> Result<(), CatanError> {
+        match (self.phase.clone(), act) {
+            (Phase::Setup(step), GameAct::BuildSettlement(point))
+                if self.setup_settlement.is_none() =>
+            {
+                self.update(GameUpdate::BuildSettlement(BuildSettlement {
+                    player,
+                    point,
+                }))?;
+                if self.setup_player(step).1 {
+                    self.grant_initial_resources(player, point);
+                }
+            },
+            (Phase::Setup(step), GameAct::BuildRoad(from, to))
+                if self.setup_settlement.is_some() =>
+            {
+                self.update(GameUpdate::BuildRoad(BuildRoad {
+                    player,
+                    road: road(from, to),
+                }))?;
+                self.next_setup(step + 1);
+            },
+            (Phase::Discard(mut drops), GameAct::DropResource(drop)) => {
+                let index = drops.iter().position(|(p, _)| *p == player).unwrap();
+                self.drop_resources(player, drops[index].1, drop)?;
+                drops.remove(index);
+                self.collect_drops(drops);
+            },
+            (Phase::Robber, GameAct::SelectRobber(coord)) => {
+                self.update(GameUpdate::SelectRobber(SelectRobber { player, coord }))?;
+                self.after_robber();
+            },
+            (Phase::Steal, GameAct::StealResource(target)) => {
+                self.update(GameUpdate::StealResource(StealResource {
+                    player,
+                    target,
+                    kind: None,
+                }))?;
+                self.phase = Phase::Action;
+            },
+            (Phase::Action, act) => self.act_in_turn(player, act)?,
+            (
+                Phase::TradeResponse(request, mut responses),
+                GameAct::TradeResponse(response),
+            ) => {
+                if response == TradeResponse::Accept {
+                    for (kind, count) in request.to() {
+                        if self.players[player].base.resources[*kind as usize] < *count {
+                            return Err("Not enough resources to accept"
+                                .to_owned()
+                                .into());
+                        }
+                    }
+                }
+                responses[player] = Some(response);
+                self.broadcast
+                    .push(GameMsg::PlayerTradeResponse((player, response)));
+                self.phase = match responses.iter().all(Option::is_some) {
+                    true => Phase::TradeConfirm(request, responses),
+                    false => Phase::TradeResponse(request, responses),
+                };
+            },
+            (Phase::TradeConfirm(request, responses), GameAct::TradeConfirm(to)) => {
+                let trade = match to {
+                    Some(to) => {
+                        if to == player || to >= self.players.len() {
+                            return Err("Invalid player index".to_owned().into());
+                        }
+                        if responses[to] != Some(TradeResponse::Accept) {
+                            return Err("Trade rejected by other player"
+                                .to_owned()
+                                .into());
+                        }
+                        Some(Trade {
+                            from: player,
+                            to: Some(to),
+                            request,
+                        })
+                    },
+                    None => None,
+                };
+                self.update(GameUpdate::Trade(trade))?;
+                self.phase = Phase::Action;
+            },
+            (_, act) => return Err(CatanError::Unexpected(act)),
+        }
+        Ok(())
+    }
+
+    fn act_in_turn(&mut self, player: usize, act: GameAct) -> Result<(), CatanError> {
+        match act {
+            GameAct::BuildRoad(from, to) => {
+                let line = road(from, to);
+                self.update(GameUpdate::BuildRoad(BuildRoad { player, road: line }))?;
+                self.undo.push(Build::Road(line));
+            },
+            GameAct::BuildSettlement(point) => {
+                self.update(GameUpdate::BuildSettlement(BuildSettlement {
+                    player,
+                    point,
+                }))?;
+                self.undo.push(Build::Settlement(point));
+            },
+            GameAct::BuildCity(point) => {
+                self.update(GameUpdate::BuildCity(BuildCity { player, point }))?;
+                self.undo.push(Build::City(point));
+            },
+            GameAct::Undo => match self.undo.pop() {
+                Some(build) => self.undo_build(player, build),
+                None => return Err("Nothing to undo".to_owned().into()),
+            },
+            GameAct::BuyDevelopmentCard => {
+                self.update(GameUpdate::BuyDevelopmentCard(BuyDevelopmentCard {
+                    player,
+                    card: None,
+                }))?;
+                // the card drawn cannot go back into the deck unseen
+                self.undo.clear();
+            },
+            GameAct::UseDevelopmentCard((card, usage)) => {
+                if self.card_used {
+                    return Err("Only one development card can be used per turn"
+                        .to_owned()
+                        .into());
+                }
+                self.update(GameUpdate::UseDevelopmentCard(UseDevelopmentCard {
+                    player,
+                    usage,
+                    card,
+                }))?;
+                self.card_used = true;
+                self.undo.clear();
+                if card == DevCard::Knight {
+                    self.after_robber();
+                }
+            },
+            GameAct::TradeRequest(request) => {
+                if self.trade_requests >= self.rules.max_trade_requests {
+                    return Err(format!(
+                        "Only {} trade requests allowed per turn",
+                        self.rules.max_trade_requests
+                    )
+                    .into());
+                }
+                if *request.target() == TradeTarget::Player {
+                    self.broadcast
+                        .push(GameMsg::PlayerTradeRequest((player, request.clone())));
+                    let mut responses = vec![None; self.players.len()];
+                    responses[player] = Some(TradeResponse::Reject);
+                    self.phase = Phase::TradeResponse(request, responses);
+                } else {
+                    self.update(GameUpdate::Trade(Some(Trade {
+                        from: player,
+                        to: None,
+                        request,
+                    })))?;
+                }
+                self.trade_requests += 1;
+                self.undo.clear();
+            },
+            GameAct::EndTurn => self.end_turn(),
+            act => return Err(CatanError::Unexpected(act)),
+        }
+        Ok(())
+    }
+
+    // the player placing at a setup step, and whether it is the second round
+    fn setup_player(&self, step: usize) -> (usize, bool) {
+        let count = self.order.len();
+        match step < count {
+            true => (self.order[step], false),
+            false => (self.order[2 * count - 1 - step], true),
+        }
+    }
+
+    fn next_setup(&mut self, step: usize) {
+        if step == 2 * self.players.len() {
+            self.is_initialized = true;
+            self.start_turn();
+            return;
+        }
+        self.phase = Phase::Setup(step);
+        self.broadcast
+            .push(GameMsg::PlayerInit(self.setup_player(step).0));
+    }
+
+    fn start_turn(&mut self) {
+        self.card_used = false;
+        self.trade_requests = 0;
+        self.undo.clear();
+        self.broadcast
+            .push(GameMsg::PlayerTurn(self.current_player));
+
+        let (dice1, dice2) = self.next_dice();
+        self.broadcast.push(GameMsg::PlayerRollDice((dice1, dice2)));
+        if dice1 + dice2 == 7 {
+            let mut drops = Vec::new();
+            for i in 0..self.players.len() {
+                let count = self.players[i].base.discard_count(self.rules.hand_limit);
+                if count > 0 {
+                    drops.push((i, count));
+                    self.broadcast
+                        .push(GameMsg::PlayerDropResources((i, count)));
+                }
+            }
+            self.collect_drops(drops);
+        } else {
+            self.hit_dice((dice1 + dice2) as usize);
+            self.phase = Phase::Action;
+        }
+    }
+
+    // waits for the listed discards, the robber moves once nobody owes cards
+    pub fn collect_drops(&mut self, drops: Vec<(usize, usize)>) {
+        if drops.is_empty() {
+            self.broadcast.push(GameMsg::PlayerDiscarding(Vec::new()));
+            self.broadcast.push(GameMsg::PlayerStartSelectRobber());
+            self.phase = Phase::Robber;
+            return;
+        }
+        let pending = drops.iter().map(|(player, _)| *player).collect();
+        self.broadcast.push(GameMsg::PlayerDiscarding(pending));
+        self.phase = Phase::Discard(drops);
+    }
+
+    fn after_robber(&mut self) {
+        self.phase = match self.steal_candidates.is_empty() {
+            true => Phase::Action,
+            false => Phase::Steal,
+        };
+    }
+
+    fn end_turn(&mut self) {
+        self.broadcast
+            .push(GameMsg::PlayerEndTurn(self.current_player));
+        self.check_longest_road();
+        if let Some(winner) = self.check_winner() {
+            self.phase = Phase::Over(winner);
+            return;
+        }
+        self.current_player = (self.current_player + 1) % self.players.len();
+        self.start_turn();
+    }
+
+    // pending roads are about to be built along with this one, a road building
+    // card checks both of its roads before placing either
+    fn check_road(
+        &self, player: usize, road: Line, pending: &[Line],
+    ) -> Result<(), String> {
+        if !self.inner.point_valid(road.start) || !self.inner.point_valid(road.end) {
+            return Err("Invalid road position".to_owned());
+        }
+
+        if self.players[player].base.roads.len() + pending.len() >= 15 {
+            return Err("Road limit reached".to_owned());
+        }
+
+        if !self.is_initialized {
+            match self.setup_settlement {
+                Some((owner, point))
+                    if owner == player && (road.start == point || road.end == point) => {
+                },
+                _ => {
+                    return Err(format!(
+                        "Setup road {:?} must touch the settlement just placed",
+                        road
+                    ));
+                },
+            }
+        }
+
+        let reaches = |to: Coordinate| {
+            self.players[player].base.have_roads_to(to)
+                || pending.iter().any(|r| r.start == to || r.end == to)
+        };
+        if self.is_initialized && !reaches(road.start) && !reaches(road.end) {
+            return Err(format!("Player have no road to {:?}", road));
+        }
+
+        if self.inner.roads().contains_key(&road) || pending.contains(&road) {
+            return Err("Road already exists".to_owned());
+        }
+        Ok(())
+    }
+
+    fn build_road(&mut self, build: BuildRoad, free: bool) -> Result<(), String> {
+        self.check_road(build.player, build.road, &[])?;
+
+        if self.is_initialized && !free {
+            if self.players[build.player].base.resources[TileKind::Brick as usize] < 1
+                || self.players[build.player].base.resources[TileKind::Wood as usize] < 1
+            {
+                return Err("Not enough resources".to_owned());
+            }
+            self.pay(build.player, &[(TileKind::Brick, 1), (TileKind::Wood, 1)]);
+        }
+
+        self.players[build.player].base.add_road(build.road);
+        self.inner.add_road(build.player, build.road);
+        if !self.is_initialized {
+            self.setup_settlement = None;
+        }
+        self.broadcast.push(GameMsg::PlayerBuildRoad(build));
+        Ok(())
+    }
+
+    fn build_settlement(&mut self, build: BuildSettlement) -> Result<(), String> {
+        if !self.inner.point_valid(build.point) {
+            return Err("Invalid settlement position".to_owned());
+        }
+
+        if self.players[build.player].base.settlement_left == 0 {
+            return Err("Settlement limit reached".to_owned());
+        }
+
+        if self.inner.point(build.point).is_owned() {
+            return Err("Point already owned".to_owned());
+        }
+
+        if self
+            .inner
+            .point_get_points(build.point)
+            .iter()
+            .flatten()
+            .any(|p| self.inner.point(*p).is_owned())
+        {
+            return Err("Ajacent Point already owned".to_owned());
+        }
+
+        if self.is_initialized {
+            if !self.players[build.player].base.have_roads_to(build.point) {
+                return Err(format!("Player have no road to {:?}", build.point));
+            }
+            let cost = [
+                (TileKind::Brick, 1),
+                (TileKind::Grain, 1),
+                (TileKind::Wool, 1),
+                (TileKind::Wood, 1),
+            ];
+            if !self.can_pay(build.player, &cost) {
+                return Err("Not enough resources".to_owned());
+            }
+            self.pay(build.player, &cost);
+        } else {
+            self.setup_settlement = Some((build.player, build.point));
+        }
+
+        self.inner.add_settlement(build.player, build.point);
+        self.players[build.player].base.score += 1;
+        self.players[build.player].base.settlement_left -= 1;
+        self.broadcast.push(GameMsg::PlayerBuildSettlement(build));
+        Ok(())
+    }
+
+    fn build_city(&mut self, build: BuildCity) -> Result<(), String> {
+        if !self.inner.point_valid(build.point) {
+            return Err("Invalid city position".to_owned());
+        }
+
+        if self.players[build.player].base.city_left == 0 {
+            return Err("City limit reached".to_owned());
+        }
+
+        let cost = [(TileKind::Stone, 3), (TileKind::Grain, 2)];
+        match self.inner.point(build.point).owner() {
+            Some(owner) => {
+                if !self.can_pay(build.player, &cost) {
+                    return Err("Not enough resources".to_owned());
+                }
+                if owner != build.player {
+                    return Err("Point not owned by player".to_owned());
+                }
+            },
+            None => {
+                return Err("Point not owned".to_owned());
+            },
+        }
+
+        self.inner.add_city(build.player, build.point);
+        self.pay(build.player, &cost);
+        self.players[build.player].base.score += 1;
+        self.players[build.player].base.city_left -= 1;
+        self.players[build.player].base.settlement_left += 1;
+        self.broadcast.push(GameMsg::PlayerBuildCity(build));
+        Ok(())
+    }
+
+    fn undo_build(&mut self, player: usize, build: Build) {
+        match build {
+            Build::Road(road) => {
+                self.inner.remove_road(road);
+                self.players[player].base.remove_road(road);
+            },
+            Build::Settlement(point) => {
+                self.inner.remove_settlement(point);
+                self.players[player].base.score -= 1;
+                self.players[player].base.settlement_left += 1;
+            },
+            Build::City(point) => {
+                self.inner.remove_city(point);
+                self.players[player].base.score -= 1;
+                self.players[player].base.city_left += 1;
+                self.players[player].base.settlement_left -= 1;
+            },
+        }
+        // the bank holds what was paid, nothing was handed out since
+        for (kind, count) in build.cost() {
+            self.take(player, *kind, *count);
+        }
+        self.broadcast.push(GameMsg::PlayerUndo((player, build)));
+    }
+
+    fn buy_development_card(
+        &mut self, mut buy: BuyDevelopmentCard,
+    ) -> Result<(), String> {
+        let cost = [
+            (TileKind::Grain, 1),
+            (TileKind::Wool, 1),
+            (TileKind::Stone, 1),
+        ];
+        if !self.can_pay(buy.player, &cost) {
+            return Err("Not enough resources".to_owned());
+        }
+        let card = match self.dev_cards.pop() {
+            Some(card) => card,
+            None => return Err("No development card left".to_owned()),
+        };
+        self.pay(buy.player, &cost);
+        self.players[buy.player].base.cards[card as usize] += 1;
+        buy.card = Some(card);
+        self.broadcast.push(GameMsg::PlayerBuyDevelopmentCard(buy));
+        Ok(())
+    }
+
+    fn use_development_card(
+        &mut self, use_card: UseDevelopmentCard,
+    ) -> Result<(), String> {
+        let player = use_card.player;
+        if self.players[player].base.cards[use_card.card as usize] == 0 {
+            return Err("Card not found".to_owned());
+        }
+        match (use_card.card, &use_card.usage) {
+            (DevCard::Knight, DevelopmentCard::Knight(select_robber)) => {
+                self.update(GameUpdate::SelectRobber(select_robber.clone()))?;
+                self.players[player].knight_count += 1;
+
+                let knights = self.players[player].knight_count;
+                if knights >= 3 {
+                    match self.most_knights {
+                        Some((holder, most)) if most < knights => {
+                            self.players[holder].base.score -= 2;
+                            self.players[player].base.score += 2;
+                            self.most_knights = Some((player, knights));
+                        },
+                        Some(_) => {},
+                        None => {
+                            self.players[player].base.score += 2;
+                            self.most_knights = Some((player, knights));
+                        },
+                    }
+                }
+            },
+            (DevCard::VictoryPoint, DevelopmentCard::VictoryPoint) => {
+                self.players[player].base.score += 1;
+            },
+            (DevCard::RoadBuilding, DevelopmentCard::RoadBuilding(roads)) => {
+                // the second road may lean on the first
+                self.check_road(player, roads[0], &[])?;
+                self.check_road(player, roads[1], &roads[..1])?;
+                for road in roads {
+                    self.build_road(
+                        BuildRoad {
+                            player,
+                            road: *road,
+                        },
+                        true,
+                    )?;
+                }
+            },
+            (DevCard::Monopoly, DevelopmentCard::Monopoly(kind)) => {
+                let mut count = 0;
+                for i in 0..self.players.len() {
+                    if i == player {
+                        continue;
+                    }
+                    count += self.players[i].base.resources[*kind as usize];
+                    self.players[i].base.resources[*kind as usize] = 0;
+                }
+                self.players[player].base.resources[*kind as usize] += count;
+            },
+            (DevCard::YearOfPlenty, DevelopmentCard::YearOfPlenty(kind1, kind2)) => {
+                let mut wanted = [0; TileKind::Max as usize];
+                wanted[*kind1 as usize] += 1;
+                wanted[*kind2 as usize] += 1;
+                for (kind, count) in wanted.iter().enumerate() {
+                    if self.bank[kind] < *count {
+                        return Err("Not enough resources in the bank".to_owned());
+                    }
+                }
+                self.take(player, *kind1, 1);
+                self.take(player, *kind2, 1);
+            },
+            (card, _) => {
+                return Err(format!("Invalid usage of {:?} card", card));
+            },
+        }
+        self.players[player].base.cards[use_card.card as usize] -= 1;
+        self.broadcast
+            .push(GameMsg::PlayerUseDevelopmentCard(use_card));
+        Ok(())
+    }
+
+    fn check_winner(&self) -> Option<usize> {
+        (0..self.players.len())
+            .find(|i| self.players[*i].base.score >= self.rules.win_score)
+    }
+
+    pub fn check_longest_road(&mut self) {
+        let longest_road = self.players[self.current_player].base.get_longest_road();
+        if longest_road >= 5 {
+            if let Some((player, length)) = self.longest_road {
+                if length < longest_road {
+                    self.longest_road = Some((self.current_player, longest_road));
+                    self.players[player].base.score -= 2;
+                    self.players[self.current_player].base.score += 2;
+                }
+            } else {
+                self.longest_road = Some((self.current_player, longest_road));
+                self.players[self.current_player].base.score += 2;
+            }
+        }
+    }
+
+    fn can_pay(&self, player: usize, cost: &[(TileKind, usize)]) -> bool {
+        cost.iter().all(|(kind, count)| {
+            self.players[player].base.resources[*kind as usize] >= *count
+        })
+    }
+
+    // returns spent resources to the bank
+    fn pay(&mut self, player: usize, cost: &[(TileKind, usize)]) {
+        for (kind, count) in cost {
+            self.players[player].base.resources[*kind as usize] -= count;
+            self.bank[*kind as usize] += count;
+        }
+    }
+
+    // hands out at most what the bank still holds
+    fn take(&mut self, player: usize, kind: TileKind, count: usize) -> usize {
+        let count = count.min(self.bank[kind as usize]);
+        self.bank[kind as usize] -= count;
+        self.players[player].base.resources[kind as usize] += count;
+        count
+    }
+
+    fn offer_resources(&mut self, mut offer: OfferResources) {
+        if offer.count >= 0 {
+            offer.count =
+                self.take(offer.player, offer.kind, offer.count as usize) as isize;
+        } else {
+            let count = (-offer.count as usize)
+                .min(self.players[offer.player].base.resources[offer.kind as usize]);
+            self.pay(offer.player, &[(offer.kind, count)]);
+            offer.count = -(count as isize);
+        }
+        self.broadcast.push(GameMsg::PlayerOfferResources(offer));
+    }
+
+    fn hit_dice(&mut self, dice: usize) {
+        let mut owed = vec![vec![0; self.players.len()]; TileKind::Max as usize];
+        // a custom map need not have every number
+        let tiles = self.inner.dice_map().get(&dice).cloned();
+        for match_tile in tiles.iter().flatten() {
+            let tile = &mut self.inner.tile(*match_tile);
+            let kind = tile.kind();
+            if !tile.is_empty() && *match_tile != self.inner.robber() {
+                let points = self.inner.tile_get_points(*match_tile);
+                for point in points {
+                    let point = &self.inner.point(point);
+                    if point.is_owned() {
+                        owed[kind as usize][point.owner().unwrap()] +=
+                            if point.city { 2 } else { 1 };
+                    }
+                }
+            }
+        }
+
+        for (kind, owed) in owed.into_iter().enumerate() {
+            let total = owed.iter().sum::<usize>();
+            let receivers = owed.iter().filter(|count| **count > 0).count();
+            // when the bank runs short nobody gets the resource,
+            // unless a single player is owed it, who takes what is left
+            if total > self.bank[kind] && receivers > 1 {
+                continue;
+            }
+            for (player, count) in owed.into_iter().enumerate() {
+                if count > 0 {
+                    self.offer_resources(OfferResources {
+                        player,
+                        count: count as isize,
+                        kind: TileKind::try_from(kind as u8).unwrap(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn select_robber(&mut self, select_robber: SelectRobber) -> Result<(), String> {
+        if self.inner.tile(select_robber.coord).is_empty() {
+            return Err("Invalid robber position".to_owned());
+        }
+        if self.inner.robber() == select_robber.coord {
+            return Err("Robber must be moved to another tile".to_owned());
+        }
+
+        let mut candidates = Vec::new();
+        for point in self.inner.tile_get_points(select_robber.coord) {
+            if let Some(owner) = self.inner.point(point).owner() {
+                let protected = match self.rules.friendly_robber {
+                    Some(score) => self.players[owner].base.score < score,
+                    None => false,
+                };
+                if owner != select_robber.player
+                    && !protected
+                    && !candidates.contains(&owner)
+                {
+                    candidates.push(owner);
+                }
+            }
+        }
+        candidates.sort();
+
+        self.inner.set_robber(select_robber.coord);
+        self.broadcast
+            .push(GameMsg::PlayerSelectRobber(select_robber));
+        self.broadcast
+            .push(GameMsg::PlayerStartSteal(candidates.clone()));
+        self.steal_candidates = candidates;
+        Ok(())
+    }
+
+    fn steal_resource(&mut self, mut steal: StealResource) -> Result<(), String> {
+        if !self.steal_candidates.contains(&steal.target) {
+            return Err("Invalid steal target".to_owned());
+        }
+        self.steal_candidates.clear();
+
+        let available = (0..TileKind::Max as usize)
+            .filter(|kind| self.players[steal.target].base.resources[*kind] > 0)
+            .collect::<Vec<_>>();
+        // a victim without cards can still be picked, nothing is stolen
+        steal.kind = match available.choose(&mut thread_rng()) {
+            Some(kind) => {
+                self.players[steal.target].base.resources[*kind] -= 1;
+                self.players[steal.player].base.resources[*kind] += 1;
+                Some(TileKind::try_from(*kind as u8).unwrap())
+            },
+            None => None,
+        };
+        self.broadcast.push(GameMsg::PlayerStealResource(steal));
+        Ok(())
+    }
+
+    fn do_player_trade(&mut self, trade: Trade) -> Result<(), String> {
+        let to = trade.to.unwrap();
+        if !self.can_pay(trade.from, trade.request.from()) {
+            return Err(format!("Player {} has not enough resources", trade.from));
+        }
+        if !self.can_pay(to, trade.request.to()) {
+            return Err(format!("Player {} has not enough resources", to));
+        }
+
+        for (kind, count) in trade.request.from() {
+            self.players[trade.from].base.resources[*kind as usize] -= count;
+            self.players[to].base.resources[*kind as usize] += count;
+        }
+
+        for (kind, count) in trade.request.to() {
+            self.players[trade.from].base.resources[*kind as usize] += count;
+            self.players[to].base.resources[*kind as usize] -= count;
+        }
+        Ok(())
+    }
+
+    fn do_local_trade(&mut self, trade: Trade) -> Result<(), String> {
+        self.inner
+            .check_valid_local_trade(&trade, &self.players[trade.from].base)?;
+        for (kind, count) in trade.request.to() {
+            if self.bank[*kind as usize] < *count {
+                return Err(format!("Bank has not enough {:?}", kind));
+            }
+        }
+
+        self.pay(trade.from, trade.request.from());
+        for (kind, count) in trade.request.to() {
+            self.take(trade.from, *kind, *count);
+        }
+        Ok(())
+    }
+
+    fn do_trade(&mut self, trade: Option<Trade>) -> Result<(), String> {
+        if let Some(trade) = &trade {
+            match trade.request.target() {
+                TradeTarget::Player => self.do_player_trade(trade.clone())?,
+                _ => self.do_local_trade(trade.clone())?,
+            }
+        }
+        self.broadcast.push(GameMsg::PlayerTrade(trade));
+        Ok(())
+    }
+
+    pub fn update(&mut self, update: GameUpdate) -> Result<(), String> {
+        match update {
+            GameUpdate::BuildRoad(build) => self.build_road(build, false)?,
+            GameUpdate::BuildSettlement(build) => self.build_settlement(build)?,
+            GameUpdate::BuildCity(build) => self.build_city(build)?,
+            GameUpdate::BuyDevelopmentCard(buy) => self.buy_development_card(buy)?,
+            GameUpdate::UseDevelopmentCard(use_card) => {
+                self.use_development_card(use_card)?
+            },
+            GameUpdate::Trade(trade) => self.do_trade(trade)?,
+            GameUpdate::SelectRobber(select_robber) => {
+                self.select_robber(select_robber)?
+            },
+            GameUpdate::StealResource(steal) => self.steal_resource(steal)?,
+            GameUpdate::OfferResources(offer) => self.offer_resources(offer),
+            GameUpdate::HitDice(dice) => self.hit_dice(dice),
+        }
+        Ok(())
+    }
+
+    pub fn next_dice(&mut self) -> (u8, u8) {
+        if !self.rules.balanced_dice {
+            let dice1 = (1 + rand::random::<usize>() % 6) as u8;
+            let dice2 = (1 + rand::random::<usize>() % 6) as u8;
+            return (dice1, dice2);
+        }
+        // reshuffle before the deck runs out, so the last cards stay unpredictable
+        if self.dice_deck.len() <= 5 {
+            self.dice_deck.clear();
+            for dice1 in 1..=6 {
+                for dice2 in 1..=6 {
+                    self.dice_deck.push((dice1, dice2));
+                }
+            }
+            self.dice_deck.shuffle(&mut thread_rng());
+        }
+        self.dice_deck.pop().unwrap()
+    }
+
+    pub fn drop_resources(
+        &mut self, player: usize, count: usize, drop: Vec<(TileKind, usize)>,
+    ) -> Result<(), String> {
+        self.players[player].base.check_valid_drop(&drop, count)?;
+        for (kind, count) in drop {
+            if count > 0 {
+                self.offer_resources(OfferResources {
+                    player,
+                    count: -(count as isize),
+                    kind,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn random_drop(&self, player: usize, count: usize) -> Vec<(TileKind, usize)> {
+        let mut cards = Vec::new();
+        for (i, cnt) in self.players[player].base.resources.iter().enumerate() {
+            for _ in 0..*cnt {
+                cards.push(TileKind::try_from(i as u8).unwrap());
+            }
+        }
+
+        let mut drop = HashMap::new();
+        for _ in 0..count.min(cards.len()) {
+            let kind = cards.swap_remove(rand::random::<usize>() % cards.len());
+            *drop.entry(kind).or_insert(0) += 1;
+        }
+        drop.into_iter().collect()
+    }
+
+    pub fn grant_initial_resources(&mut self, player: usize, point: Coordinate) {
+        for tile in self.inner.ponint_get_tile(point).into_iter().flatten() {
+            if self.inner.tile(tile).is_resource() {
+                self.offer_resources(OfferResources {
+                    player,
+                    count: 1,
+                    kind: self.inner.tile(tile).kind(),
+                });
+            }
+        }
+    }
+
+    pub fn roll_first_player(&mut self) -> usize {
+        let mut candidates = (0..self.players.len()).collect::<Vec<_>>();
+        while candidates.len() > 1 {
+            let mut rolls = Vec::new();
+            for player in candidates.iter() {
+                let dice1 = (1 + rand::random::<usize>() % 6) as u8;
+                let dice2 = (1 + rand::random::<usize>() % 6) as u8;
+                self.broadcast
+                    .push(GameMsg::PlayerFirstRoll((*player, (dice1, dice2))));
+                rolls.push((*player, dice1 + dice2));
+            }
+            let best = rolls.iter().map(|(_, roll)| *roll).max().unwrap();
+            candidates = rolls
+                .into_iter()
+                .filter(|(_, roll)| *roll == best)
+                .map(|(player, _)| player)
+                .collect();
+        }
+        candidates[0]
+    }
+
+    pub fn setup_order(&self) -> Vec<usize> {
+        (0..self.players.len())
+            .map(|i| (self.current_player + i) % self.players.len())
+            .collect()
+    }
+
+    // builds the suggested starts of the map, the second settlement pays out
+    pub fn preplace_starts(&mut self, order: &[usize]) -> Result<(), String> {
+        for round in 0..2 {
+            for (seat, player) in order.iter().enumerate() {
+                let (point, road) = self.starts[seat][round];
+                self.update(GameUpdate::BuildSettlement(BuildSettlement {
+                    player: *player,
+                    point,
+                }))?;
+                self.update(GameUpdate::BuildRoad(BuildRoad {
+                    player: *player,
+                    road,
+                }))?;
+                if round == 1 {
+                    self.grant_initial_resources(*player, point);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn owned_points(board: &CatanCommon) -> impl Iterator<Item = (Coordinate, Point)> + '_ {
+    board.points().iter().enumerate().flat_map(|(x, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|(_, point)| point.is_owned())
+            .map(move |(y, point)| (Coordinate::new(x, y), *point))
+    })
+}
+
+// a hash of what a player knows for sure: the board, the bank, its own hand and
+// how many resources and development cards everyone holds, written out by hand
+// so that the server and a wasm client agree on it
+pub fn checksum(
+    board: &CatanCommon, bank: &[usize], players: &[&PlayerCommon], viewer: usize,
+) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut write = |value: usize| {
+        for byte in (value as u64).to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    write(board.robber().x);
+    write(board.robber().y);
+    let mut roads = board.roads().iter().collect::<Vec<_>>();
+    roads.sort_by_key(|(road, _)| (road.start, road.end));
+    for (road, owner) in roads {
+        for value in [road.start.x, road.start.y, road.end.x, road.end.y, *owner] {
+            write(value);
+        }
+    }
+    for (coord, point) in owned_points(board) {
+        for value in [coord.x, coord.y, point.owner.unwrap(), point.city as usize] {
+            write(value);
+        }
+    }
+    bank.iter().for_each(|count| write(*count));
+    for (i, player) in players.iter().enumerate() {
+        if i == viewer {
+            player.resources.iter().for_each(|count| write(*count));
+            player.cards.iter().for_each(|count| write(*count));
+        }
+        write(player.resources_count());
+        write(player.card_count());
+    }
+    hash
+}
+
+// the road between two points, facing the way the board stores it
+fn road(from: Coordinate, to: Coordinate) -> Line {
+    if from.x == to.x {
+        Line::new(from, to)
+    } else {
+        Line::new(to, from)
+    }
+}
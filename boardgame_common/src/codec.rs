@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    catan::element::{
+        BuildCity, BuildRoad, BuildSettlement, GameAct, GameMsg, GameStart,
+        OfferResources, PlayerCommon, SelectRobber, Tile, TileKind,
+    },
+    element::{Coordinate, Line},
+};
+
+// negotiated at connect through the "compact" feature
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Plain,
+    Compact,
+}
+
+// the transport already writes integers as varints, the compact form saves on
+// structure instead: coordinates and small fields share bytes and the game start
+// drops everything the client can rebuild
+
+const RESOURCES: [TileKind; 5] = [
+    TileKind::Wood,
+    TileKind::Brick,
+    TileKind::Grain,
+    TileKind::Wool,
+    TileKind::Stone,
+];
+
+// points per axis that fit the four bits a packed coordinate gives each
+pub const MAX_POINTS: usize = 16;
+
+// boards up to 16 points wide and high
+fn pack_coord(coord: Coordinate) -> Option<u8> {
+    if coord.x < MAX_POINTS && coord.y < MAX_POINTS {
+        Some((coord.x << 4 | coord.y) as u8)
+    } else {
+        None
+    }
+}
+
+fn unpack_coord(byte: u8) -> Coordinate {
+    Coordinate::new((byte >> 4) as usize, (byte & 0xf) as usize)
+}
+
+fn pack_line(line: Line) -> Option<[u8; 2]> {
+    Some([pack_coord(line.start)?, pack_coord(line.end)?])
+}
+
+fn unpack_line(bytes: [u8; 2]) -> Line {
+    Line {
+        start: unpack_coord(bytes[0]),
+        end: unpack_coord(bytes[1]),
+    }
+}
+
+fn pack_player(player: usize) -> Option<u8> {
+    u8::try_from(player).ok()
+}
+
+fn unpack_kind(byte: u8) -> Result<TileKind, String> {
+    TileKind::try_from(byte).map_err(|_| format!("Invalid tile kind {}", byte))
+}
+
+// the dice map follows from the tiles, deserts stand for seven
+fn dice_map(tiles: &[Vec<Tile>]) -> HashMap<usize, Vec<Coordinate>> {
+    let mut dice_map = HashMap::new();
+    for (x, row) in tiles.iter().enumerate() {
+        for (y, tile) in row.iter().enumerate() {
+            let number = match (tile.kind(), tile.number()) {
+                (TileKind::Dessert, _) => 7,
+                (_, Some(number)) => number,
+                _ => continue,
+            };
+            dice_map
+                .entry(number)
+                .or_insert(Vec::new())
+                .push(Coordinate::new(x, y));
+        }
+    }
+    dice_map
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedStart {
+    rows: u8,
+    // kind in the low three bits, number above
+    tiles: Vec<u8>,
+    harbors: Vec<([u8; 2], u8)>,
+    robber: u8,
+    bank: [usize; 5],
+    players: Vec<PlayerCommon>,
+    you: u8,
+    names: Vec<String>,
+}
+
+impl PackedStart {
+    fn pack(start: &GameStart) -> Option<Self> {
+        let rows = u8::try_from(start.tile.len()).ok()?;
+        let mut tiles = Vec::new();
+        for row in start.tile.iter() {
+            if row.len() != start.tile[0].len() {
+                return None;
+            }
+            for tile in row {
+                let number = match tile.number() {
+                    Some(number) if (1..16).contains(&number) => number as u8,
+                    Some(_) => return None,
+                    None => 0,
+                };
+                tiles.push(tile.kind() as u8 | number << 3);
+            }
+        }
+        if dice_map(&start.tile) != start.dice_map || !Self::bank_packable(&start.bank) {
+            return None;
+        }
+
+        let mut harbors = Vec::new();
+        for (line, kind) in start.harbor.iter() {
+            harbors.push((pack_line(*line)?, *kind as u8));
+        }
+        Some(Self {
+            rows,
+            tiles,
+            harbors,
+            robber: pack_coord(start.robber)?,
+            bank: RESOURCES.map(|kind| start.bank[kind as usize]),
+            players: start.players.clone(),
+            you: pack_player(start.you)?,
+            names: start.names.clone(),
+        })
+    }
+
+    fn bank_packable(bank: &[usize; TileKind::Max as usize]) -> bool {
+        (0..bank.len())
+            .all(|i| bank[i] == 0 || RESOURCES.iter().any(|k| *k as usize == i))
+    }
+
+    fn unpack_bank(bank: [usize; 5]) -> [usize; TileKind::Max as usize] {
+        let mut full = [0; TileKind::Max as usize];
+        for (kind, count) in RESOURCES.iter().zip(bank) {
+            full[*kind as usize] = count;
+        }
+        full
+    }
+
+    fn unpack(self) -> Result<GameStart, String> {
+        let rows = self.rows as usize;
+        if rows == 0
+            || self.tiles.is_empty()
+            || self.tiles.len() / rows * rows != self.tiles.len()
+        {
+            return Err("Invalid packed tiles".to_owned());
+        }
+        let mut tile = Vec::new();
+        for row in self.tiles.chunks(self.tiles.len() / rows) {
+            let mut tiles = Vec::new();
+            for byte in row {
+                let mut packed = Tile::default();
+                packed.set_kind(unpack_kind(byte & 0x7)?);
+                if byte >> 3 != 0 {
+                    packed.set_number((byte >> 3) as usize);
+                }
+                tiles.push(packed);
+            }
+            tile.push(tiles);
+        }
+
+        let mut harbor = Vec::new();
+        for (line, kind) in self.harbors {
+            harbor.push((unpack_line(line), unpack_kind(kind)?));
+        }
+        Ok(GameStart {
+            dice_map: dice_map(&tile),
+            tile,
+            harbor,
+            robber: unpack_coord(self.robber),
+            bank: Self::unpack_bank(self.bank),
+            players: self.players,
+            you: self.you as usize,
+            names: self.names,
+        })
+    }
+}
+
+// messages without a compact form are sent as they are
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompactMsg {
+    GameStart(PackedStart),
+    // first die in the high half
+    RollDice(u8),
+    BuildRoad(u8, [u8; 2]),
+    BuildSettlement(u8, u8),
+    BuildCity(u8, u8),
+    SelectRobber(u8, u8),
+    // player in the high bits, kind in the low three
+    OfferResources(u8, isize),
+    BankResources([usize; 5]),
+}
+
+impl CompactMsg {
+    pub fn pack(msg: &GameMsg) -> Option<Self> {
+        match msg {
+            GameMsg::GameStart(start) => PackedStart::pack(start).map(Self::GameStart),
+            GameMsg::PlayerRollDice((dice1, dice2)) if *dice1 < 16 && *dice2 < 16 => {
+                Some(Self::RollDice(dice1 << 4 | dice2))
+            },
+            GameMsg::PlayerBuildRoad(build) => Some(Self::BuildRoad(
+                pack_player(build.player)?,
+                pack_line(build.road)?,
+            )),
+            GameMsg::PlayerBuildSettlement(build) => Some(Self::BuildSettlement(
+                pack_player(build.player)?,
+                pack_coord(build.point)?,
+            )),
+            GameMsg::PlayerBuildCity(build) => Some(Self::BuildCity(
+                pack_player(build.player)?,
+                pack_coord(build.point)?,
+            )),
+            GameMsg::PlayerSelectRobber(select) => Some(Self::SelectRobber(
+                pack_player(select.player)?,
+                pack_coord(select.coord)?,
+            )),
+            GameMsg::PlayerOfferResources(offer) if offer.player < 32 => {
+                Some(Self::OfferResources(
+                    (offer.player as u8) << 3 | offer.kind as u8,
+                    offer.count,
+                ))
+            },
+            GameMsg::BankResources(bank) if PackedStart::bank_packable(bank) => Some(
+                Self::BankResources(RESOURCES.map(|kind| bank[kind as usize])),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn unpack(self) -> Result<GameMsg, String> {
+        Ok(match self {
+            Self::GameStart(start) => GameMsg::GameStart(start.unpack()?),
+            Self::RollDice(dice) => GameMsg::PlayerRollDice((dice >> 4, dice & 0xf)),
+            Self::BuildRoad(player, road) => GameMsg::PlayerBuildRoad(BuildRoad {
+                player: player as usize,
+                road: unpack_line(road),
+            }),
+            Self::BuildSettlement(player, point) => {
+                GameMsg::PlayerBuildSettlement(BuildSettlement {
+                    player: player as usize,
+                    point: unpack_coord(point),
+                })
+            },
+            Self::BuildCity(player, point) => GameMsg::PlayerBuildCity(BuildCity {
+                player: player as usize,
+                point: unpack_coord(point),
+            }),
+            Self::SelectRobber(player, coord) => {
+                GameMsg::PlayerSelectRobber(SelectRobber {
+                    player: player as usize,
+                    coord: unpack_coord(coord),
+                })
+            },
+            Self::OfferResources(packed, count) => {
+                GameMsg::PlayerOfferResources(OfferResources {
+                    player: (packed >> 3) as usize,
+                    count,
+                    kind: unpack_kind(packed & 0x7)?,
+                })
+            },
+            Self::BankResources(bank) => {
+                GameMsg::BankResources(PackedStart::unpack_bank(bank))
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompactAct {
+    BuildRoad([u8; 2]),
+    BuildSettlement(u8),
+    BuildCity(u8),
+    SelectRobber(u8),
+}
+
+impl CompactAct {
+    pub fn pack(act: &GameAct) -> Option<Self> {
+        match act {
+            GameAct::BuildRoad(from, to) => {
+                Some(Self::BuildRoad([pack_coord(*from)?, pack_coord(*to)?]))
+            },
+            GameAct::BuildSettlement(point) => {
+                Some(Self::BuildSettlement(pack_coord(*point)?))
+            },
+            GameAct::BuildCity(point) => Some(Self::BuildCity(pack_coord(*point)?)),
+            GameAct::SelectRobber(coord) => Some(Self::SelectRobber(pack_coord(*coord)?)),
+            _ => None,
+        }
+    }
+
+    pub fn unpack(self) -> GameAct {
+        match self {
+            Self::BuildRoad([from, to]) => {
+                GameAct::BuildRoad(unpack_coord(from), unpack_coord(to))
+            },
+            Self::BuildSettlement(point) => GameAct::BuildSettlement(unpack_coord(point)),
+            Self::BuildCity(point) => GameAct::BuildCity(unpack_coord(point)),
+            Self::SelectRobber(coord) => GameAct::SelectRobber(unpack_coord(coord)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bincode::Options;
+
+    use super::*;
+    use crate::network::ServerMsg;
+
+    // the transport encodes messages with the default bincode options
+    fn wire_size(msg: &ServerMsg) -> usize {
+        bincode::DefaultOptions::new().serialize(msg).unwrap().len()
+    }
+
+    fn variant(msg: &GameMsg) -> String {
+        format!("{:?}", msg)
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn test_compact_recorded_game() {
+        let game: Vec<GameMsg> =
+            serde_json::from_str(include_str!("../fixtures/recorded_game.json")).unwrap();
+
+        let mut sizes: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+        let mut names = 0;
+        for msg in game.iter() {
+            if let GameMsg::GameStart(start) = msg {
+                // seat names are sent verbatim either way
+                names += bincode::DefaultOptions::new()
+                    .serialize(&start.names)
+                    .unwrap()
+                    .len();
+            }
+            let plain = ServerMsg::encode(msg.clone(), Encoding::Plain);
+            let compact = ServerMsg::encode(msg.clone(), Encoding::Compact);
+            let decoded = match compact.clone() {
+                ServerMsg::Catan(msg) => msg,
+                ServerMsg::Compact(msg) => msg.unpack().unwrap(),
+                _ => unreachable!("unexpected message {:?}", compact),
+            };
+            assert_eq!(&decoded, msg);
+
+            let entry = sizes.entry(variant(msg)).or_default();
+            entry.0 += 1;
+            entry.1 += wire_size(&plain);
+            entry.2 += wire_size(&compact);
+        }
+
+        // the examples/wire_sizes report breaks these down by message
+        let plain = sizes.values().map(|s| s.1).sum::<usize>();
+        let compact = sizes.values().map(|s| s.2).sum::<usize>();
+        let start = sizes["GameStart"];
+        assert!((start.2 - names) * 2 <= start.1 - names);
+        assert!(compact < plain);
+    }
+
+    #[test]
+    fn test_compact_fallback() {
+        // too large to pack, sent as is
+        let far = Coordinate::new(20, 1);
+        let msg = GameMsg::PlayerBuildSettlement(BuildSettlement {
+            player: 0,
+            point: far,
+        });
+        assert_eq!(CompactMsg::pack(&msg), None);
+        assert_eq!(CompactAct::pack(&GameAct::BuildCity(far)), None);
+        assert_eq!(CompactAct::pack(&GameAct::EndTurn), None);
+
+        let act = GameAct::BuildRoad(Coordinate::new(3, 4), Coordinate::new(2, 4));
+        assert_eq!(CompactAct::pack(&act).unwrap().unpack(), act);
+
+        let offer = GameMsg::PlayerOfferResources(OfferResources {
+            player: 3,
+            count: -12,
+            kind: TileKind::Stone,
+        });
+        assert_eq!(CompactMsg::pack(&offer).unwrap().unpack(), Ok(offer));
+    }
+}
@@ -42,3 +42,396 @@ impl Line {
         }
     }
 }
+
+// axial coordinate of a pointy-top hex, the offset grid keeps odd rows shifted
+// half a tile to the right with x as the row and y as the column
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hex {
+    pub q: isize,
+    pub r: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Corner {
+    North,
+    South,
+}
+
+// every vertex is the north or the south corner of exactly one hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Vertex {
+    pub hex: Hex,
+    pub corner: Corner,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Side {
+    NorthWest,
+    NorthEast,
+    West,
+}
+
+// every edge is the north west, north east or west side of exactly one hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edge {
+    pub hex: Hex,
+    pub side: Side,
+}
+
+impl Hex {
+    // east, north east, north west, west, south west, south east
+    pub const DIRECTIONS: [(isize, isize); 6] =
+        [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+    pub fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+
+    fn from_offset(row: isize, col: isize) -> Self {
+        Self {
+            q: col - (row - (row & 1)) / 2,
+            r: row,
+        }
+    }
+
+    fn to_offset(self) -> (isize, isize) {
+        (self.r, self.q + (self.r - (self.r & 1)) / 2)
+    }
+
+    pub fn from_coordinate(coord: Coordinate) -> Self {
+        Self::from_offset(coord.x as isize, coord.y as isize)
+    }
+
+    // None when the hex lies before the first row or column of the grid
+    pub fn to_coordinate(self) -> Option<Coordinate> {
+        let (row, col) = self.to_offset();
+        if row < 0 || col < 0 {
+            return None;
+        }
+        Some(Coordinate::new(row as usize, col as usize))
+    }
+
+    pub fn offset(self, dq: isize, dr: isize) -> Self {
+        Self::new(self.q + dq, self.r + dr)
+    }
+
+    pub fn distance(self, other: Hex) -> usize {
+        let dq = self.q - other.q;
+        let dr = self.r - other.r;
+        (dq.unsigned_abs() + dr.unsigned_abs() + (dq + dr).unsigned_abs()) / 2
+    }
+
+    pub fn neighbors(self) -> impl Iterator<Item = Hex> {
+        Self::DIRECTIONS
+            .into_iter()
+            .map(move |(dq, dr)| self.offset(dq, dr))
+    }
+
+    // clockwise from the north corner
+    pub fn vertices(self) -> impl Iterator<Item = Vertex> {
+        [
+            Vertex::north(self),
+            Vertex::south(self.offset(1, -1)),
+            Vertex::north(self.offset(0, 1)),
+            Vertex::south(self),
+            Vertex::north(self.offset(-1, 1)),
+            Vertex::south(self.offset(0, -1)),
+        ]
+        .into_iter()
+    }
+
+    // clockwise from the north east side
+    pub fn edges(self) -> impl Iterator<Item = Edge> {
+        [
+            Edge::new(self, Side::NorthEast),
+            Edge::new(self.offset(1, 0), Side::West),
+            Edge::new(self.offset(0, 1), Side::NorthWest),
+            Edge::new(self.offset(-1, 1), Side::NorthEast),
+            Edge::new(self, Side::West),
+            Edge::new(self, Side::NorthWest),
+        ]
+        .into_iter()
+    }
+}
+
+impl Vertex {
+    pub fn north(hex: Hex) -> Self {
+        Self {
+            hex,
+            corner: Corner::North,
+        }
+    }
+
+    pub fn south(hex: Hex) -> Self {
+        Self {
+            hex,
+            corner: Corner::South,
+        }
+    }
+
+    // point rows lie between tile rows, so row x holds the north corners of
+    // tile row x and the south corners of tile row x - 1
+    pub fn from_coordinate(coord: Coordinate) -> Self {
+        let x = coord.x as isize;
+        let y = coord.y as isize;
+        if (x + y) % 2 == 1 {
+            Self::north(Hex::from_offset(x, (y - 1 - (x & 1)) / 2))
+        } else {
+            Self::south(Hex::from_offset(x - 1, (y - 1 - ((x - 1) & 1)) / 2))
+        }
+    }
+
+    pub fn to_coordinate(self) -> Option<Coordinate> {
+        let (row, col) = self.hex.to_offset();
+        let x = match self.corner {
+            Corner::North => row,
+            Corner::South => row + 1,
+        };
+        let y = 2 * col + 1 + (row & 1);
+        if x < 0 || y < 0 {
+            return None;
+        }
+        Some(Coordinate::new(x as usize, y as usize))
+    }
+
+    pub fn neighbors(self) -> impl Iterator<Item = Vertex> {
+        let hex = self.hex;
+        match self.corner {
+            Corner::North => [
+                Vertex::south(hex.offset(0, -1)),
+                Vertex::south(hex.offset(1, -1)),
+                Vertex::south(hex.offset(1, -2)),
+            ],
+            Corner::South => [
+                Vertex::north(hex.offset(-1, 1)),
+                Vertex::north(hex.offset(0, 1)),
+                Vertex::north(hex.offset(-1, 2)),
+            ],
+        }
+        .into_iter()
+    }
+
+    pub fn hexes(self) -> impl Iterator<Item = Hex> {
+        let hex = self.hex;
+        match self.corner {
+            Corner::North => [hex, hex.offset(0, -1), hex.offset(1, -1)],
+            Corner::South => [hex, hex.offset(-1, 1), hex.offset(0, 1)],
+        }
+        .into_iter()
+    }
+
+    pub fn edges(self) -> impl Iterator<Item = Edge> {
+        let hex = self.hex;
+        match self.corner {
+            Corner::North => [
+                Edge::new(hex, Side::NorthWest),
+                Edge::new(hex, Side::NorthEast),
+                Edge::new(hex.offset(1, -1), Side::West),
+            ],
+            Corner::South => [
+                Edge::new(hex.offset(-1, 1), Side::NorthEast),
+                Edge::new(hex.offset(0, 1), Side::NorthWest),
+                Edge::new(hex.offset(0, 1), Side::West),
+            ],
+        }
+        .into_iter()
+    }
+}
+
+impl Edge {
+    pub fn new(hex: Hex, side: Side) -> Self {
+        Self { hex, side }
+    }
+
+    // None unless the vertices are adjacent
+    pub fn between(a: Vertex, b: Vertex) -> Option<Self> {
+        if a == b {
+            return None;
+        }
+        a.edges().find(|edge| edge.vertices().contains(&b))
+    }
+
+    pub fn vertices(self) -> [Vertex; 2] {
+        let hex = self.hex;
+        match self.side {
+            Side::NorthWest => [Vertex::south(hex.offset(0, -1)), Vertex::north(hex)],
+            Side::NorthEast => [Vertex::north(hex), Vertex::south(hex.offset(1, -1))],
+            Side::West => [
+                Vertex::south(hex.offset(0, -1)),
+                Vertex::north(hex.offset(-1, 1)),
+            ],
+        }
+    }
+
+    pub fn hexes(self) -> [Hex; 2] {
+        let hex = self.hex;
+        match self.side {
+            Side::NorthWest => [hex, hex.offset(0, -1)],
+            Side::NorthEast => [hex, hex.offset(1, -1)],
+            Side::West => [hex, hex.offset(-1, 0)],
+        }
+    }
+
+    // the four edges sharing a vertex with this one
+    pub fn neighbors(self) -> impl Iterator<Item = Edge> {
+        self.vertices()
+            .into_iter()
+            .flat_map(|vertex| vertex.edges())
+            .filter(move |edge| *edge != self)
+    }
+
+    pub fn from_line(line: Line) -> Option<Self> {
+        Self::between(
+            Vertex::from_coordinate(line.start),
+            Vertex::from_coordinate(line.end),
+        )
+    }
+
+    pub fn to_line(self) -> Option<Line> {
+        let [a, b] = self.vertices();
+        Some(Line::new(a.to_coordinate()?, b.to_coordinate()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::catan::element::{CatanCommon, Point, Tile};
+
+    fn region() -> Vec<Hex> {
+        let mut hexes = Vec::new();
+        for q in -6..=6 {
+            for r in -6..=6 {
+                hexes.push(Hex::new(q, r));
+            }
+        }
+        hexes
+    }
+
+    fn board() -> CatanCommon {
+        CatanCommon::new(
+            vec![vec![Tile::default(); 5]; 5],
+            vec![vec![Point::default(); 11]; 6],
+            HashMap::new(),
+            Vec::new(),
+            HashMap::new(),
+            Coordinate::default(),
+        )
+    }
+
+    #[test]
+    fn test_coordinate_round_trip() {
+        for x in 0..20 {
+            for y in 0..20 {
+                let coord = Coordinate::new(x, y);
+                assert_eq!(Hex::from_coordinate(coord).to_coordinate(), Some(coord));
+                assert_eq!(Vertex::from_coordinate(coord).to_coordinate(), Some(coord));
+            }
+        }
+        for hex in region() {
+            if let Some(coord) = hex.to_coordinate() {
+                assert_eq!(Hex::from_coordinate(coord), hex);
+            }
+            for vertex in hex.vertices() {
+                if let Some(coord) = vertex.to_coordinate() {
+                    assert_eq!(Vertex::from_coordinate(coord), vertex);
+                }
+            }
+            for edge in hex.edges() {
+                if let Some(line) = edge.to_line() {
+                    assert_eq!(Edge::from_line(line), Some(edge));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_adjacency_symmetry() {
+        for hex in region() {
+            assert_eq!(hex.neighbors().collect::<HashSet<_>>().len(), 6);
+            for neighbor in hex.neighbors() {
+                assert!(neighbor.neighbors().any(|h| h == hex));
+                assert_eq!(hex.distance(neighbor), 1);
+            }
+
+            assert_eq!(hex.vertices().collect::<HashSet<_>>().len(), 6);
+            for vertex in hex.vertices() {
+                assert!(vertex.hexes().any(|h| h == hex));
+                assert_eq!(vertex.hexes().collect::<HashSet<_>>().len(), 3);
+                for neighbor in vertex.neighbors() {
+                    assert!(neighbor.neighbors().any(|v| v == vertex));
+                    assert!(Edge::between(vertex, neighbor).is_some());
+                }
+                for edge in vertex.edges() {
+                    assert!(edge.vertices().contains(&vertex));
+                }
+                assert_eq!(Edge::between(vertex, vertex), None);
+            }
+
+            assert_eq!(hex.edges().collect::<HashSet<_>>().len(), 6);
+            for edge in hex.edges() {
+                assert!(edge.hexes().contains(&hex));
+                assert_eq!(edge.neighbors().collect::<HashSet<_>>().len(), 4);
+                for neighbor in edge.neighbors() {
+                    assert!(neighbor.neighbors().any(|e| e == edge));
+                }
+                let [a, b] = edge.vertices();
+                assert_eq!(Edge::between(a, b), Some(edge));
+                assert_eq!(Edge::between(b, a), Some(edge));
+                // the two hexes of an edge share both of its vertices
+                for hex in edge.hexes() {
+                    assert!(hex.vertices().any(|v| v == a));
+                    assert!(hex.vertices().any(|v| v == b));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_offset_grid() {
+        let board = board();
+        for x in 0..5 {
+            for y in 0..5 {
+                let coord = Coordinate::new(x, y);
+                let vertices = Hex::from_coordinate(coord)
+                    .vertices()
+                    .map(|v| v.to_coordinate().unwrap())
+                    .collect::<HashSet<_>>();
+                let points = board.tile_get_points(coord).into_iter().collect();
+                assert_eq!(vertices, points);
+            }
+        }
+
+        for x in 0..6 {
+            for y in 0..11 {
+                let coord = Coordinate::new(x, y);
+                let vertex = Vertex::from_coordinate(coord);
+                let neighbors = vertex
+                    .neighbors()
+                    .filter_map(|v| v.to_coordinate())
+                    .filter(|c| c.x < 6 && c.y < 11)
+                    .collect::<HashSet<_>>();
+                let points = board
+                    .point_get_points(coord)
+                    .into_iter()
+                    .flatten()
+                    .collect::<HashSet<_>>();
+                assert_eq!(neighbors, points);
+
+                let hexes = vertex
+                    .hexes()
+                    .filter_map(|h| h.to_coordinate())
+                    .filter(|c| c.x < 5 && c.y < 5)
+                    .collect::<HashSet<_>>();
+                let tiles = board
+                    .ponint_get_tile(coord)
+                    .into_iter()
+                    .flatten()
+                    .collect::<HashSet<_>>();
+                assert_eq!(hexes, tiles);
+            }
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::{fmt::Debug, future::Future, ops::RangeInclusive};
+
+use super::player::GamePlayer;
+
+// a game the server can host, the lobby offers every registered one
+pub trait Game: Sized + 'static {
+    // shown in the lobby and the key of the registry
+    const NAME: &'static str;
+    const PLAYERS: RangeInclusive<usize>;
+
+    type Action: Debug + Send + 'static;
+    type Message: Debug + Clone + Send + 'static;
+    // picked by the host for every game of this kind
+    type Config: Clone + Send + Sync + 'static;
+    type Outcome: Debug + Send + 'static;
+
+    fn run<P>(
+        players: Vec<P>, config: Self::Config,
+    ) -> impl Future<Output = Self::Outcome> + Send
+    where
+        P: GamePlayer<Self> + Send + 'static;
+}
@@ -1,4 +1,7 @@
 pub mod catan;
+pub mod codec;
 pub mod element;
+pub mod game;
 pub mod network;
 pub mod player;
+pub mod snake;
@@ -1,19 +1,136 @@
 use serde::{Deserialize, Serialize};
 
-use super::catan::element::{GameAct, GameMsg};
+use super::{
+    catan::element::{GameAct, GameMsg},
+    codec::{CompactAct, CompactMsg, Encoding},
+    snake::element::{SnakeAct, SnakeMsg},
+};
 #[cfg(not(target_family = "wasm"))]
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Duration, SystemTime};
 #[cfg(target_family = "wasm")]
-use web_time::{Duration, Instant, SystemTime};
+use web_time::{Duration, SystemTime};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+// bump on any change to the wire format, new enum variants only ever go at the end
+pub const PROTOCOL_VERSION: u32 = 7;
+// oldest client the server still understands, older ones are told to reload since
+// the transport cannot skip messages they do not know
+pub const MIN_PROTOCOL_VERSION: u32 = 7;
+// features a client must announce to join
+pub const REQUIRED_FEATURES: [&str; 1] = ["catan"];
+// features used only when both sides announce them
+pub const OPTIONAL_FEATURES: [&str; 1] = ["compact"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    pub version: u32,
+    pub features: Vec<String>,
+}
+
+impl Handshake {
+    pub fn current() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            features: REQUIRED_FEATURES
+                .iter()
+                .chain(OPTIONAL_FEATURES.iter())
+                .map(|f| f.to_string())
+                .collect(),
+        }
+    }
+
+    // the server answer, the features both sides know
+    pub fn welcome(&self) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            features: self
+                .features
+                .iter()
+                .filter(|f| {
+                    REQUIRED_FEATURES.contains(&f.as_str())
+                        || OPTIONAL_FEATURES.contains(&f.as_str())
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        if self.features.iter().any(|f| f == "compact") {
+            Encoding::Compact
+        } else {
+            Encoding::Plain
+        }
+    }
+
+    // the reason is shown to the player as is
+    pub fn check(&self) -> Result<(), String> {
+        if self.version < MIN_PROTOCOL_VERSION || self.version > PROTOCOL_VERSION {
+            return Err(format!(
+                "Client protocol {} is not supported, the server speaks {} to {}. Please reload the page.",
+                self.version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ));
+        }
+        for feature in REQUIRED_FEATURES {
+            if !self.features.iter().any(|f| f == feature) {
+                return Err(format!("Client does not support {}", feature));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum ServerMsg {
     Catan(GameMsg),
+    Rejected(String),
+    Welcome(Handshake),
+    Compact(CompactMsg),
+    Snake(SnakeMsg),
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl ServerMsg {
+    pub fn encode(msg: GameMsg, encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Compact => match CompactMsg::pack(&msg) {
+                Some(msg) => ServerMsg::Compact(msg),
+                None => ServerMsg::Catan(msg),
+            },
+            Encoding::Plain => ServerMsg::Catan(msg),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum ClientMsg {
     Catan(GameAct),
+    Compact(CompactAct),
+    // the game to play from protocol 3 to 6, only kept so that the variants after
+    // it keep their index
+    Join(String),
+    Snake(SnakeAct),
+    // a join with a rule preset, the host rules apply without one
+    JoinWith((String, Option<String>)),
+}
+
+impl ClientMsg {
+    pub fn encode(act: GameAct, encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Compact => match CompactAct::pack(&act) {
+                Some(act) => ClientMsg::Compact(act),
+                None => ClientMsg::Catan(act),
+            },
+            Encoding::Plain => ClientMsg::Catan(act),
+        }
+    }
+
+    // None for actions of another game
+    pub fn decode(self) -> Option<GameAct> {
+        match self {
+            ClientMsg::Catan(act) => Some(act),
+            ClientMsg::Compact(act) => Some(act.unpack()),
+            _ => None,
+        }
+    }
 }
 #[cfg(feature = "server")]
 pub type NetworkServer = bevy_simplenet::Server<NetworkChannel>;
@@ -28,7 +145,7 @@ pub type NetworkClientEvent = bevy_simplenet::ClientEventFrom<NetworkChannel>;
 #[derive(Debug, Clone)]
 pub struct NetworkChannel;
 impl bevy_simplenet::ChannelPack for NetworkChannel {
-    type ConnectMsg = ();
+    type ConnectMsg = Handshake;
     type ClientMsg = ClientMsg;
     type ClientRequest = ();
     type ServerMsg = ServerMsg;
@@ -49,11 +166,18 @@ pub fn new_server() -> NetworkServer {
     )
 }
 
+pub const DEFAULT_SERVER: &str = "ws://boardgame.studio:9001/ws";
+
 #[cfg(feature = "client")]
 pub fn new_client() -> NetworkClient {
+    new_client_at(DEFAULT_SERVER)
+}
+
+#[cfg(feature = "client")]
+pub fn new_client_at(server: &str) -> NetworkClient {
     bevy_simplenet::ClientFactory::<NetworkChannel>::new("network").new_client(
         enfync::builtin::Handle::default(), //automatically selects native/WASM runtime
-        url::Url::parse("ws://boardgame.studio:9001/ws").unwrap(),
+        url::Url::parse(server).unwrap(),
         bevy_simplenet::AuthRequest::None {
             client_id: SystemTime::now()
                 .duration_since(
@@ -75,6 +199,353 @@ pub fn new_client() -> NetworkClient {
             heartbeat_interval: Duration::from_secs(300),
             ..Default::default()
         },
-        (),
+        Handshake::current(),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+
+    use bincode::Options;
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::{
+        catan::element::{
+            Build, BuildRoad, GameStart, StealResource, Tile, TileKind, TradeRequest,
+            TradeTarget,
+        },
+        element::{Coordinate, Line},
+        snake::element::{Direction, Snake, SnakeBoard},
+    };
+
+    // the transport sends messages as bincode with its default options, which
+    // tags enum variants by index
+    fn wire() -> impl bincode::Options {
+        bincode::DefaultOptions::new()
+    }
+
+    // a message whose bytes no longer match its fixture breaks deployed clients,
+    // the fixtures keep the bytes in hex
+    fn check<T>(fixtures: &serde_json::Value, name: &str, value: T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let fixture = fixtures[name]
+            .as_str()
+            .unwrap_or_else(|| panic!("missing fixture {}", name));
+        let bytes = (0..fixture.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&fixture[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            wire().serialize(&value).unwrap(),
+            bytes,
+            "{} changed its encoding",
+            name
+        );
+        assert_eq!(wire().deserialize::<T>(&bytes).unwrap(), value);
+    }
+
+    // the handshake travels as json in the query of the websocket url
+    fn check_handshake(fixtures: &serde_json::Value, handshake: Handshake) {
+        assert_eq!(
+            serde_json::to_value(&handshake).unwrap(),
+            fixtures["handshake"],
+            "the handshake changed its encoding"
+        );
+        assert_eq!(
+            serde_json::from_value::<Handshake>(fixtures["handshake"].clone()).unwrap(),
+            handshake
+        );
+    }
+
+    #[test]
+    fn test_protocol_fixtures() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v1.json")).unwrap();
+        let point = Coordinate::new(2, 3);
+        let road = Line::new(point, Coordinate::new(2, 4));
+
+        let handshake = Handshake {
+            version: 1,
+            features: vec!["catan".to_owned()],
+        };
+        assert_eq!(handshake.encoding(), Encoding::Plain);
+        check_handshake(&fixtures, handshake);
+        check(
+            &fixtures,
+            "rejected",
+            ServerMsg::Rejected("Client protocol 0 is not supported".to_owned()),
+        );
+        check(&fixtures, "turn", ServerMsg::Catan(GameMsg::PlayerTurn(1)));
+        check(
+            &fixtures,
+            "roll_dice",
+            ServerMsg::Catan(GameMsg::PlayerRollDice((3, 4))),
+        );
+        check(
+            &fixtures,
+            "start_select_robber",
+            ServerMsg::Catan(GameMsg::PlayerStartSelectRobber()),
+        );
+        check(
+            &fixtures,
+            "build_road",
+            ServerMsg::Catan(GameMsg::PlayerBuildRoad(BuildRoad { player: 0, road })),
+        );
+        check(
+            &fixtures,
+            "steal_resource",
+            ServerMsg::Catan(GameMsg::PlayerStealResource(StealResource {
+                player: 0,
+                target: 1,
+                kind: Some(TileKind::Wool),
+            })),
+        );
+        check(
+            &fixtures,
+            "bank_resources",
+            ServerMsg::Catan(GameMsg::BankResources([0, 0, 19, 19, 19, 19, 19])),
+        );
+        check(
+            &fixtures,
+            "act_build_road",
+            ClientMsg::Catan(GameAct::BuildRoad(road.start, road.end)),
+        );
+        check(
+            &fixtures,
+            "act_build_settlement",
+            ClientMsg::Catan(GameAct::BuildSettlement(point)),
+        );
+        check(
+            &fixtures,
+            "act_trade_request",
+            ClientMsg::Catan(GameAct::TradeRequest(TradeRequest::new(
+                vec![(TileKind::Wood, 2)],
+                vec![(TileKind::Stone, 1)],
+                TradeTarget::Player,
+            ))),
+        );
+        check(
+            &fixtures,
+            "act_end_turn",
+            ClientMsg::Catan(GameAct::EndTurn),
+        );
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v2() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v2.json")).unwrap();
+        let point = Coordinate::new(2, 3);
+        let road = Line::new(point, Coordinate::new(2, 4));
+
+        let handshake = Handshake {
+            version: 2,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake.clone());
+        check(&fixtures, "welcome", ServerMsg::Welcome(handshake));
+        check(
+            &fixtures,
+            "compact_roll_dice",
+            ServerMsg::encode(GameMsg::PlayerRollDice((3, 4)), Encoding::Compact),
+        );
+        check(
+            &fixtures,
+            "compact_build_road",
+            ServerMsg::encode(
+                GameMsg::PlayerBuildRoad(BuildRoad { player: 1, road }),
+                Encoding::Compact,
+            ),
+        );
+        check(
+            &fixtures,
+            "compact_bank_resources",
+            ServerMsg::encode(
+                GameMsg::BankResources([0, 0, 19, 18, 17, 16, 15]),
+                Encoding::Compact,
+            ),
+        );
+        check(
+            &fixtures,
+            "act_compact_build_settlement",
+            ClientMsg::encode(GameAct::BuildSettlement(point), Encoding::Compact),
+        );
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v3() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v3.json")).unwrap();
+
+        let handshake = Handshake {
+            version: 3,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake.clone());
+        check(&fixtures, "welcome", ServerMsg::Welcome(handshake));
+        check(&fixtures, "join", ClientMsg::Join("snake".to_owned()));
+        check(
+            &fixtures,
+            "act_snake_turn",
+            ClientMsg::Snake(SnakeAct::Turn(Direction::Up)),
+        );
+        check(
+            &fixtures,
+            "snake_board",
+            ServerMsg::Snake(SnakeMsg::Board(SnakeBoard {
+                width: 20,
+                height: 20,
+                tick: 7,
+                snakes: vec![Snake {
+                    body: vec![Coordinate::new(4, 3), Coordinate::new(3, 3)],
+                    direction: Direction::Right,
+                    alive: true,
+                }],
+                food: vec![Coordinate::new(9, 9)],
+            })),
+        );
+        check(
+            &fixtures,
+            "snake_game_over",
+            ServerMsg::Snake(SnakeMsg::GameOver(Some(1))),
+        );
+        assert_eq!(ClientMsg::Join("snake".to_owned()).decode(), None);
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v4() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v4.json")).unwrap();
+
+        let handshake = Handshake {
+            version: 4,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake);
+        check(
+            &fixtures,
+            "checksum",
+            ServerMsg::Catan(GameMsg::Checksum(0x1234_5678_9abc_def0)),
+        );
+        check(&fixtures, "act_resync", ClientMsg::Catan(GameAct::Resync));
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v5() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v5.json")).unwrap();
+
+        let handshake = Handshake {
+            version: 5,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake);
+        let mut tile = Tile::default();
+        tile.set_kind(TileKind::Wood);
+        tile.set_number(6);
+        let start = GameStart {
+            tile: vec![vec![tile]],
+            harbor: Vec::new(),
+            robber: Coordinate::new(0, 0),
+            dice_map: [(6, vec![Coordinate::new(0, 0)])].into(),
+            bank: [0, 0, 19, 19, 19, 19, 19],
+            players: Vec::new(),
+            you: 1,
+            names: vec!["Ann".to_owned(), "Bob".to_owned()],
+        };
+        check(
+            &fixtures,
+            "game_start",
+            ServerMsg::Catan(GameMsg::GameStart(start.clone())),
+        );
+        check(
+            &fixtures,
+            "compact_game_start",
+            ServerMsg::encode(GameMsg::GameStart(start), Encoding::Compact),
+        );
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v6() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v6.json")).unwrap();
+        let road = Line::new(Coordinate::new(2, 3), Coordinate::new(2, 4));
+
+        let handshake = Handshake {
+            version: 6,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake);
+        check(&fixtures, "act_undo", ClientMsg::Catan(GameAct::Undo));
+        check(
+            &fixtures,
+            "undo_road",
+            ServerMsg::Catan(GameMsg::PlayerUndo((1, Build::Road(road)))),
+        );
+        check(
+            &fixtures,
+            "undo_city",
+            ServerMsg::Catan(GameMsg::PlayerUndo((0, Build::City(road.start)))),
+        );
+    }
+
+    #[test]
+    fn test_protocol_fixtures_v7() {
+        let fixtures: serde_json::Value =
+            serde_json::from_str(include_str!("../fixtures/protocol_v7.json")).unwrap();
+
+        let handshake = Handshake {
+            version: 7,
+            features: vec!["catan".to_owned(), "compact".to_owned()],
+        };
+        check_handshake(&fixtures, handshake);
+        check(
+            &fixtures,
+            "join_preset",
+            ClientMsg::JoinWith(("catan".to_owned(), Some("quick".to_owned()))),
+        );
+        check(
+            &fixtures,
+            "join_host_rules",
+            ClientMsg::JoinWith(("snake".to_owned(), None)),
+        );
+        assert_eq!(
+            ClientMsg::JoinWith(("catan".to_owned(), None)).decode(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_handshake_check() {
+        Handshake::current().check().unwrap();
+
+        // the fixtures of older versions still decode, but their clients cannot
+        // pick a rule preset and are told to reload
+        for version in 1..MIN_PROTOCOL_VERSION {
+            let mut old = Handshake::current();
+            old.version = version;
+            old.check().expect_err("older than the server supports");
+        }
+
+        let mut new = Handshake::current();
+        new.version = PROTOCOL_VERSION + 1;
+        new.check().expect_err("newer than the server");
+
+        let mut partial = Handshake::current();
+        partial.features.clear();
+        partial.check().expect_err("missing a required feature");
+
+        let mut plain = Handshake::current();
+        plain.features.retain(|f| f != "compact");
+        plain.features.push("telepathy".to_owned());
+        let welcome = plain.welcome();
+        assert_eq!(welcome.version, PROTOCOL_VERSION);
+        assert_eq!(welcome.features, vec!["catan".to_owned()]);
+        assert_eq!(welcome.encoding(), Encoding::Plain);
+        assert_eq!(Handshake::current().welcome().encoding(), Encoding::Compact);
+    }
+}
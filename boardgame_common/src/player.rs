@@ -1,18 +1,11 @@
-use super::catan::element::{GameAct, GameMsg};
+use std::future::Future;
 
-pub enum GamePlayerAction {
-    Catan(GameAct),
-    PlaceHolder,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum GamePlayerMessage {
-    Catan(GameMsg),
-    PlaceHolder,
-}
+use super::game::Game;
 
-pub trait GamePlayer {
+pub trait GamePlayer<G: Game> {
     fn get_name(&self) -> String;
-    async fn get_action(&mut self) -> GamePlayerAction;
-    async fn send_message(&mut self, message: GamePlayerMessage);
+    /// Must be cancel safe, the game may drop a pending call while it waits on
+    /// several players at once. None once the player is gone.
+    fn get_action(&mut self) -> impl Future<Output = Option<G::Action>> + Send;
+    fn send_message(&mut self, message: G::Message) -> impl Future<Output = ()> + Send;
 }
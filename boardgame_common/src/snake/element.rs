@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::element::Coordinate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Up,
+    Right,
+    Down,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+        }
+    }
+
+    // None when the step leaves a width x height board, y grows upwards
+    pub fn step(
+        self, from: Coordinate, width: usize, height: usize,
+    ) -> Option<Coordinate> {
+        let Coordinate { x, y } = from;
+        let (x, y) = match self {
+            Self::Left => (x.checked_sub(1)?, y),
+            Self::Right => (x + 1, y),
+            Self::Down => (x, y.checked_sub(1)?),
+            Self::Up => (x, y + 1),
+        };
+        if x < width && y < height {
+            Some(Coordinate { x, y })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snake {
+    // head first
+    pub body: Vec<Coordinate>,
+    pub direction: Direction,
+    pub alive: bool,
+}
+
+impl Snake {
+    pub fn head(&self) -> Coordinate {
+        self.body[0]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnakeBoard {
+    pub width: usize,
+    pub height: usize,
+    pub tick: u64,
+    // indexed by seat
+    pub snakes: Vec<Snake>,
+    // shared by all snakes
+    pub food: Vec<Coordinate>,
+}
+
+impl SnakeBoard {
+    // dead snakes are gone from the board
+    pub fn is_free(&self, cell: Coordinate) -> bool {
+        !self.food.contains(&cell)
+            && !self
+                .snakes
+                .iter()
+                .any(|snake| snake.alive && snake.body.contains(&cell))
+    }
+
+    pub fn alive(&self) -> usize {
+        self.snakes.iter().filter(|snake| snake.alive).count()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnakeAct {
+    Turn(Direction),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnakeMsg {
+    // the seat of the receiving player
+    GameStart(usize, SnakeBoard),
+    Board(SnakeBoard),
+    // the last snake standing, None when the rest died together
+    GameOver(Option<usize>),
+}
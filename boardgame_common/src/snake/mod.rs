@@ -0,0 +1,3 @@
+pub mod element;
+
+pub const NAME: &str = "snake";
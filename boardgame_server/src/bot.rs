@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use boardgame_common::{
+    catan::element::*,
+    element::{Coordinate, Line},
+};
+
+use super::{game::CatanGame, local::LocalSeat};
+
+// keeps a local game moving, it takes a good start and answers every prompt but
+// never builds, trades or plays a card on its own
+#[derive(Default)]
+pub struct CatanBot {
+    board: Option<CatanCommon>,
+    size: (usize, usize),
+    me: usize,
+    current: usize,
+    resources: [usize; TileKind::Max as usize],
+    // the last settlement asked for, and those the game turned down, asked
+    // again it picks another spot
+    picked: Option<Coordinate>,
+    refused: Vec<Coordinate>,
+}
+
+impl CatanBot {
+    pub async fn play(mut self, mut seat: LocalSeat<CatanGame>) {
+        while let Some(msg) = seat.messages.recv().await {
+            for action in self.react(&msg) {
+                if seat.actions.send(action).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    // the actions owed for a message, in the order the game asks for them
+    pub fn react(&mut self, msg: &GameMsg) -> Vec<GameAct> {
+        let me = self.me;
+        let mine = self.current == me;
+        match msg {
+            GameMsg::GameStart(start) => self.start(start.clone()),
+            GameMsg::PlayerTurn(player) => self.current = *player,
+            GameMsg::PlayerInit(player) if *player == me => return self.place_start(),
+            GameMsg::PlayerRollDice((dice1, dice2)) if mine && dice1 + dice2 != 7 => {
+                return vec![GameAct::EndTurn];
+            },
+            GameMsg::PlayerDropResources((player, count)) if *player == me => {
+                return vec![GameAct::DropResource(self.discard(*count))];
+            },
+            GameMsg::PlayerStartSelectRobber() if mine => {
+                return vec![GameAct::SelectRobber(self.robber_target())];
+            },
+            GameMsg::PlayerStartSteal(candidates) if mine => {
+                return match candidates.first() {
+                    Some(target) => {
+                        vec![GameAct::StealResource(*target), GameAct::EndTurn]
+                    },
+                    None => vec![GameAct::EndTurn],
+                };
+            },
+            GameMsg::PlayerTradeRequest((player, _)) if *player != me => {
+                return vec![GameAct::TradeResponse(TradeResponse::Reject)];
+            },
+            GameMsg::PlayerBuildRoad(build) => {
+                self.board_mut().add_road(build.player, build.road);
+            },
+            GameMsg::PlayerBuildSettlement(build) => {
+                self.board_mut().add_settlement(build.player, build.point);
+            },
+            GameMsg::PlayerBuildCity(build) => {
+                self.board_mut().add_city(build.player, build.point);
+            },
+            GameMsg::PlayerSelectRobber(select) => {
+                self.board_mut().set_robber(select.coord);
+            },
+            GameMsg::PlayerUseDevelopmentCard(card) => match &card.usage {
+                DevelopmentCard::Knight(select) => {
+                    self.board_mut().set_robber(select.coord);
+                },
+                DevelopmentCard::RoadBuilding(roads) => {
+                    for road in roads {
+                        self.board_mut().add_road(card.player, *road);
+                    }
+                },
+                DevelopmentCard::Monopoly(kind) if card.player != me => {
+                    self.resources[*kind as usize] = 0;
+                },
+                _ => {},
+            },
+            GameMsg::PlayerOfferResources(offer) if offer.player == me => {
+                let count = &mut self.resources[offer.kind as usize];
+                *count = count.saturating_add_signed(offer.count);
+            },
+            GameMsg::PlayerStealResource(steal) => {
+                if let Some(kind) = steal.kind {
+                    if steal.player == me {
+                        self.resources[kind as usize] += 1;
+                    } else if steal.target == me {
+                        self.resources[kind as usize] -= 1;
+                    }
+                }
+            },
+            _ => {},
+        }
+        Vec::new()
+    }
+
+    fn start(&mut self, start: GameStart) {
+        let rows = start.tile.len() + 1;
+        let columns = 2 * start.tile[0].len() + 1;
+        let points = vec![vec![Point::default(); columns]; rows];
+        self.size = (rows, columns);
+        self.me = start.you;
+        self.resources = start.players[start.you].resources;
+        self.board = Some(CatanCommon::new(
+            start.tile,
+            points,
+            HashMap::new(),
+            start.harbor,
+            start.dice_map,
+            start.robber,
+        ));
+    }
+
+    // the game always opens with a GameStart
+    fn board(&self) -> &CatanCommon {
+        self.board.as_ref().expect("no game started")
+    }
+
+    fn board_mut(&mut self) -> &mut CatanCommon {
+        self.board.as_mut().expect("no game started")
+    }
+
+    // an out of range neighbour is never valid, check before looking it up
+    fn neighbours(&self, point: Coordinate) -> Vec<Coordinate> {
+        let board = self.board();
+        board
+            .point_get_points(point)
+            .into_iter()
+            .flatten()
+            .filter(|p| board.point_valid(*p))
+            .collect()
+    }
+
+    // the dots under the numbers around a point, how often it pays out
+    fn value(&self, point: Coordinate) -> usize {
+        let board = self.board();
+        board
+            .ponint_get_tile(point)
+            .into_iter()
+            .flatten()
+            .filter(|tile| board.tile(*tile).is_resource())
+            .filter_map(|tile| board.tile(tile).number())
+            .map(|number| 6 - number.abs_diff(7))
+            .sum()
+    }
+
+    // a settlement of ours without a road to it yet, the road was turned down
+    fn unfinished_start(&self) -> Option<(Coordinate, Coordinate)> {
+        let board = self.board();
+        let roads = board.roads();
+        for x in 0..self.size.0 {
+            for y in 0..self.size.1 {
+                let point = Coordinate::new(x, y);
+                if !board.point_valid(point)
+                    || board.point(point).owner() != Some(self.me)
+                {
+                    continue;
+                }
+                let neighbours = self.neighbours(point);
+                if neighbours
+                    .iter()
+                    .any(|p| roads.get(&Line::new(point, *p)) == Some(&self.me))
+                {
+                    continue;
+                }
+                let to = neighbours
+                    .into_iter()
+                    .find(|p| !roads.contains_key(&Line::new(point, *p)))?;
+                return Some((point, to));
+            }
+        }
+        None
+    }
+
+    fn place_start(&mut self) -> Vec<GameAct> {
+        if let Some((point, to)) = self.unfinished_start() {
+            return vec![GameAct::BuildRoad(point, to)];
+        }
+        if let Some(point) = self.picked.take() {
+            if self.board().point(point).owner() != Some(self.me) {
+                self.refused.push(point);
+            }
+        }
+        let board = self.board();
+        let mut best: Option<(usize, Coordinate, Coordinate)> = None;
+        for x in 0..self.size.0 {
+            for y in 0..self.size.1 {
+                let point = Coordinate::new(x, y);
+                if !board.point_valid(point)
+                    || board.point(point).is_owned()
+                    || self.refused.contains(&point)
+                {
+                    continue;
+                }
+                let neighbours = self.neighbours(point);
+                if neighbours.iter().any(|p| board.point(*p).is_owned()) {
+                    continue;
+                }
+                let Some(to) = neighbours
+                    .into_iter()
+                    .find(|p| !board.roads().contains_key(&Line::new(point, *p)))
+                else {
+                    continue;
+                };
+                let value = self.value(point);
+                if best.is_none_or(|(best, _, _)| value > best) {
+                    best = Some((value, point, to));
+                }
+            }
+        }
+        let (_, point, to) = best.expect("no room left for a settlement");
+        self.picked = Some(point);
+        vec![
+            GameAct::BuildSettlement(point),
+            GameAct::BuildRoad(point, to),
+        ]
+    }
+
+    // the tile touching the most rivals, rather one without our own buildings
+    fn robber_target(&self) -> Coordinate {
+        let board = self.board();
+        let mut best: Option<(usize, Coordinate)> = None;
+        for x in 0..board.tiles().len() {
+            for y in 0..board.tiles()[x].len() {
+                let tile = Coordinate::new(x, y);
+                if board.tile(tile).is_empty() || tile == board.robber() {
+                    continue;
+                }
+                let owners = board
+                    .tile_get_points(tile)
+                    .into_iter()
+                    .filter(|p| board.point_valid(*p))
+                    .filter_map(|p| board.point(p).owner())
+                    .collect::<Vec<_>>();
+                let score = match owners.contains(&self.me) {
+                    true => 0,
+                    false => owners.len() + 1,
+                };
+                if best.is_none_or(|(best, _)| score > best) {
+                    best = Some((score, tile));
+                }
+            }
+        }
+        best.expect("no tile left for the robber").1
+    }
+
+    // the most plentiful resources go first
+    fn discard(&self, count: usize) -> Vec<(TileKind, usize)> {
+        let mut left = self.resources;
+        let mut drop = [0; TileKind::Max as usize];
+        for _ in 0..count {
+            let kind = (0..left.len()).max_by_key(|kind| left[*kind]).unwrap();
+            if left[kind] == 0 {
+                break;
+            }
+            left[kind] -= 1;
+            drop[kind] += 1;
+        }
+        drop.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(kind, count)| (TileKind::try_from(kind as u8).unwrap(), *count))
+            .collect()
+    }
+}
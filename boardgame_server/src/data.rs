@@ -1,121 +1,511 @@
-use boardgame_common::catan::element::{DevCard, Point, Tile, TileKind};
-use boardgame_common::element::{Coordinate, Line};
-use rand::{prelude::SliceRandom, thread_rng};
+use boardgame_common::catan::element::{Point, Tile, TileKind};
+use boardgame_common::element::{Coordinate, Edge, Hex, Line};
+use rand::{prelude::SliceRandom, rngs::StdRng, thread_rng, SeedableRng};
 use std::collections::HashMap;
 
+use super::map::{CatanMap, Placement};
+
+const ASSIGNABLE: [[bool; 5]; 5] = [
+    [false, true, true, true, false],
+    [true, true, true, true, false],
+    [true, true, true, true, true],
+    [true, true, true, true, false],
+    [false, true, true, true, false],
+];
+
+const HARBOR_CANDIATE: [Line; 9] = [
+    Line {
+        start: Coordinate { x: 1, y: 1 },
+        end: Coordinate { x: 1, y: 2 },
+    },
+    Line {
+        start: Coordinate { x: 2, y: 0 },
+        end: Coordinate { x: 3, y: 0 },
+    },
+    Line {
+        start: Coordinate { x: 4, y: 1 },
+        end: Coordinate { x: 4, y: 2 },
+    },
+    Line {
+        start: Coordinate { x: 5, y: 4 },
+        end: Coordinate { x: 5, y: 5 },
+    },
+    Line {
+        start: Coordinate { x: 5, y: 7 },
+        end: Coordinate { x: 5, y: 8 },
+    },
+    Line {
+        start: Coordinate { x: 0, y: 4 },
+        end: Coordinate { x: 0, y: 5 },
+    },
+    Line {
+        start: Coordinate { x: 0, y: 7 },
+        end: Coordinate { x: 0, y: 8 },
+    },
+    Line {
+        start: Coordinate { x: 1, y: 9 },
+        end: Coordinate { x: 2, y: 9 },
+    },
+    Line {
+        start: Coordinate { x: 3, y: 9 },
+        end: Coordinate { x: 4, y: 9 },
+    },
+];
+
+const TILE_KINDS: [TileKind; 19] = [
+    TileKind::Dessert,
+    TileKind::Wool,
+    TileKind::Wool,
+    TileKind::Wool,
+    TileKind::Wool,
+    TileKind::Wood,
+    TileKind::Wood,
+    TileKind::Wood,
+    TileKind::Wood,
+    TileKind::Grain,
+    TileKind::Grain,
+    TileKind::Grain,
+    TileKind::Grain,
+    TileKind::Stone,
+    TileKind::Stone,
+    TileKind::Stone,
+    TileKind::Brick,
+    TileKind::Brick,
+    TileKind::Brick,
+];
+
+const HARBOR_TILE_KINDS: [TileKind; 9] = [
+    TileKind::Wool,
+    TileKind::Wood,
+    TileKind::Grain,
+    TileKind::Stone,
+    TileKind::Brick,
+    TileKind::Dessert,
+    TileKind::Dessert,
+    TileKind::Dessert,
+    TileKind::Dessert,
+];
+
+const DICE_COUNT: [u8; 13] = [0, 0, 1, 2, 2, 2, 2, 1, 2, 2, 2, 2, 1];
+
+// the starting map of the rulebook, read row by row over ASSIGNABLE
+const BEGINNER_TILE_KINDS: [TileKind; 19] = [
+    TileKind::Stone,
+    TileKind::Wool,
+    TileKind::Wood,
+    TileKind::Grain,
+    TileKind::Brick,
+    TileKind::Wool,
+    TileKind::Brick,
+    TileKind::Grain,
+    TileKind::Wood,
+    TileKind::Dessert,
+    TileKind::Wood,
+    TileKind::Stone,
+    TileKind::Wood,
+    TileKind::Stone,
+    TileKind::Grain,
+    TileKind::Wool,
+    TileKind::Brick,
+    TileKind::Grain,
+    TileKind::Wool,
+];
+
+const BEGINNER_NUMBERS: [usize; 18] =
+    [10, 2, 9, 12, 6, 4, 10, 9, 11, 3, 8, 8, 3, 4, 5, 5, 6, 11];
+
+// in the order of HARBOR_CANDIATE
+const BEGINNER_HARBOR_KINDS: [TileKind; 9] = [
+    TileKind::Stone,
+    TileKind::Grain,
+    TileKind::Dessert,
+    TileKind::Wood,
+    TileKind::Brick,
+    TileKind::Dessert,
+    TileKind::Wool,
+    TileKind::Dessert,
+    TileKind::Dessert,
+];
+
+// suggested settlements with the far end of their road, for up to four players
+const BEGINNER_STARTS: [[(Coordinate, Coordinate); 2]; 4] = [
+    [
+        (Coordinate { x: 4, y: 4 }, Coordinate { x: 5, y: 4 }),
+        (Coordinate { x: 1, y: 7 }, Coordinate { x: 1, y: 8 }),
+    ],
+    [
+        (Coordinate { x: 3, y: 8 }, Coordinate { x: 3, y: 7 }),
+        (Coordinate { x: 1, y: 3 }, Coordinate { x: 1, y: 2 }),
+    ],
+    [
+        (Coordinate { x: 3, y: 2 }, Coordinate { x: 3, y: 1 }),
+        (Coordinate { x: 4, y: 6 }, Coordinate { x: 4, y: 7 }),
+    ],
+    [
+        (Coordinate { x: 1, y: 5 }, Coordinate { x: 2, y: 5 }),
+        (Coordinate { x: 2, y: 9 }, Coordinate { x: 1, y: 9 }),
+    ],
+];
+
 pub struct CatanData {
     pub robber: Coordinate,
-    pub dev_cards: Vec<DevCard>,
     pub tiles: Vec<Vec<Tile>>,
     pub dics_map: HashMap<usize, Vec<Coordinate>>,
     pub harbors: Vec<(Line, TileKind)>,
     pub points: Vec<Vec<Point>>,
-    pub winscore: u8,
+    pub fairness: f32,
+    // settlement and road of both setup rounds per seat, empty for a manual setup
+    pub starts: Vec<Vec<(Coordinate, Line)>>,
 }
 
 pub enum CatanDataSetup {
     Basic,
+    Balanced(BoardConstraints),
+    // a map loaded and validated when the server started
+    FromMap(CatanMap),
+    // pre-place the suggested starts
+    Beginner(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct BoardConstraints {
+    pub seed: u64,
+    // no 6 or 8 next to another 6 or 8
+    pub no_adjacent_red: bool,
+    pub no_adjacent_same: bool,
+    // largest gap between the richest and the poorest resource, in average pips per tile
+    pub max_pip_spread: f32,
+    // 2:1 harbors never touch a tile of their own resource
+    pub balance_harbors: bool,
+    pub max_attempts: usize,
+    // numbers tried per attempt before the backtracking gives up
+    pub max_placements: usize,
+}
+
+impl BoardConstraints {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            no_adjacent_red: true,
+            no_adjacent_same: true,
+            max_pip_spread: 1.0,
+            balance_harbors: true,
+            max_attempts: 1000,
+            max_placements: 10000,
+        }
+    }
+}
+
+fn pips(number: usize) -> usize {
+    6 - (7 - number as isize).unsigned_abs()
+}
+
+fn is_red(number: usize) -> bool {
+    number == 6 || number == 8
+}
+
+pub(super) struct BoardLayout {
+    pub(super) tiles: Vec<Coordinate>,
+    pub(super) neighbors: HashMap<Coordinate, Vec<Coordinate>>,
+    // land tiles along each harbor edge, none when its points are not adjacent
+    pub(super) harbor_tiles: Vec<Vec<Coordinate>>,
+}
+
+impl BoardLayout {
+    pub(super) fn new(land: &[Vec<bool>], harbors: &[Line]) -> Self {
+        let mut tiles = Vec::new();
+        for (x, column) in land.iter().enumerate() {
+            for (y, land) in column.iter().enumerate() {
+                if *land {
+                    tiles.push(Coordinate { x, y });
+                }
+            }
+        }
+
+        let land_at = |hex: Hex| hex.to_coordinate().filter(|tile| tiles.contains(tile));
+        let neighbors = tiles
+            .iter()
+            .map(|tile| {
+                let hex = Hex::from_coordinate(*tile);
+                (*tile, hex.neighbors().filter_map(land_at).collect())
+            })
+            .collect();
+
+        let harbor_tiles = harbors
+            .iter()
+            .map(|harbor| match Edge::from_line(*harbor) {
+                Some(edge) => edge.hexes().into_iter().filter_map(land_at).collect(),
+                None => Vec::new(),
+            })
+            .collect();
+
+        Self {
+            tiles,
+            neighbors,
+            harbor_tiles,
+        }
+    }
+
+    fn standard() -> Self {
+        let land = ASSIGNABLE
+            .iter()
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>();
+        Self::new(&land, &HARBOR_CANDIATE)
+    }
+
+    fn number_allowed(
+        &self, constraints: &BoardConstraints, tile: Coordinate, number: usize,
+        numbers: &HashMap<Coordinate, usize>,
+    ) -> bool {
+        self.neighbors[&tile]
+            .iter()
+            .all(|other| match numbers.get(other) {
+                Some(other) => {
+                    let red =
+                        constraints.no_adjacent_red && is_red(number) && is_red(*other);
+                    let same = constraints.no_adjacent_same && number == *other;
+                    !(red || same)
+                },
+                None => true,
+            })
+    }
+
+    // backtracks over the remaining numbers, gives up once the budget is spent
+    fn place_numbers(
+        &self, constraints: &BoardConstraints, tiles: &[Coordinate],
+        numbers: &mut HashMap<Coordinate, usize>, pool: &mut Vec<usize>,
+        budget: &mut usize,
+    ) -> bool {
+        let tile = match tiles.get(numbers.len()) {
+            Some(tile) => *tile,
+            None => return true,
+        };
+        let mut tried = Vec::new();
+        for i in 0..pool.len() {
+            let number = pool[i];
+            if tried.contains(&number) {
+                continue;
+            }
+            tried.push(number);
+            if *budget == 0 {
+                return false;
+            }
+            *budget -= 1;
+            if !self.number_allowed(constraints, tile, number, numbers) {
+                continue;
+            }
+            pool.remove(i);
+            numbers.insert(tile, number);
+            if self.place_numbers(constraints, tiles, numbers, pool, budget) {
+                return true;
+            }
+            numbers.remove(&tile);
+            pool.insert(i, number);
+        }
+        false
+    }
+
+    fn adjacent_pairs(&self, tiles: &[Vec<Tile>]) -> (usize, usize) {
+        let mut red = 0;
+        let mut same = 0;
+        for tile in self.tiles.iter() {
+            for other in self.neighbors[tile].iter().filter(|other| *other > tile) {
+                if let (Some(a), Some(b)) = (
+                    tiles[tile.x][tile.y].number(),
+                    tiles[other.x][other.y].number(),
+                ) {
+                    if is_red(a) && is_red(b) {
+                        red += 1;
+                    }
+                    if a == b {
+                        same += 1;
+                    }
+                }
+            }
+        }
+        (red, same)
+    }
+
+    fn pip_spread(&self, tiles: &[Vec<Tile>]) -> f32 {
+        let mut total = [0; TileKind::Max as usize];
+        let mut count = [0; TileKind::Max as usize];
+        for tile in self.tiles.iter() {
+            let tile = &tiles[tile.x][tile.y];
+            if let Some(number) = tile.number() {
+                total[tile.kind() as usize] += pips(number);
+                count[tile.kind() as usize] += 1;
+            }
+        }
+        let average = (0..total.len())
+            .filter(|i| count[*i] > 0)
+            .map(|i| total[i] as f32 / count[i] as f32)
+            .collect::<Vec<_>>();
+        let max = average.iter().cloned().fold(f32::MIN, f32::max);
+        let min = average.iter().cloned().fold(f32::MAX, f32::min);
+        max - min
+    }
+
+    fn unbalanced_harbors(
+        &self, tiles: &[Vec<Tile>], harbors: &[(Line, TileKind)],
+    ) -> usize {
+        harbors
+            .iter()
+            .zip(self.harbor_tiles.iter())
+            .filter(|((_, kind), touching)| {
+                kind.is_resource()
+                    && touching
+                        .iter()
+                        .any(|tile| tiles[tile.x][tile.y].kind() == *kind)
+            })
+            .count()
+    }
+
+    // 100 for a board without hot spots, every flaw costs points
+    pub(super) fn fairness(
+        &self, tiles: &[Vec<Tile>], harbors: &[(Line, TileKind)],
+    ) -> f32 {
+        let (red, same) = self.adjacent_pairs(tiles);
+        let score = 100.0
+            - 10.0 * red as f32
+            - 5.0 * same as f32
+            - 10.0 * self.pip_spread(tiles)
+            - 5.0 * self.unbalanced_harbors(tiles, harbors) as f32;
+        score.max(0.0)
+    }
 }
 
 impl CatanData {
     pub fn new(setup: CatanDataSetup) -> Self {
         match setup {
             CatanDataSetup::Basic => Self::basic(),
+            CatanDataSetup::Balanced(constraints) => Self::balanced(constraints),
+            CatanDataSetup::FromMap(map) => map.build(),
+            CatanDataSetup::Beginner(preplaced) => Self::beginner(preplaced),
         }
     }
 
-    fn basic() -> Self {
-        const ASSIGNABLE: [[bool; 5]; 5] = [
-            [false, true, true, true, false],
-            [true, true, true, true, false],
-            [true, true, true, true, true],
-            [true, true, true, true, false],
-            [false, true, true, true, false],
-        ];
-
-        const HARBOR_CANDIATE: [Line; 9] = [
-            Line {
-                start: Coordinate { x: 1, y: 1 },
-                end: Coordinate { x: 1, y: 2 },
-            },
-            Line {
-                start: Coordinate { x: 2, y: 0 },
-                end: Coordinate { x: 3, y: 0 },
-            },
-            Line {
-                start: Coordinate { x: 4, y: 1 },
-                end: Coordinate { x: 4, y: 2 },
-            },
-            Line {
-                start: Coordinate { x: 5, y: 4 },
-                end: Coordinate { x: 5, y: 5 },
-            },
-            Line {
-                start: Coordinate { x: 5, y: 7 },
-                end: Coordinate { x: 5, y: 8 },
-            },
-            Line {
-                start: Coordinate { x: 0, y: 4 },
-                end: Coordinate { x: 0, y: 5 },
-            },
-            Line {
-                start: Coordinate { x: 0, y: 7 },
-                end: Coordinate { x: 0, y: 8 },
-            },
-            Line {
-                start: Coordinate { x: 1, y: 9 },
-                end: Coordinate { x: 2, y: 9 },
-            },
-            Line {
-                start: Coordinate { x: 3, y: 9 },
-                end: Coordinate { x: 4, y: 9 },
-            },
-        ];
-
-        const TILE_KINDS: [TileKind; 19] = [
-            TileKind::Dessert,
-            TileKind::Wool,
-            TileKind::Wool,
-            TileKind::Wool,
-            TileKind::Wool,
-            TileKind::Wood,
-            TileKind::Wood,
-            TileKind::Wood,
-            TileKind::Wood,
-            TileKind::Grain,
-            TileKind::Grain,
-            TileKind::Grain,
-            TileKind::Grain,
-            TileKind::Stone,
-            TileKind::Stone,
-            TileKind::Stone,
-            TileKind::Brick,
-            TileKind::Brick,
-            TileKind::Brick,
-        ];
-
-        const HARBOR_TILE_KINDS: [TileKind; 9] = [
-            TileKind::Wool,
-            TileKind::Wood,
-            TileKind::Grain,
-            TileKind::Stone,
-            TileKind::Brick,
-            TileKind::Dessert,
-            TileKind::Dessert,
-            TileKind::Dessert,
-            TileKind::Dessert,
-        ];
-
-        const DEVELOPMENT_CARD: [(DevCard, usize); 5] = [
-            (DevCard::Knight, 14),
-            (DevCard::VictoryPoint, 5),
-            (DevCard::RoadBuilding, 2),
-            (DevCard::Monopoly, 2),
-            (DevCard::YearOfPlenty, 2),
-        ];
-
-        const DICE_COUNT: [u8; 13] = [0, 0, 1, 2, 2, 2, 2, 1, 2, 2, 2, 2, 1];
+    fn beginner(preplaced: bool) -> Self {
+        let map = CatanMap {
+            land: ASSIGNABLE.iter().map(|row| row.to_vec()).collect(),
+            tiles: Placement::Fixed(BEGINNER_TILE_KINDS.to_vec()),
+            numbers: Placement::Fixed(BEGINNER_NUMBERS.to_vec()),
+            harbors: HARBOR_CANDIATE.to_vec(),
+            harbor_kinds: Placement::Fixed(BEGINNER_HARBOR_KINDS.to_vec()),
+            robber: None,
+        };
+        let mut data = map.build();
+        if preplaced {
+            data.starts = BEGINNER_STARTS
+                .iter()
+                .map(|seat| {
+                    seat.iter()
+                        .map(|(point, end)| (*point, Line::new(*point, *end)))
+                        .collect()
+                })
+                .collect();
+        }
+        data
+    }
+
+    fn balanced(constraints: BoardConstraints) -> Self {
+        let layout = BoardLayout::standard();
+        let mut rng = StdRng::seed_from_u64(constraints.seed);
+        let mut best: Option<Self> = None;
+
+        for attempt in 0..constraints.max_attempts.max(1) {
+            let mut tile_kind = TILE_KINDS.to_vec();
+            let mut harbor_tile_kind = HARBOR_TILE_KINDS.to_vec();
+            tile_kind.shuffle(&mut rng);
+            harbor_tile_kind.shuffle(&mut rng);
 
+            let mut tiles = vec![vec![Tile::default(); 5]; 5];
+            let mut robber = Coordinate { x: 0, y: 0 };
+            let mut numbered = Vec::new();
+            for (coord, kind) in layout.tiles.iter().zip(tile_kind) {
+                tiles[coord.x][coord.y].set_kind(kind);
+                if kind == TileKind::Dessert {
+                    robber = *coord;
+                } else {
+                    numbered.push(*coord);
+                }
+            }
+
+            let mut pool = Vec::new();
+            for (number, count) in DICE_COUNT.iter().enumerate() {
+                if number != 7 {
+                    pool.extend(std::iter::repeat_n(number, *count as usize));
+                }
+            }
+            pool.shuffle(&mut rng);
+            let mut numbers = HashMap::new();
+            let mut budget = constraints.max_placements;
+            let placed = layout.place_numbers(
+                &constraints,
+                &numbered,
+                &mut numbers,
+                &mut pool,
+                &mut budget,
+            );
+            // the backtracking leaves the pool as it was, the numbers are
+            // dealt in that order and the attempt only counts for fairness
+            if !placed {
+                numbers = numbered.iter().cloned().zip(pool).collect();
+            }
+
+            let mut dics_map = HashMap::new();
+            dics_map.insert(7, vec![robber]);
+            for coord in numbered.iter() {
+                let number = numbers[coord];
+                tiles[coord.x][coord.y].set_number(number);
+                dics_map.entry(number).or_insert(Vec::new()).push(*coord);
+            }
+
+            let harbors = HARBOR_CANDIATE
+                .iter()
+                .cloned()
+                .zip(harbor_tile_kind)
+                .collect::<Vec<_>>();
+
+            let valid = placed
+                && layout.pip_spread(&tiles) <= constraints.max_pip_spread
+                && (!constraints.balance_harbors
+                    || layout.unbalanced_harbors(&tiles, &harbors) == 0);
+            let fairness = layout.fairness(&tiles, &harbors);
+            let data = Self {
+                robber,
+                tiles,
+                dics_map,
+                harbors,
+                points: vec![vec![Point::default(); 11]; 6],
+                fairness,
+                starts: Vec::new(),
+            };
+
+            if valid {
+                println!(
+                    "Board {} generated after {} attempts, fairness {:.1}",
+                    constraints.seed,
+                    attempt + 1,
+                    fairness
+                );
+                return data;
+            }
+            if best.as_ref().is_none_or(|best| best.fairness < fairness) {
+                best = Some(data);
+            }
+        }
+
+        // every attempt yields a board, the first one is always kept
+        let data = best.expect("a balanced board takes at least one attempt");
+        println!(
+            "Board {} does not meet every constraint, using the fairest one, fairness {:.1}",
+            constraints.seed, data.fairness
+        );
+        data
+    }
+
+    fn basic() -> Self {
         let mut tile_kind = TILE_KINDS.to_vec();
         let mut harbor_tile_kind = HARBOR_TILE_KINDS.to_vec();
         let mut tiles = vec![vec![Tile::default(); 5]; 5];
@@ -142,15 +532,14 @@ impl CatanData {
             }
         }
 
-        for i in 0..9 {
-            let harbor = HARBOR_CANDIATE[i];
+        for harbor in HARBOR_CANDIATE {
             let kind = harbor_tile_kind.pop().unwrap();
             harbors.push((harbor, kind));
         }
 
         valid_dice_coord.shuffle(&mut thread_rng());
-        for i in 0..DICE_COUNT.len() {
-            let mut dice = DICE_COUNT[i];
+        for (i, count) in DICE_COUNT.iter().enumerate() {
+            let mut dice = *count;
             while dice > 0 {
                 if i == 7 {
                     break;
@@ -166,26 +555,19 @@ impl CatanData {
             }
         }
 
-        let mut dev_cards = Vec::new();
-        for (card, count) in DEVELOPMENT_CARD.iter() {
-            for _ in 0..*count {
-                dev_cards.push(*card);
-            }
-        }
-        dev_cards.shuffle(&mut thread_rng());
-
         let points_x = tiles.len() + 1;
         let points_y = tiles.len() * 2 + 1;
         let points = vec![vec![Point::default(); points_y]; points_x];
+        let fairness = BoardLayout::standard().fairness(&tiles, &harbors);
 
         Self {
             robber,
-            dev_cards,
             tiles,
             dics_map,
             harbors,
             points,
-            winscore: 10,
+            fairness,
+            starts: Vec::new(),
         }
     }
 }
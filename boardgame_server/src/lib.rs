@@ -0,0 +1,12 @@
+pub mod bot;
+pub mod data;
+pub mod game;
+#[cfg(feature = "network")]
+pub mod lobby;
+pub mod local;
+pub mod map;
+#[cfg(feature = "network")]
+pub mod registry;
+pub mod snake;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use boardgame_common::network::{new_server, ClientMsg, NetworkServerEvent, ServerMsg};
+use tokio::select;
+
+use super::registry::{Connection, GameRegistry};
+
+// clients waiting for a game to fill up
+struct Lobby {
+    registry: GameRegistry,
+    // connected but not yet joined a game
+    pending: HashMap<u128, Connection>,
+    // by game and rule preset, only players asking for the same rules meet
+    waiting: HashMap<(String, Option<String>), Vec<Connection>>,
+}
+
+impl Lobby {
+    fn new(registry: GameRegistry) -> Self {
+        Self {
+            registry,
+            pending: HashMap::new(),
+            waiting: HashMap::new(),
+        }
+    }
+
+    fn join(
+        &mut self, name: &str, preset: Option<String>, conn: Connection,
+    ) -> Result<(), String> {
+        let seats = match self.registry.seats(name) {
+            Some(seats) => seats,
+            None => return Err(format!("This server does not host {}", name)),
+        };
+        if let Some(preset) = &preset {
+            self.registry.check_preset(name, preset)?;
+        }
+        let key = (name.to_owned(), preset);
+        let queue = self.waiting.entry(key.clone()).or_default();
+        queue.push(conn);
+        println!(
+            "{} joined {} {} ({}/{})",
+            queue.last().unwrap().client_id,
+            name,
+            key.1.as_deref().unwrap_or("with the host rules"),
+            queue.len(),
+            seats
+        );
+        if queue.len() >= seats {
+            let conns = std::mem::take(queue);
+            if let Err(err) = self.registry.launch(name, key.1.as_deref(), conns) {
+                println!("{}", err);
+            }
+        }
+        Ok(())
+    }
+
+    // a client picking its game after the welcome
+    fn select(
+        &mut self, client_id: u128, name: &str, preset: Option<String>,
+    ) -> Result<(), String> {
+        match self.pending.remove(&client_id) {
+            Some(conn) => self.join(name, preset, conn),
+            None => Ok(()),
+        }
+    }
+
+    fn leave(&mut self, client_id: u128) {
+        let _ = self.pending.remove(&client_id);
+        for queue in self.waiting.values_mut() {
+            queue.retain(|conn| conn.client_id != client_id);
+        }
+    }
+}
+
+// accepts clients and starts the registered games, returns when the server stops
+pub async fn serve(registry: GameRegistry) {
+    let mut lobby = Lobby::new(registry);
+    let mut server = new_server();
+    let mut clients = HashMap::new();
+    let (server_tx, mut server_rx) =
+        tokio::sync::mpsc::unbounded_channel::<(u128, ServerMsg)>();
+    loop {
+        select! {
+            server_event = server.next() => {
+                if let Some((client_id, server_event)) = server_event {
+                    match server_event {
+                        NetworkServerEvent::Report(connection_report) => {
+                            match connection_report {
+                                bevy_simplenet::ServerReport::Connected(_, handshake) => {
+                                    if let Err(reason) = handshake.check() {
+                                        println!("Rejected client {}: {}", client_id, reason);
+                                        server.send(client_id, ServerMsg::Rejected(reason));
+                                        continue;
+                                    }
+                                    let welcome = handshake.welcome();
+                                    let encoding = welcome.encoding();
+                                    server.send(client_id, ServerMsg::Welcome(welcome));
+                                    // add client
+                                    let (clt_tx, clt_rx) =
+                                        tokio::sync::mpsc::unbounded_channel::<ClientMsg>();
+                                    let (srv_tx, mut srv_rx) =
+                                        tokio::sync::mpsc::unbounded_channel::<ServerMsg>();
+                                    let _ = clients.insert(client_id, clt_tx);
+                                    let server_tx_clone = server_tx.clone();
+                                    let conn = Connection {
+                                        client_id,
+                                        encoding,
+                                        tx: srv_tx,
+                                        rx: clt_rx,
+                                    };
+                                    let _ = lobby.pending.insert(client_id, conn);
+                                    tokio::task::spawn(async move {
+                                        while let Some(msg) = srv_rx.recv().await {
+                                            if server_tx_clone.send((client_id, msg)).is_err() {
+                                                break;
+                                            }
+                                        }
+                                    });
+                                },
+                                bevy_simplenet::ServerReport::Disconnected => {
+                                    // remove client
+                                    let _ = clients.remove(&client_id);
+                                    lobby.leave(client_id);
+                                },
+                            }
+                        },
+                        NetworkServerEvent::Msg(ClientMsg::JoinWith((name, preset))) => {
+                            if let Err(reason) = lobby.select(client_id, &name, preset) {
+                                println!("Rejected client {}: {}", client_id, reason);
+                                server.send(client_id, ServerMsg::Rejected(reason));
+                            }
+                        },
+                        NetworkServerEvent::Msg(msg) => {
+                            if let Some(clt_tx) = clients.get(&client_id) {
+                                if clt_tx.send(msg).is_err() {
+                                    let _ = clients.remove(&client_id);
+                                }
+                            }
+                        },
+                        NetworkServerEvent::Request(..) => continue,
+                    }
+                }
+            },
+            server_msg = server_rx.recv() => {
+                match server_msg {
+                    Some((client_id, msg)) => {
+                        server.send(client_id, msg);
+                    },
+                    None => break,
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use boardgame_common::{game::Game, player::GamePlayer};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+// a seat played from the same process, the game never sees a network
+pub struct LocalPlayer<G: Game> {
+    name: String,
+    actions: UnboundedReceiver<G::Action>,
+    messages: UnboundedSender<G::Message>,
+}
+
+// the other end of a LocalPlayer, held by a local ui or a bot
+pub struct LocalSeat<G: Game> {
+    pub actions: UnboundedSender<G::Action>,
+    pub messages: UnboundedReceiver<G::Message>,
+}
+
+pub fn local_seat<G: Game>(name: &str) -> (LocalPlayer<G>, LocalSeat<G>) {
+    let (action_tx, action_rx) = unbounded_channel();
+    let (message_tx, message_rx) = unbounded_channel();
+    let player = LocalPlayer {
+        name: name.to_owned(),
+        actions: action_rx,
+        messages: message_tx,
+    };
+    let seat = LocalSeat {
+        actions: action_tx,
+        messages: message_rx,
+    };
+    (player, seat)
+}
+
+impl<G: Game> GamePlayer<G> for LocalPlayer<G> {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn get_action(&mut self) -> Option<G::Action> {
+        self.actions.recv().await
+    }
+
+    async fn send_message(&mut self, message: G::Message) {
+        // the seat may be gone already, the game notices on its next action
+        let _ = self.messages.send(message);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use boardgame_common::{
+    catan::element::{Point, Tile, TileKind},
+    codec::MAX_POINTS,
+    element::{Coordinate, Line},
+};
+use rand::{prelude::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
+
+use super::data::{BoardLayout, CatanData};
+
+// items are handed out over the tiles or harbors in order, or shuffled first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Placement<T> {
+    Fixed(Vec<T>),
+    Shuffled(Vec<T>),
+}
+
+impl<T: Clone> Placement<T> {
+    fn items(&self) -> &Vec<T> {
+        match self {
+            Placement::Fixed(items) => items,
+            Placement::Shuffled(items) => items,
+        }
+    }
+
+    fn place(&self) -> Vec<T> {
+        match self {
+            Placement::Fixed(items) => items.clone(),
+            Placement::Shuffled(items) => {
+                let mut items = items.clone();
+                items.shuffle(&mut thread_rng());
+                items
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatanMap {
+    // land[x][y], the grid size follows from it
+    pub land: Vec<Vec<bool>>,
+    // over land tiles, x first then y
+    pub tiles: Placement<TileKind>,
+    // over land tiles that are not deserts, in the same order
+    pub numbers: Placement<usize>,
+    pub harbors: Vec<Line>,
+    // Dessert stands for a 3:1 harbor
+    pub harbor_kinds: Placement<TileKind>,
+    // defaults to the first desert
+    pub robber: Option<Coordinate>,
+}
+
+impl CatanMap {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Can not read map {}: {}", path, err))?;
+        let map: Self = serde_json::from_str(&content)
+            .map_err(|err| format!("Invalid map {}: {}", path, err))?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.land.is_empty() || self.land[0].is_empty() {
+            return Err("Map has no tiles".to_owned());
+        }
+        if self.land.iter().any(|row| row.len() != self.land[0].len()) {
+            return Err("Map rows differ in length".to_owned());
+        }
+        // the compact codec packs point coordinates in four bits each, a row
+        // of tiles has two points per tile and one more
+        let (rows, columns) = (self.land.len() + 1, 2 * self.land[0].len() + 1);
+        if rows > MAX_POINTS || columns > MAX_POINTS {
+            return Err(format!(
+                "Map of {}x{} tiles is too large, at most {}x{} fit",
+                self.land.len(),
+                self.land[0].len(),
+                MAX_POINTS - 1,
+                (MAX_POINTS - 1) / 2
+            ));
+        }
+
+        let land = self.land.iter().flatten().filter(|land| **land).count();
+        if self.tiles.items().len() != land {
+            return Err(format!(
+                "Map has {} land tiles but {} tile kinds",
+                land,
+                self.tiles.items().len()
+            ));
+        }
+        for kind in self.tiles.items() {
+            if !kind.is_resource() && *kind != TileKind::Dessert {
+                return Err(format!("Invalid tile kind {:?}", kind));
+            }
+        }
+        let deserts = self
+            .tiles
+            .items()
+            .iter()
+            .filter(|kind| **kind == TileKind::Dessert)
+            .count();
+        match self.robber {
+            Some(robber) if !self.land_tiles().contains(&robber) => {
+                return Err(format!("Robber {:?} is not on land", robber));
+            },
+            None if deserts == 0 => {
+                return Err("Map needs a desert or a robber position".to_owned());
+            },
+            _ => {},
+        }
+
+        if self.numbers.items().len() != land - deserts {
+            return Err(format!(
+                "Map has {} producing tiles but {} numbers",
+                land - deserts,
+                self.numbers.items().len()
+            ));
+        }
+        for number in self.numbers.items() {
+            if *number < 2 || *number > 12 || *number == 7 {
+                return Err(format!("Invalid number {}", number));
+            }
+        }
+
+        if self.harbors.len() != self.harbor_kinds.items().len() {
+            return Err(format!(
+                "Map has {} harbors but {} harbor kinds",
+                self.harbors.len(),
+                self.harbor_kinds.items().len()
+            ));
+        }
+        for kind in self.harbor_kinds.items() {
+            if !kind.is_resource() && *kind != TileKind::Dessert {
+                return Err(format!("Invalid harbor kind {:?}", kind));
+            }
+        }
+        let layout = BoardLayout::new(&self.land, &self.harbors);
+        for (harbor, touching) in self.harbors.iter().zip(layout.harbor_tiles.iter()) {
+            // a harbor sits on an edge between land and sea
+            if touching.len() != 1 {
+                return Err(format!("Harbor {:?} is not on the coast", harbor));
+            }
+        }
+        Ok(())
+    }
+
+    fn land_tiles(&self) -> Vec<Coordinate> {
+        let mut tiles = Vec::new();
+        for x in 0..self.land.len() {
+            for y in 0..self.land[x].len() {
+                if self.land[x][y] {
+                    tiles.push(Coordinate { x, y });
+                }
+            }
+        }
+        tiles
+    }
+
+    // expects a map that passed validate
+    pub fn build(&self) -> CatanData {
+        let width = self.land.len();
+        let height = self.land[0].len();
+        let mut tiles = vec![vec![Tile::default(); height]; width];
+        let mut numbered = Vec::new();
+        let mut deserts = Vec::new();
+        for (coord, kind) in self.land_tiles().into_iter().zip(self.tiles.place()) {
+            tiles[coord.x][coord.y].set_kind(kind);
+            if kind == TileKind::Dessert {
+                deserts.push(coord);
+            } else {
+                numbered.push(coord);
+            }
+        }
+
+        let mut dics_map = HashMap::new();
+        for (coord, number) in numbered.into_iter().zip(self.numbers.place()) {
+            tiles[coord.x][coord.y].set_number(number);
+            dics_map.entry(number).or_insert(Vec::new()).push(coord);
+        }
+        if !deserts.is_empty() {
+            dics_map.insert(7, deserts.clone());
+        }
+
+        let robber = match self.robber {
+            Some(robber) => robber,
+            None => deserts[0],
+        };
+
+        let harbors = self
+            .harbors
+            .iter()
+            .cloned()
+            .zip(self.harbor_kinds.place())
+            .collect::<Vec<_>>();
+        let fairness =
+            BoardLayout::new(&self.land, &self.harbors).fairness(&tiles, &harbors);
+
+        CatanData {
+            robber,
+            tiles,
+            dics_map,
+            harbors,
+            points: vec![vec![Point::default(); height * 2 + 1]; width + 1],
+            fairness,
+            starts: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{future::Future, marker::PhantomData, ops::RangeInclusive, pin::Pin};
+
+use boardgame_common::{
+    catan::rules::CatanRules,
+    codec::Encoding,
+    game::Game,
+    network::{ClientMsg, ServerMsg},
+    player::GamePlayer,
+};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use super::{
+    game::{CatanConfig, CatanGame},
+    snake::SnakeGame,
+};
+
+// a game that can be played over the network protocol
+pub trait NetworkGame: Game {
+    // rule presets a client may ask for when it joins
+    const PRESETS: &'static [&'static str] = &[];
+
+    fn encode(message: Self::Message, encoding: Encoding) -> ServerMsg;
+    // None for messages that belong to another game
+    fn decode(message: ClientMsg) -> Option<Self::Action>;
+
+    // the host config with one of PRESETS applied
+    fn preset(config: &Self::Config, _preset: &str) -> Self::Config {
+        config.clone()
+    }
+}
+
+impl NetworkGame for CatanGame {
+    const PRESETS: &'static [&'static str] = &CatanRules::PRESETS;
+
+    fn encode(message: Self::Message, encoding: Encoding) -> ServerMsg {
+        ServerMsg::encode(message, encoding)
+    }
+
+    fn decode(message: ClientMsg) -> Option<Self::Action> {
+        message.decode()
+    }
+
+    fn preset(config: &CatanConfig, preset: &str) -> CatanConfig {
+        CatanConfig {
+            rules: CatanRules::preset(preset).unwrap_or_else(|| config.rules.clone()),
+            ..config.clone()
+        }
+    }
+}
+
+impl NetworkGame for SnakeGame {
+    fn encode(message: Self::Message, _: Encoding) -> ServerMsg {
+        ServerMsg::Snake(message)
+    }
+
+    fn decode(message: ClientMsg) -> Option<Self::Action> {
+        match message {
+            ClientMsg::Snake(act) => Some(act),
+            _ => None,
+        }
+    }
+}
+
+pub struct Connection {
+    pub client_id: u128,
+    pub encoding: Encoding,
+    pub tx: UnboundedSender<ServerMsg>,
+    pub rx: UnboundedReceiver<ClientMsg>,
+}
+
+pub struct NetWorkPlayer<G> {
+    conn: Connection,
+    game: PhantomData<fn() -> G>,
+}
+
+impl<G> NetWorkPlayer<G> {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            game: PhantomData,
+        }
+    }
+}
+
+impl<G: NetworkGame> GamePlayer<G> for NetWorkPlayer<G> {
+    fn get_name(&self) -> String {
+        self.conn.client_id.to_string()
+    }
+
+    async fn get_action(&mut self) -> Option<G::Action> {
+        loop {
+            let msg = self.conn.rx.recv().await?;
+            if let Some(action) = G::decode(msg) {
+                return Some(action);
+            }
+        }
+    }
+
+    async fn send_message(&mut self, message: G::Message) {
+        // the client may be gone already, the game notices on its next action
+        let _ = self.conn.tx.send(G::encode(message, self.conn.encoding));
+    }
+}
+
+type Launch = Box<
+    dyn Fn(Vec<Connection>, Option<&str>) -> Pin<Box<dyn Future<Output = ()> + Send>>
+        + Send
+        + Sync,
+>;
+
+struct Entry {
+    name: &'static str,
+    players: RangeInclusive<usize>,
+    // how many joined clients start a game
+    seats: usize,
+    presets: &'static [&'static str],
+    launch: Launch,
+}
+
+// the game types this server hosts, each with the config picked by the host
+#[derive(Default)]
+pub struct GameRegistry {
+    entries: Vec<Entry>,
+}
+
+impl GameRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<G: NetworkGame>(&mut self, config: G::Config) {
+        assert!(
+            self.players(G::NAME).is_none(),
+            "{} registered twice",
+            G::NAME
+        );
+        self.entries.push(Entry {
+            name: G::NAME,
+            players: G::PLAYERS,
+            seats: *G::PLAYERS.start(),
+            presets: G::PRESETS,
+            launch: Box::new(move |conns, preset| {
+                let players = conns.into_iter().map(NetWorkPlayer::<G>::new).collect();
+                let config = match preset {
+                    Some(preset) => G::preset(&config, preset),
+                    None => config.clone(),
+                };
+                Box::pin(async move {
+                    let outcome = G::run(players, config).await;
+                    println!("{} finished: {:?}", G::NAME, outcome);
+                })
+            }),
+        });
+    }
+
+    // in registration order, the first one is the default
+    pub fn games(&self) -> Vec<&'static str> {
+        self.entries.iter().map(|entry| entry.name).collect()
+    }
+
+    pub fn players(&self, name: &str) -> Option<RangeInclusive<usize>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.players.clone())
+    }
+
+    pub fn seats(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.seats)
+    }
+
+    // games fill up to the fewest players allowed unless the host asks for more
+    pub fn set_seats(&mut self, name: &str, seats: usize) -> Result<(), String> {
+        let entry = match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => entry,
+            None => return Err(format!("Unknown game {}", name)),
+        };
+        if !entry.players.contains(&seats) {
+            return Err(format!(
+                "{} takes {:?} players, not {}",
+                name, entry.players, seats
+            ));
+        }
+        entry.seats = seats;
+        Ok(())
+    }
+
+    // a preset asked for by a client, the host config is used without one
+    pub fn check_preset(&self, name: &str, preset: &str) -> Result<(), String> {
+        let entry = match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => entry,
+            None => return Err(format!("Unknown game {}", name)),
+        };
+        if !entry.presets.contains(&preset) {
+            return Err(format!(
+                "Unknown rule preset {}, expected one of {:?}",
+                preset, entry.presets
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn launch(
+        &self, name: &str, preset: Option<&str>, conns: Vec<Connection>,
+    ) -> Result<(), String> {
+        let entry = match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => entry,
+            None => return Err(format!("Unknown game {}", name)),
+        };
+        if let Some(preset) = preset {
+            self.check_preset(name, preset)?;
+        }
+        if !entry.players.contains(&conns.len()) {
+            return Err(format!(
+                "{} takes {:?} players, got {}",
+                name,
+                entry.players,
+                conns.len()
+            ));
+        }
+        tokio::task::spawn((entry.launch)(conns, preset));
+        Ok(())
+    }
+}
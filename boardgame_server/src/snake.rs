@@ -0,0 +1,292 @@
+use std::{future::Future, ops::RangeInclusive, task::Poll, time::Duration};
+
+use boardgame_common::{
+    element::Coordinate,
+    game::Game,
+    player::GamePlayer,
+    snake::{
+        self,
+        element::{Direction, Snake, SnakeAct, SnakeBoard, SnakeMsg},
+    },
+};
+use rand::{seq::IteratorRandom, thread_rng, Rng};
+
+const START_LENGTH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct SnakeConfig {
+    pub width: usize,
+    pub height: usize,
+    pub tick: Duration,
+    // food kept on the board for every snake in the game
+    pub food_per_player: usize,
+}
+
+impl Default for SnakeConfig {
+    fn default() -> Self {
+        Self {
+            width: 20,
+            height: 20,
+            tick: Duration::from_millis(200),
+            food_per_player: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnakeOutcome {
+    pub winner: Option<usize>,
+    pub lengths: Vec<usize>,
+}
+
+// even seats start on the left heading right, odd seats on the right heading left
+pub(super) fn new_board(config: &SnakeConfig, players: usize) -> SnakeBoard {
+    let snakes = (0..players)
+        .map(|i| {
+            let y = config.height * (i + 1) / (players + 1);
+            let (direction, head) = if i % 2 == 0 {
+                (Direction::Right, START_LENGTH)
+            } else {
+                (Direction::Left, config.width - START_LENGTH - 1)
+            };
+            let body = (0..START_LENGTH)
+                .map(|j| match direction {
+                    Direction::Right => Coordinate::new(head - j, y),
+                    _ => Coordinate::new(head + j, y),
+                })
+                .collect();
+            Snake {
+                body,
+                direction,
+                alive: true,
+            }
+        })
+        .collect();
+    SnakeBoard {
+        width: config.width,
+        height: config.height,
+        tick: 0,
+        snakes,
+        food: Vec::new(),
+    }
+}
+
+// moves every snake one cell, turns that reverse a snake are ignored
+pub(super) fn advance(
+    board: &mut SnakeBoard, turns: &mut [Option<Direction>], food: usize,
+    rng: &mut impl Rng,
+) {
+    board.tick += 1;
+    for (snake, turn) in board.snakes.iter_mut().zip(turns.iter_mut()) {
+        if let Some(turn) = turn.take() {
+            if turn != snake.direction.opposite() {
+                snake.direction = turn;
+            }
+        }
+    }
+
+    let heads = board
+        .snakes
+        .iter()
+        .map(|snake| match snake.alive {
+            true => snake
+                .direction
+                .step(snake.head(), board.width, board.height),
+            false => None,
+        })
+        .collect::<Vec<_>>();
+    let eats = heads
+        .iter()
+        .map(|head| head.is_some_and(|head| board.food.contains(&head)))
+        .collect::<Vec<_>>();
+
+    let mut dead = Vec::new();
+    for (i, head) in heads.iter().enumerate() {
+        if !board.snakes[i].alive {
+            continue;
+        }
+        let Some(head) = head else {
+            // ran into the wall
+            dead.push(i);
+            continue;
+        };
+        let crashed = board.snakes.iter().enumerate().any(|(j, other)| {
+            if !other.alive {
+                return false;
+            }
+            // a tail moves on unless its snake grows this tick
+            let len = match eats[j] {
+                true => other.body.len(),
+                false => other.body.len() - 1,
+            };
+            other.body[..len].contains(head) || (i != j && heads[j] == Some(*head))
+        });
+        if crashed {
+            dead.push(i);
+        }
+    }
+
+    for (i, snake) in board.snakes.iter_mut().enumerate() {
+        if !snake.alive {
+            continue;
+        }
+        if dead.contains(&i) {
+            snake.alive = false;
+            continue;
+        }
+        snake.body.insert(0, heads[i].unwrap());
+        if eats[i] {
+            board.food.retain(|food| *food != snake.body[0]);
+        } else {
+            snake.body.pop();
+        }
+    }
+
+    spawn_food(board, food, rng);
+}
+
+// tops the shared food up to the given amount per living snake
+pub(super) fn spawn_food(board: &mut SnakeBoard, food: usize, rng: &mut impl Rng) {
+    let wanted = food * board.alive();
+    while board.food.len() < wanted {
+        let free = (0..board.width)
+            .flat_map(|x| (0..board.height).map(move |y| Coordinate::new(x, y)))
+            .filter(|cell| board.is_free(*cell))
+            .choose(rng);
+        match free {
+            Some(cell) => board.food.push(cell),
+            None => break,
+        }
+    }
+}
+
+pub(super) struct SnakeArena<P> {
+    pub(super) players: Vec<P>,
+    pub(super) board: SnakeBoard,
+    // the last direction asked for by each seat since the previous tick
+    turns: Vec<Option<Direction>>,
+    connected: Vec<bool>,
+    config: SnakeConfig,
+}
+
+impl<P> SnakeArena<P>
+where
+    P: GamePlayer<SnakeGame>,
+{
+    pub fn new(players: Vec<P>, config: SnakeConfig) -> Self {
+        let mut board = new_board(&config, players.len());
+        spawn_food(&mut board, config.food_per_player, &mut thread_rng());
+        Self {
+            turns: vec![None; players.len()],
+            connected: vec![true; players.len()],
+            players,
+            board,
+            config,
+        }
+    }
+
+    async fn broadcast(&mut self, msg: SnakeMsg) {
+        for (player, connected) in self.players.iter_mut().zip(self.connected.iter()) {
+            if *connected {
+                player.send_message(msg.clone()).await;
+            }
+        }
+    }
+
+    // None once the deadline passes
+    async fn next_action(
+        &mut self, deadline: tokio::time::Instant,
+    ) -> Option<(usize, Option<SnakeAct>)> {
+        let mut actions = self
+            .players
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| self.connected[*i])
+            .map(|(i, player)| (i, Box::pin(player.get_action())))
+            .collect::<Vec<_>>();
+        let mut timeout = std::pin::pin!(tokio::time::sleep_until(deadline));
+
+        std::future::poll_fn(|cx| {
+            for (i, action) in actions.iter_mut() {
+                if let Poll::Ready(action) = action.as_mut().poll(cx) {
+                    return Poll::Ready(Some((*i, action)));
+                }
+            }
+            timeout.as_mut().poll(cx).map(|_| None)
+        })
+        .await
+    }
+
+    fn winner(&self) -> Option<Option<usize>> {
+        match self.board.alive() {
+            0 => Some(None),
+            1 if self.players.len() > 1 => {
+                Some(self.board.snakes.iter().position(|snake| snake.alive))
+            },
+            _ => None,
+        }
+    }
+
+    pub async fn run(&mut self) -> SnakeOutcome {
+        for i in 0..self.players.len() {
+            let msg = SnakeMsg::GameStart(i, self.board.clone());
+            self.players[i].send_message(msg).await;
+        }
+
+        let mut deadline = tokio::time::Instant::now() + self.config.tick;
+        loop {
+            match self.next_action(deadline).await {
+                Some((i, Some(SnakeAct::Turn(direction)))) => {
+                    self.turns[i] = Some(direction);
+                },
+                Some((i, None)) => {
+                    println!("{} left the game", self.players[i].get_name());
+                    self.connected[i] = false;
+                    self.board.snakes[i].alive = false;
+                },
+                None => {
+                    advance(
+                        &mut self.board,
+                        &mut self.turns,
+                        self.config.food_per_player,
+                        &mut thread_rng(),
+                    );
+                    self.broadcast(SnakeMsg::Board(self.board.clone())).await;
+                    if let Some(winner) = self.winner() {
+                        self.broadcast(SnakeMsg::GameOver(winner)).await;
+                        return SnakeOutcome {
+                            winner,
+                            lengths: self
+                                .board
+                                .snakes
+                                .iter()
+                                .map(|snake| snake.body.len())
+                                .collect(),
+                        };
+                    }
+                    deadline += self.config.tick;
+                },
+            }
+        }
+    }
+}
+
+pub struct SnakeGame {}
+
+impl Game for SnakeGame {
+    const NAME: &'static str = snake::NAME;
+    const PLAYERS: RangeInclusive<usize> = 2..=4;
+
+    type Action = SnakeAct;
+    type Message = SnakeMsg;
+    type Config = SnakeConfig;
+    type Outcome = SnakeOutcome;
+
+    async fn run<P>(players: Vec<P>, config: SnakeConfig) -> SnakeOutcome
+    where
+        P: GamePlayer<Self> + Send + 'static,
+    {
+        let mut game = SnakeArena::new(players, config);
+        game.run().await
+    }
+}
@@ -1,544 +1,1820 @@
-#[cfg(test)]
-mod tests {
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
 
-    use std::collections::HashMap;
+use boardgame_common::{
+    catan::{
+        element::*,
+        rules::CatanRules,
+        state::{checksum, CatanError, CatanState, GameUpdate, Phase},
+    },
+    element::{Coordinate, Line},
+    game::Game,
+    player::GamePlayer,
+    snake::element::{Direction, Snake, SnakeAct, SnakeBoard, SnakeMsg},
+};
 
-    use tokio::{
-        select,
-        sync::mpsc::{UnboundedReceiver, UnboundedSender},
+use crate::{
+    bot::CatanBot,
+    data::{BoardConstraints, BoardLayout, CatanData, CatanDataSetup},
+    game::{catan_state, Catan, CatanBoard, CatanConfig, CatanGame, HELD_LIMIT},
+    local::local_seat,
+    map::{CatanMap, Placement},
+    snake::{advance, new_board, SnakeArena, SnakeConfig, SnakeGame},
+};
+#[cfg(feature = "network")]
+use crate::{
+    lobby::serve,
+    registry::{GameRegistry, NetworkGame},
+};
+
+fn pop_msg_and_assert(game: &mut CatanState, expected: GameMsg) {
+    let msg = game.broadcast.pop().unwrap();
+
+    println!("{:?}", msg);
+    assert_eq!(msg, expected);
+}
+
+// the last message as player i gets it, left in place for the next check
+fn assert_seen_by(game: &CatanState, i: usize, expected: GameMsg) {
+    let msg = game.broadcast.last().unwrap().seen_by(i);
+
+    println!("{:?}", msg);
+    assert_eq!(msg, expected);
+}
+
+#[tokio::test]
+async fn test_game() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 1),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 1),
+            end: Coordinate::new(1, 2),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    let build = BuildSettlement {
+        player: 1,
+        point: Coordinate::new(1, 1),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .expect_err("duplicate build");
+
+    let build = BuildRoad {
+        player: 1,
+        road: Line {
+            start: Coordinate::new(1, 1),
+            end: Coordinate::new(1, 2),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("duplicate build");
+
+    let build = BuildSettlement {
+        player: 1,
+        point: Coordinate::new(1, 2),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .expect_err("impossible build");
+
+    let build = BuildSettlement {
+        player: 1,
+        point: Coordinate::new(1, 3),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+
+    let build = BuildRoad {
+        player: 1,
+        road: Line {
+            start: Coordinate::new(1, 3),
+            end: Coordinate::new(2, 3),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 5),
     };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
 
-    use boardgame_common::{
-        catan::element::*,
-        element::{Coordinate, Line},
-        network::{new_server, ClientMsg, NetworkServerEvent, ServerMsg},
-        player::{GamePlayer, GamePlayerAction, GamePlayerMessage},
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 5),
+            end: Coordinate::new(1, 6),
+        },
     };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
 
-    use crate::{
-        data::CatanDataSetup,
-        game::{Catan, CatanGame, GameUpdate},
+    let build = BuildSettlement {
+        player: 1,
+        point: Coordinate::new(3, 5),
     };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
 
-    struct TestPlayer {
-        name: String,
+    let build = BuildRoad {
+        player: 1,
+        road: Line {
+            start: Coordinate::new(3, 5),
+            end: Coordinate::new(3, 6),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    game.is_initialized = true;
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 7),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .expect_err("no road");
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 6),
+            end: Coordinate::new(1, 7),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("no resource");
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 6),
+            end: Coordinate::new(1, 7),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    game.players[0].base.resources[TileKind::Wool as usize] += 1;
+    game.players[0].base.resources[TileKind::Grain as usize] += 1;
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 7),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+    assert_eq!(game.players[0].base.score, 3);
+    assert_eq!(game.players[0].base.get_longest_road(), 2);
+    assert_eq!(game.players[1].base.score, 2);
+
+    let build = BuyDevelopmentCard {
+        player: 0,
+        card: None,
+    };
+    game.update(GameUpdate::BuyDevelopmentCard(build.clone()))
+        .expect_err("no resource");
+
+    game.players[0].base.resources[TileKind::Stone as usize] += 1;
+    game.players[0].base.resources[TileKind::Wool as usize] += 1;
+    game.players[0].base.resources[TileKind::Grain as usize] += 1;
+    game.update(GameUpdate::BuyDevelopmentCard(build.clone()))
+        .unwrap();
+    assert_seen_by(&game, 1, GameMsg::PlayerBuyDevelopmentCard(build.clone()));
+    let card = BuyDevelopmentCard {
+        player: 0,
+        card: Some({
+            let mut card = DevCard::Knight;
+            for i in 0..game.players[0].base.cards.len() {
+                if game.players[0].base.cards[i] > 0 {
+                    card = DevCard::try_from(i as u8).unwrap();
+                }
+            }
+            card
+        }),
+    };
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuyDevelopmentCard(card.clone()));
+
+    let build = UseDevelopmentCard {
+        player: 0,
+        card: DevCard::YearOfPlenty,
+        usage: DevelopmentCard::YearOfPlenty(TileKind::Stone, TileKind::Stone),
+    };
+    if card.card.unwrap() == DevCard::YearOfPlenty {
+        game.update(GameUpdate::UseDevelopmentCard(build.clone()))
+            .unwrap();
+        pop_msg_and_assert(&mut game, GameMsg::PlayerUseDevelopmentCard(build.clone()));
+    } else {
+        game.update(GameUpdate::UseDevelopmentCard(build.clone()))
+            .expect_err("no card");
     }
 
-    impl TestPlayer {
-        fn new(name: String) -> Self {
-            Self { name }
-        }
+    game.players[0].base.resources[TileKind::Stone as usize] += 3;
+    game.players[0].base.resources[TileKind::Grain as usize] += 2;
+
+    let build = BuildCity {
+        player: 0,
+        point: Coordinate::new(1, 7),
+    };
+    game.update(GameUpdate::BuildCity(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+
+    game.players[0].base.resources[TileKind::Stone as usize] += 3;
+    game.players[0].base.resources[TileKind::Grain as usize] += 2;
+
+    let build = BuildCity {
+        player: 0,
+        point: Coordinate::new(1, 5),
+    };
+    game.update(GameUpdate::BuildCity(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+
+    game.players[0].base.resources[TileKind::Stone as usize] += 3;
+    game.players[0].base.resources[TileKind::Grain as usize] += 2;
+
+    let build = BuildCity {
+        player: 0,
+        point: Coordinate::new(1, 1),
+    };
+    game.update(GameUpdate::BuildCity(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 2);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 6),
+            end: Coordinate::new(1, 6),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 6),
+            end: Coordinate::new(0, 7),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 3);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 7),
+            end: Coordinate::new(0, 8),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 4);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 8),
+            end: Coordinate::new(0, 9),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("invalid location");
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 9),
+            end: Coordinate::new(0, 10),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("invalid location");
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(0, 8),
+            end: Coordinate::new(1, 8),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 5);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 7),
+            end: Coordinate::new(1, 8),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 7);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 8),
+            end: Coordinate::new(1, 9),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    assert_eq!(game.players[0].base.score, 6);
+    assert_eq!(game.players[0].base.get_longest_road(), 7);
+    assert_eq!(game.players[1].base.score, 2);
+
+    game.players[0].base.resources[TileKind::Brick as usize] += 1;
+    game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    let build = BuildRoad {
+        player: 0,
+        road: Line {
+            start: Coordinate::new(1, 9),
+            end: Coordinate::new(1, 10),
+        },
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("invalid position");
+
+    game.check_longest_road();
+    assert_eq!(game.players[0].base.score, 8);
+    assert_eq!(game.players[0].base.get_longest_road(), 7);
+    assert_eq!(game.players[1].base.score, 2);
+}
+
+#[test]
+fn test_apply() {
+    let rules = CatanRules {
+        first_player_roll: false,
+        ..Default::default()
+    };
+    let mut game = catan_state(CatanDataSetup::Basic, 2, rules);
+    assert_eq!(game.begin(), Ok(vec![GameMsg::PlayerInit(0)]));
+    assert_eq!(game.expects(), vec![0]);
+
+    let point = Coordinate::new(1, 1);
+    assert_eq!(
+        game.apply(1, GameAct::BuildSettlement(point)),
+        Err(CatanError::NotYourTurn(1))
+    );
+    assert_eq!(
+        game.apply(0, GameAct::EndTurn),
+        Err(CatanError::Unexpected(GameAct::EndTurn))
+    );
+    assert!(matches!(
+        game.apply(0, GameAct::BuildRoad(point, Coordinate::new(1, 2))),
+        Err(CatanError::Unexpected(_))
+    ));
+
+    let build = BuildSettlement { player: 0, point };
+    assert_eq!(
+        game.apply(0, GameAct::BuildSettlement(point)),
+        Ok(vec![GameMsg::PlayerBuildSettlement(build)])
+    );
+    assert!(matches!(
+        game.apply(
+            0,
+            GameAct::BuildRoad(Coordinate::new(1, 5), Coordinate::new(1, 6))
+        ),
+        Err(CatanError::Invalid(_))
+    ));
+    let road = Line::new(point, Coordinate::new(1, 2));
+    assert_eq!(
+        game.apply(0, GameAct::BuildRoad(point, Coordinate::new(1, 2))),
+        Ok(vec![
+            GameMsg::PlayerBuildRoad(BuildRoad { player: 0, road }),
+            GameMsg::PlayerInit(1),
+        ])
+    );
+    assert_eq!(game.phase, Phase::Setup(1));
+    assert_eq!(game.expects(), vec![1]);
+}
+
+#[test]
+fn test_road_building_is_atomic() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    game.is_initialized = true;
+    game.phase = Phase::Action;
+    let road = Line::new(Coordinate::new(1, 1), Coordinate::new(1, 2));
+    game.inner.add_road(0, road);
+    game.players[0].base.add_road(road);
+    game.players[0].base.cards[DevCard::RoadBuilding as usize] = 1;
+    let first = Line::new(Coordinate::new(1, 2), Coordinate::new(1, 3));
+    let second = Line::new(Coordinate::new(1, 3), Coordinate::new(1, 4));
+
+    // the second road is already there, so neither is built
+    let usage = DevelopmentCard::RoadBuilding([first, road]);
+    assert!(matches!(
+        game.apply(
+            0,
+            GameAct::UseDevelopmentCard((DevCard::RoadBuilding, usage))
+        ),
+        Err(CatanError::Invalid(_))
+    ));
+    assert_eq!(game.inner.roads().len(), 1);
+    assert_eq!(game.players[0].base.roads.len(), 1);
+    assert_eq!(
+        game.players[0].base.cards[DevCard::RoadBuilding as usize],
+        1
+    );
+
+    // the second road only touches the first, and the card is still unused
+    let usage = DevelopmentCard::RoadBuilding([first, second]);
+    let msgs = game
+        .apply(
+            0,
+            GameAct::UseDevelopmentCard((DevCard::RoadBuilding, usage)),
+        )
+        .unwrap();
+    assert_eq!(
+        msgs[..2],
+        [
+            GameMsg::PlayerBuildRoad(BuildRoad {
+                player: 0,
+                road: first
+            }),
+            GameMsg::PlayerBuildRoad(BuildRoad {
+                player: 0,
+                road: second
+            }),
+        ]
+    );
+    assert_eq!(game.inner.roads().len(), 3);
+    assert_eq!(
+        game.players[0].base.cards[DevCard::RoadBuilding as usize],
+        0
+    );
+}
+
+#[test]
+fn test_undo() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    game.is_initialized = true;
+    game.phase = Phase::Action;
+    let point = Coordinate::new(1, 1);
+    let road = Line::new(point, Coordinate::new(1, 2));
+    game.inner.add_road(0, road);
+    game.players[0].base.add_road(road);
+    for (kind, count) in [
+        (TileKind::Brick, 1),
+        (TileKind::Grain, 4),
+        (TileKind::Wool, 2),
+        (TileKind::Wood, 1),
+        (TileKind::Stone, 4),
+    ] {
+        game.players[0].base.resources[kind as usize] = count;
+        game.bank[kind as usize] -= count;
     }
+    let resources = game.players[0].base.resources;
+    let bank = game.bank;
 
-    impl GamePlayer for TestPlayer {
-        fn get_name(&self) -> String {
-            self.name.clone()
-        }
+    game.apply(0, GameAct::BuildSettlement(point)).unwrap();
+    game.apply(0, GameAct::BuildCity(point)).unwrap();
+    assert_eq!(game.players[0].base.score, 2);
+
+    let msgs = game.apply(0, GameAct::Undo).unwrap();
+    assert_eq!(msgs[0], GameMsg::PlayerUndo((0, Build::City(point))));
+    assert!(game.inner.point(point).is_owned());
+    assert!(!game.inner.point(point).is_city());
+    assert_eq!(game.players[0].base.city_left, 4);
+
+    let msgs = game.apply(0, GameAct::Undo).unwrap();
+    assert_eq!(msgs[0], GameMsg::PlayerUndo((0, Build::Settlement(point))));
+    assert!(!game.inner.point(point).is_owned());
+    assert_eq!(game.players[0].base.score, 0);
+    assert_eq!(game.players[0].base.settlement_left, 5);
+    assert_eq!(game.players[0].base.resources, resources);
+    assert_eq!(game.bank, bank);
+    assert!(matches!(
+        game.apply(0, GameAct::Undo),
+        Err(CatanError::Invalid(_))
+    ));
+
+    // a drawn card was seen, the build before it stays
+    game.apply(0, GameAct::BuildSettlement(point)).unwrap();
+    game.apply(0, GameAct::BuyDevelopmentCard).unwrap();
+    assert!(matches!(
+        game.apply(0, GameAct::Undo),
+        Err(CatanError::Invalid(_))
+    ));
+    assert!(game.inner.point(point).is_owned());
+}
+
+#[tokio::test]
+async fn test_setup_road_touches_settlement() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 1), Coordinate::new(1, 2)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("no settlement placed");
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 1),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 5), Coordinate::new(1, 6)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("road away from the settlement");
+
+    let build = BuildRoad {
+        player: 1,
+        road: Line::new(Coordinate::new(1, 1), Coordinate::new(1, 2)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("settlement owned by another player");
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 1), Coordinate::new(1, 2)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    let build = BuildSettlement {
+        player: 0,
+        point: Coordinate::new(1, 5),
+    };
+    game.update(GameUpdate::BuildSettlement(build.clone()))
+        .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 2), Coordinate::new(1, 3)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("road extends the first settlement instead of the second");
 
-        async fn get_action(&mut self) -> GamePlayerAction {
-            unreachable!()
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 5), Coordinate::new(1, 6)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+
+    let build = BuildRoad {
+        player: 0,
+        road: Line::new(Coordinate::new(1, 6), Coordinate::new(1, 7)),
+    };
+    game.update(GameUpdate::BuildRoad(build.clone()))
+        .expect_err("only one road per setup settlement");
+}
+
+#[tokio::test]
+async fn test_initial_resources() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+
+    // every point around the desert, including the ones on the coast
+    let desert = game.inner.robber();
+    for (i, point) in game.inner.tile_get_points(desert).into_iter().enumerate() {
+        let player = i % 2;
+        let mut expected = game.players[player].base.resources;
+        for tile in game.inner.ponint_get_tile(point).into_iter().flatten() {
+            let kind = game.inner.tile(tile).kind();
+            if kind.is_resource() {
+                expected[kind as usize] += 1;
+            }
         }
+        game.grant_initial_resources(player, point);
+
+        assert_eq!(game.players[player].base.resources, expected);
+        assert_eq!(
+            game.players[player].base.resources[TileKind::Empty as usize],
+            0
+        );
+        assert_eq!(
+            game.players[player].base.resources[TileKind::Dessert as usize],
+            0
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_first_player_roll() {
+    let mut game = catan_state(CatanDataSetup::Basic, 4, CatanRules::default());
+    let first = game.roll_first_player();
+    assert!(first < 4);
 
-        async fn send_message(&mut self, _: GamePlayerMessage) {
-            unreachable!()
+    let mut last_round = HashMap::new();
+    for msg in game.broadcast.drain(..) {
+        match msg {
+            GameMsg::PlayerFirstRoll((player, (dice1, dice2))) => {
+                assert!((1..=6).contains(&dice1) && (1..=6).contains(&dice2));
+                // a player rolling again starts the next tie break
+                if last_round.contains_key(&player) {
+                    last_round.clear();
+                }
+                last_round.insert(player, dice1 + dice2);
+            },
+            _ => unreachable!("unexpected message {:?}", msg),
         }
     }
+    let best = last_round.values().max().unwrap();
+    assert_eq!(last_round[&first], *best);
+}
 
-    fn pop_msg_and_assert<P>(game: &mut Catan<P>, expected: GameMsg) {
-        let msg = game.broadcast.pop().unwrap();
+struct ScriptedPlayer {
+    name: String,
+    actions: VecDeque<GameAct>,
+    messages: Vec<GameMsg>,
+}
 
-        println!("{:?}", msg);
-        assert_eq!(msg, expected);
+impl ScriptedPlayer {
+    fn new(name: String, actions: Vec<GameAct>) -> Self {
+        Self {
+            name,
+            actions: actions.into(),
+            messages: Vec::new(),
+        }
     }
+}
 
-    fn pop_player_msg_and_assert<P>(game: &mut Catan<P>, i: usize, expected: GameMsg) {
-        let msg = game.players[i].message.pop().unwrap();
+impl GamePlayer<CatanGame> for ScriptedPlayer {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
 
-        println!("{:?}", msg);
-        assert_eq!(msg, expected);
+    async fn get_action(&mut self) -> Option<GameAct> {
+        match self.actions.pop_front() {
+            Some(action) => Some(action),
+            None => std::future::pending().await,
+        }
     }
 
-    #[tokio::test]
-    async fn test_game() {
-        let player1 = TestPlayer::new("Player1".to_string());
-        let player2 = TestPlayer::new("Player2".to_string());
+    async fn send_message(&mut self, message: GameMsg) {
+        self.messages.push(message);
+    }
+}
 
-        let mut game = Catan::new(vec![player1, player2], CatanDataSetup::Basic);
+#[test]
+fn test_check_valid_drop() {
+    let mut player = PlayerCommon::default();
+    player.resources[TileKind::Wood as usize] = 4;
+    player.resources[TileKind::Brick as usize] = 4;
+    player.resources[TileKind::Stone as usize] = 1;
+    assert_eq!(player.discard_count(7), 4);
 
-        let build = BuildSettlement {
-            player: 0,
-            point: Coordinate::new(1, 1),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+    player
+        .check_valid_drop(&[(TileKind::Wood, 2), (TileKind::Brick, 2)], 4)
+        .unwrap();
+    player
+        .check_valid_drop(
+            &[
+                (TileKind::Wood, 2),
+                (TileKind::Empty, 0),
+                (TileKind::Wood, 2),
+            ],
+            4,
+        )
+        .unwrap();
+    player
+        .check_valid_drop(&[(TileKind::Wood, 3)], 4)
+        .expect_err("too few");
+    player
+        .check_valid_drop(&[(TileKind::Wood, 5)], 5)
+        .expect_err("more than owned");
+    player
+        .check_valid_drop(&[(TileKind::Wood, 3), (TileKind::Wood, 2)], 5)
+        .expect_err("more than owned across entries");
+    player
+        .check_valid_drop(&[(TileKind::Stone, 1), (TileKind::Dessert, 3)], 4)
+        .expect_err("not a resource");
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 1),
-                end: Coordinate::new(1, 2),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    player.resources[TileKind::Stone as usize] = 0;
+    assert_eq!(player.discard_count(7), 4);
+    player.resources[TileKind::Brick as usize] = 3;
+    assert_eq!(player.discard_count(7), 0);
+}
 
-        let build = BuildSettlement {
-            player: 1,
-            point: Coordinate::new(1, 1),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .expect_err("duplicate build");
-
-        let build = BuildRoad {
-            player: 1,
-            road: Line {
-                start: Coordinate::new(1, 1),
-                end: Coordinate::new(1, 2),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone()))
-            .expect_err("duplicate build");
+#[test]
+fn test_checksum() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    let point = Coordinate::new(1, 1);
+    game.update(GameUpdate::BuildSettlement(BuildSettlement {
+        player: 0,
+        point,
+    }))
+    .unwrap();
+    let road = Line::new(point, Coordinate::new(1, 2));
+    game.update(GameUpdate::BuildRoad(BuildRoad { player: 0, road }))
+        .unwrap();
+    game.players[1].base.resources[TileKind::Wool as usize] = 2;
+    let seen = game.checksum(1);
 
-        let build = BuildSettlement {
-            player: 1,
-            point: Coordinate::new(1, 2),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .expect_err("impossible build");
+    // the other hand is hidden, only how many cards it holds counts
+    game.players[0].base.resources[TileKind::Wood as usize] = 3;
+    assert_ne!(game.checksum(1), seen);
+    let seen = game.checksum(1);
+    game.players[0].base.resources[TileKind::Wood as usize] = 2;
+    game.players[0].base.resources[TileKind::Brick as usize] = 1;
+    assert_eq!(game.checksum(1), seen);
+    game.players[0].base.cards[DevCard::Knight as usize] = 1;
+    assert_ne!(game.checksum(1), seen);
+    let seen = game.checksum(1);
+    game.players[1].base.resources[TileKind::Wool as usize] = 1;
+    assert_ne!(game.checksum(1), seen);
+    let seen = game.checksum(1);
+    let robber = game.inner.robber();
+    game.inner
+        .set_robber(Coordinate::new(robber.x + 1, robber.y));
+    assert_ne!(game.checksum(1), seen);
 
-        let build = BuildSettlement {
-            player: 1,
-            point: Coordinate::new(1, 3),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+    // a client rebuilding from the view agrees with the server
+    game.drain();
+    let view = game.view(1);
+    assert_eq!(view.start.players[0].resources_count(), 3);
+    assert_eq!(view.start.players[0].card_count(), 1);
+    assert_eq!(view.start.players[1], game.players[1].base);
+    assert_eq!(view.buildings, vec![(point, 0, false)]);
+    let mut board = view.board();
+    let mut bank = view.start.bank;
+    let mut players = view.start.players.clone();
+    let local = |board: &CatanCommon, bank: &[usize], players: &[PlayerCommon]| {
+        let players = players.iter().collect::<Vec<_>>();
+        checksum(board, bank, &players, 1)
+    };
+    assert_eq!(local(&board, &bank, &players), game.checksum(1));
 
-        let build = BuildRoad {
-            player: 1,
-            road: Line {
-                start: Coordinate::new(1, 3),
-                end: Coordinate::new(2, 3),
+    // the other player builds from cards the viewer never saw
+    game.is_initialized = true;
+    let road = Line::new(Coordinate::new(1, 2), Coordinate::new(1, 3));
+    game.update(GameUpdate::BuildRoad(BuildRoad { player: 0, road }))
+        .unwrap();
+    for msg in game.drain() {
+        match msg {
+            GameMsg::PlayerBuildRoad(build) => {
+                board.add_road(build.player, build.road);
+                players[0].add_road(build.road);
+                for (kind, count) in Build::Road(build.road).cost() {
+                    players[0].remove_resource(Some(*kind), *count);
+                }
             },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+            GameMsg::BankResources(resources) => bank = resources,
+            _ => unreachable!("unexpected message {:?}", msg),
+        }
+    }
+    assert_eq!(players[0].resources_count(), 1);
+    assert_eq!(local(&board, &bank, &players), game.checksum(1));
+}
 
-        let build = BuildSettlement {
-            player: 0,
-            point: Coordinate::new(1, 5),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+#[tokio::test]
+async fn test_resync() {
+    let point = Coordinate::new(1, 1);
+    let players = vec![
+        ScriptedPlayer::new(
+            "Player0".to_string(),
+            vec![
+                GameAct::BuildSettlement(point),
+                GameAct::BuildRoad(point, Coordinate::new(1, 2)),
+            ],
+        ),
+        // both come before the turn of the player, the settlement waits for it
+        ScriptedPlayer::new(
+            "Player1".to_string(),
+            vec![
+                GameAct::Resync,
+                GameAct::BuildSettlement(Coordinate::new(3, 5)),
+            ],
+        ),
+    ];
+    let rules = CatanRules {
+        first_player_roll: false,
+        ..Default::default()
+    };
+    let mut game = Catan::new(players, CatanDataSetup::Basic, rules);
+    let msgs = game.state.begin().unwrap();
+    game.send(msgs).await;
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 5),
-                end: Coordinate::new(1, 6),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    let placed = tokio::time::timeout(Duration::from_secs(1), async {
+        while game.state.setup_settlement != Some((1, Coordinate::new(3, 5))) {
+            game.step().await;
+        }
+    })
+    .await;
+    assert!(placed.is_ok());
+    assert!(game.players[1]
+        .messages
+        .iter()
+        .any(|msg| matches!(msg, GameMsg::Resync(view)
+            if view.start.you == 1 && view.start.names == ["Player0", "Player1"])));
+    assert!(!game.players[0]
+        .messages
+        .iter()
+        .any(|msg| matches!(msg, GameMsg::Resync(_))));
+}
 
-        let build = BuildSettlement {
-            player: 1,
-            point: Coordinate::new(3, 5),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
+async fn collect_drops(game: &mut Catan<ScriptedPlayer>, drops: Vec<(usize, usize)>) {
+    game.state.collect_drops(drops);
+    let msgs = game.state.drain();
+    game.send(msgs).await;
+    while matches!(game.state.phase, Phase::Discard(_)) {
+        game.step().await;
+    }
+}
 
-        let build = BuildRoad {
-            player: 1,
-            road: Line {
-                start: Coordinate::new(3, 5),
-                end: Coordinate::new(3, 6),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+#[tokio::test]
+async fn test_collect_drops() {
+    let players = vec![
+        ScriptedPlayer::new("Player0".to_string(), vec![]),
+        ScriptedPlayer::new(
+            "Player1".to_string(),
+            vec![
+                GameAct::DropResource(vec![(TileKind::Wood, 3)]),
+                GameAct::DropResource(vec![(TileKind::Wood, 2), (TileKind::Wool, 2)]),
+            ],
+        ),
+        ScriptedPlayer::new(
+            "Player2".to_string(),
+            vec![GameAct::DropResource(vec![(TileKind::Stone, 5)])],
+        ),
+    ];
+    let mut game = Catan::new(players, CatanDataSetup::Basic, CatanRules::default());
+    game.state.players[1].base.resources[TileKind::Wood as usize] = 4;
+    game.state.players[1].base.resources[TileKind::Wool as usize] = 4;
+    game.state.players[2].base.resources[TileKind::Stone as usize] = 10;
 
-        game.is_initialized = true;
+    collect_drops(&mut game, vec![(1, 4), (2, 5)]).await;
+    assert_eq!(game.state.phase, Phase::Robber);
 
-        let build = BuildSettlement {
-            player: 0,
-            point: Coordinate::new(1, 7),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .expect_err("no road");
+    let resources = &game.state.players[1].base.resources;
+    assert_eq!(resources[TileKind::Wood as usize], 2);
+    assert_eq!(resources[TileKind::Wool as usize], 2);
+    let resources = &game.state.players[2].base.resources;
+    assert_eq!(resources[TileKind::Stone as usize], 5);
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 6),
-                end: Coordinate::new(1, 7),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone()))
-            .expect_err("no resource");
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    // the invalid drop is asked again, only from the player who made it
+    assert!(game.players[1]
+        .messages
+        .contains(&GameMsg::PlayerDropResources((1, 4))));
+    assert!(!game.players[2]
+        .messages
+        .contains(&GameMsg::PlayerDropResources((1, 4))));
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 6),
-                end: Coordinate::new(1, 7),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    let discarding = game.players[0]
+        .messages
+        .iter()
+        .filter_map(|msg| match msg {
+            GameMsg::PlayerDiscarding(players) => Some(players.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(discarding.first(), Some(&vec![1, 2]));
+    assert_eq!(discarding.last(), Some(&vec![]));
+}
 
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        game.players[0].base.resources[TileKind::Wool as usize] += 1;
-        game.players[0].base.resources[TileKind::Grain as usize] += 1;
+#[tokio::test]
+async fn test_collect_drops_timeout() {
+    let players = vec![
+        ScriptedPlayer::new("Player0".to_string(), vec![]),
+        ScriptedPlayer::new("Player1".to_string(), vec![]),
+    ];
+    let mut game = Catan::new(players, CatanDataSetup::Basic, CatanRules::default());
+    game.discard_timeout = Duration::from_millis(10);
+    game.state.players[1].base.resources[TileKind::Wood as usize] = 5;
+    game.state.players[1].base.resources[TileKind::Grain as usize] = 4;
 
-        let build = BuildSettlement {
-            player: 0,
-            point: Coordinate::new(1, 7),
-        };
-        game.update(GameUpdate::BuildSettlement(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildSettlement(build.clone()));
-        assert_eq!(game.players[0].base.score, 3);
-        assert_eq!(game.players[0].base.get_longest_road(), 2);
-        assert_eq!(game.players[1].base.score, 2);
+    collect_drops(&mut game, vec![(1, 4)]).await;
 
-        let build = BuyDevelopmentCard {
-            player: 0,
-            card: None,
-        };
-        game.update(GameUpdate::BuyDevelopmentCard(build.clone()))
-            .expect_err("no resource");
+    assert_eq!(game.state.players[1].base.resources_count(), 5);
+    assert_eq!(game.state.players[0].base.resources_count(), 0);
+}
 
-        game.players[0].base.resources[TileKind::Stone as usize] += 1;
-        game.players[0].base.resources[TileKind::Wool as usize] += 1;
-        game.players[0].base.resources[TileKind::Grain as usize] += 1;
-        game.update(GameUpdate::BuyDevelopmentCard(build.clone()))
-            .unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuyDevelopmentCard(build.clone()));
-        let card = BuyDevelopmentCard {
-            player: 0,
-            card: Some({
-                let mut card = DevCard::Knight;
-                for i in 0..game.players[0].base.cards.len() {
-                    if game.players[0].base.cards[i] > 0 {
-                        card = DevCard::try_from(i as u8).unwrap();
-                    }
-                }
-                card
-            }),
-        };
-        pop_player_msg_and_assert(
-            &mut game,
-            0,
-            GameMsg::PlayerBuyDevelopmentCard(card.clone()),
-        );
+#[tokio::test]
+async fn test_held_actions() {
+    let players = vec![
+        ScriptedPlayer::new("Player0".to_string(), vec![GameAct::EndTurn; 20]),
+        ScriptedPlayer::new(
+            "Player1".to_string(),
+            vec![GameAct::DropResource(vec![(TileKind::Wood, 4)])],
+        ),
+        ScriptedPlayer::new("Player2".to_string(), vec![]),
+    ];
+    let mut game = Catan::new(players, CatanDataSetup::Basic, CatanRules::default());
+    game.state.current_player = 2;
+    game.state.players[1].base.resources[TileKind::Wood as usize] = 8;
+    game.state.collect_drops(vec![(1, 4)]);
+    game.state.drain();
 
-        let build = UseDevelopmentCard {
-            player: 0,
-            card: DevCard::YearOfPlenty,
-            usage: DevelopmentCard::YearOfPlenty(TileKind::Stone, TileKind::Stone),
-        };
-        if card.card.unwrap() == DevCard::YearOfPlenty {
-            game.update(GameUpdate::UseDevelopmentCard(build.clone()))
-                .unwrap();
-            pop_msg_and_assert(
-                &mut game,
-                GameMsg::PlayerUseDevelopmentCard(build.clone()),
-            );
-        } else {
-            game.update(GameUpdate::UseDevelopmentCard(build.clone()))
-                .expect_err("no card");
+    // only so many actions are kept for a player who is not asked
+    for _ in 0..20 {
+        game.step().await;
+    }
+    assert_eq!(game.held[0].len(), HELD_LIMIT);
+
+    // they are gone once the phase moves on to somebody else
+    game.step().await;
+    assert_eq!(game.state.phase, Phase::Robber);
+    assert!(game.held[0].is_empty());
+}
+
+fn setup_robber_tile(game: &mut CatanState) -> Coordinate {
+    let mut tile = None;
+    for x in 0..5 {
+        for y in 0..5 {
+            let coord = Coordinate::new(x, y);
+            if tile.is_none()
+                && !game.inner.tile(coord).is_empty()
+                && game.inner.robber() != coord
+            {
+                tile = Some(coord);
+            }
         }
+    }
+    let tile = tile.unwrap();
+    let points = game.inner.tile_get_points(tile);
+    for (player, point) in [(0, points[0]), (1, points[2]), (2, points[4])] {
+        game.update(GameUpdate::BuildSettlement(BuildSettlement {
+            player,
+            point,
+        }))
+        .unwrap();
+    }
+    game.broadcast.clear();
+    tile
+}
 
-        game.players[0].base.resources[TileKind::Stone as usize] += 3;
-        game.players[0].base.resources[TileKind::Grain as usize] += 2;
+#[tokio::test]
+async fn test_select_robber() {
+    let mut game = catan_state(CatanDataSetup::Basic, 4, CatanRules::default());
+    let tile = setup_robber_tile(&mut game);
 
-        let build = BuildCity {
-            player: 0,
-            point: Coordinate::new(1, 7),
-        };
-        game.update(GameUpdate::BuildCity(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+    let robber = game.inner.robber();
+    game.update(GameUpdate::SelectRobber(SelectRobber {
+        player: 0,
+        coord: robber,
+    }))
+    .expect_err("robber must leave its tile");
+    game.update(GameUpdate::SelectRobber(SelectRobber {
+        player: 0,
+        coord: Coordinate::new(0, 0),
+    }))
+    .expect_err("robber must stay on the board");
+    assert!(game.broadcast.is_empty());
 
-        game.players[0].base.resources[TileKind::Stone as usize] += 3;
-        game.players[0].base.resources[TileKind::Grain as usize] += 2;
+    let select = SelectRobber {
+        player: 0,
+        coord: tile,
+    };
+    game.update(GameUpdate::SelectRobber(select.clone()))
+        .unwrap();
+    assert_eq!(game.inner.robber(), tile);
+    // the thief is never a victim, players without buildings there neither
+    pop_msg_and_assert(&mut game, GameMsg::PlayerStartSteal(vec![1, 2]));
+    pop_msg_and_assert(&mut game, GameMsg::PlayerSelectRobber(select));
+    assert_eq!(game.steal_candidates, vec![1, 2]);
 
-        let build = BuildCity {
-            player: 0,
-            point: Coordinate::new(1, 5),
-        };
-        game.update(GameUpdate::BuildCity(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+    let select = SelectRobber {
+        player: 1,
+        coord: robber,
+    };
+    game.update(GameUpdate::SelectRobber(select.clone()))
+        .unwrap();
+    let mut expected = Vec::new();
+    for point in game.inner.tile_get_points(robber) {
+        if let Some(owner) = game.inner.point(point).owner() {
+            if owner != 1 && !expected.contains(&owner) {
+                expected.push(owner);
+            }
+        }
+    }
+    expected.sort();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerStartSteal(expected.clone()));
+    assert_eq!(game.steal_candidates, expected);
 
-        game.players[0].base.resources[TileKind::Stone as usize] += 3;
-        game.players[0].base.resources[TileKind::Grain as usize] += 2;
+    // a rejected steal asks again with the same candidates
+    game.phase = Phase::Steal;
+    assert_eq!(
+        game.prompt(game.current_player),
+        Some(GameMsg::PlayerStartSteal(expected))
+    );
+}
 
-        let build = BuildCity {
-            player: 0,
-            point: Coordinate::new(1, 1),
-        };
-        game.update(GameUpdate::BuildCity(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildCity(build.clone()));
+#[tokio::test]
+async fn test_steal_resource() {
+    let mut game = catan_state(CatanDataSetup::Basic, 4, CatanRules::default());
+    let robber = game.inner.robber();
+    let tile = setup_robber_tile(&mut game);
+    game.players[1].base.resources[TileKind::Wool as usize] = 2;
 
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 2);
-        assert_eq!(game.players[1].base.score, 2);
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 1,
+        kind: None,
+    }))
+    .expect_err("robber not moved yet");
 
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
+    game.update(GameUpdate::SelectRobber(SelectRobber {
+        player: 0,
+        coord: tile,
+    }))
+    .unwrap();
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 3,
+        kind: None,
+    }))
+    .expect_err("no building on the robber tile");
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(0, 6),
-                end: Coordinate::new(1, 6),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 1,
+        kind: None,
+    }))
+    .unwrap();
+    assert_eq!(game.players[0].base.resources[TileKind::Wool as usize], 1);
+    assert_eq!(game.players[1].base.resources[TileKind::Wool as usize], 1);
 
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(0, 6),
-                end: Coordinate::new(0, 7),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+    // only the thief and the victim learn what was stolen
+    let revealed = StealResource {
+        player: 0,
+        target: 1,
+        kind: Some(TileKind::Wool),
+    };
+    let hidden = StealResource {
+        kind: None,
+        ..revealed.clone()
+    };
+    assert_seen_by(&game, 0, GameMsg::PlayerStealResource(revealed.clone()));
+    assert_seen_by(&game, 1, GameMsg::PlayerStealResource(revealed));
+    assert_seen_by(&game, 2, GameMsg::PlayerStealResource(hidden.clone()));
+    assert_seen_by(&game, 3, GameMsg::PlayerStealResource(hidden));
 
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 3);
-        assert_eq!(game.players[1].base.score, 2);
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 2,
+        kind: None,
+    }))
+    .expect_err("only one steal per robber move");
 
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
+    // a victim without cards can be picked, nothing moves
+    for coord in [robber, tile] {
+        game.update(GameUpdate::SelectRobber(SelectRobber { player: 0, coord }))
+            .unwrap();
+    }
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 2,
+        kind: None,
+    }))
+    .unwrap();
+    assert_eq!(game.players[0].base.resources_count(), 1);
+    assert_seen_by(
+        &game,
+        2,
+        GameMsg::PlayerStealResource(StealResource {
             player: 0,
-            road: Line {
-                start: Coordinate::new(0, 7),
-                end: Coordinate::new(0, 8),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
+            target: 2,
+            kind: None,
+        }),
+    );
+}
 
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 4);
-        assert_eq!(game.players[1].base.score, 2);
+fn setup_dice_tile(game: &mut CatanState, owners: [usize; 2]) -> TileKind {
+    let robber = game.inner.robber();
+    let mut tile = None;
+    for x in 0..5 {
+        for y in 0..5 {
+            let coord = Coordinate::new(x, y);
+            if tile.is_none() && game.inner.tile(coord).is_resource() && coord != robber {
+                tile = Some(coord);
+            }
+        }
+    }
+    let tile = tile.unwrap();
+    // only this tile produces on an 8
+    game.inner = CatanCommon::new(
+        game.inner.tiles().clone(),
+        vec![vec![Point::default(); 11]; 6],
+        HashMap::new(),
+        game.inner.harbors().clone(),
+        HashMap::from([(8, vec![tile])]),
+        robber,
+    );
+    let points = game.inner.tile_get_points(tile);
+    for (player, point) in [(owners[0], points[0]), (owners[1], points[2])] {
+        game.update(GameUpdate::BuildSettlement(BuildSettlement {
+            player,
+            point,
+        }))
+        .unwrap();
+    }
+    game.inner.tile(tile).kind()
+}
 
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(0, 8),
-                end: Coordinate::new(0, 9),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone()))
-            .expect_err("invalid location");
+#[tokio::test]
+async fn test_bank_shortage() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    let kind = setup_dice_tile(&mut game, [0, 1]);
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(0, 9),
-                end: Coordinate::new(0, 10),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone()))
-            .expect_err("invalid location");
+    game.bank[kind as usize] = 1;
+    game.update(GameUpdate::HitDice(8)).unwrap();
+    assert_eq!(game.players[0].base.resources[kind as usize], 0);
+    assert_eq!(game.players[1].base.resources[kind as usize], 0);
+    assert_eq!(game.bank[kind as usize], 1);
 
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(0, 8),
-                end: Coordinate::new(1, 8),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 5);
-        assert_eq!(game.players[1].base.score, 2);
-
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 7),
-                end: Coordinate::new(1, 8),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 7);
-        assert_eq!(game.players[1].base.score, 2);
-
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
-            player: 0,
-            road: Line {
-                start: Coordinate::new(1, 8),
-                end: Coordinate::new(1, 9),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone())).unwrap();
-        pop_msg_and_assert(&mut game, GameMsg::PlayerBuildRoad(build.clone()));
-        assert_eq!(game.players[0].base.score, 6);
-        assert_eq!(game.players[0].base.get_longest_road(), 7);
-        assert_eq!(game.players[1].base.score, 2);
-
-        game.players[0].base.resources[TileKind::Brick as usize] += 1;
-        game.players[0].base.resources[TileKind::Wood as usize] += 1;
-        let build = BuildRoad {
+    game.bank[kind as usize] = 2;
+    game.update(GameUpdate::HitDice(8)).unwrap();
+    assert_eq!(game.players[0].base.resources[kind as usize], 1);
+    assert_eq!(game.players[1].base.resources[kind as usize], 1);
+    assert_eq!(game.bank[kind as usize], 0);
+
+    // a single player owed the resource takes what is left
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    let kind = setup_dice_tile(&mut game, [0, 0]);
+
+    game.bank[kind as usize] = 1;
+    game.update(GameUpdate::HitDice(8)).unwrap();
+    assert_eq!(game.players[0].base.resources[kind as usize], 1);
+    assert_eq!(game.bank[kind as usize], 0);
+    pop_msg_and_assert(
+        &mut game,
+        GameMsg::PlayerOfferResources(OfferResources {
             player: 0,
-            road: Line {
-                start: Coordinate::new(1, 9),
-                end: Coordinate::new(1, 10),
-            },
-        };
-        game.update(GameUpdate::BuildRoad(build.clone()))
-            .expect_err("invalid position");
+            count: 1,
+            kind,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_bank_payments() {
+    let mut game = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    game.is_initialized = true;
+    assert_eq!(game.bank[TileKind::Wood as usize], 19);
+    assert_eq!(game.bank[TileKind::Dessert as usize], 0);
+
+    game.players[0].base.resources[TileKind::Grain as usize] = 2;
+    game.players[0].base.resources[TileKind::Wool as usize] = 2;
+    game.players[0].base.resources[TileKind::Stone as usize] = 2;
+    game.bank[TileKind::Grain as usize] -= 2;
+    game.bank[TileKind::Wool as usize] -= 2;
+    game.bank[TileKind::Stone as usize] -= 2;
+
+    let buy = BuyDevelopmentCard {
+        player: 0,
+        card: None,
+    };
+    game.update(GameUpdate::BuyDevelopmentCard(buy.clone()))
+        .unwrap();
+    assert_eq!(game.bank[TileKind::Grain as usize], 18);
+    assert_eq!(game.bank[TileKind::Wool as usize], 18);
+    assert_eq!(game.bank[TileKind::Stone as usize], 18);
 
-        game.check_longest_road();
-        assert_eq!(game.players[0].base.score, 8);
-        assert_eq!(game.players[0].base.get_longest_road(), 7);
-        assert_eq!(game.players[1].base.score, 2);
+    // an empty deck does not cost anything
+    game.dev_cards.clear();
+    game.update(GameUpdate::BuyDevelopmentCard(buy))
+        .expect_err("no development card left");
+    assert_eq!(game.players[0].base.resources_count(), 3);
+
+    game.players[0].base.resources[TileKind::Wood as usize] = 4;
+    game.bank[TileKind::Wood as usize] -= 4;
+    game.bank[TileKind::Brick as usize] = 0;
+    let request = TradeRequest::new(
+        vec![(TileKind::Wood, 4)],
+        vec![(TileKind::Brick, 1)],
+        TradeTarget::Bank,
+    );
+    game.update(GameUpdate::Trade(Some(Trade {
+        from: 0,
+        to: None,
+        request: request.clone(),
+    })))
+    .expect_err("bank has no brick");
+
+    game.bank[TileKind::Brick as usize] = 1;
+    game.update(GameUpdate::Trade(Some(Trade {
+        from: 0,
+        to: None,
+        request,
+    })))
+    .unwrap();
+    assert_eq!(game.players[0].base.resources[TileKind::Brick as usize], 1);
+    assert_eq!(game.bank[TileKind::Brick as usize], 0);
+    assert_eq!(game.bank[TileKind::Wood as usize], 19);
+}
+
+#[tokio::test]
+async fn test_friendly_robber() {
+    let mut game = catan_state(
+        CatanDataSetup::Basic,
+        4,
+        CatanRules::preset("friendly").unwrap(),
+    );
+    let tile = setup_robber_tile(&mut game);
+    game.players[2].base.score = 3;
+
+    game.update(GameUpdate::SelectRobber(SelectRobber {
+        player: 0,
+        coord: tile,
+    }))
+    .unwrap();
+    pop_msg_and_assert(&mut game, GameMsg::PlayerStartSteal(vec![2]));
+    game.update(GameUpdate::StealResource(StealResource {
+        player: 0,
+        target: 1,
+        kind: None,
+    }))
+    .expect_err("player under the friendly robber score");
+}
+
+#[tokio::test]
+async fn test_balanced_dice() {
+    let mut game = catan_state(
+        CatanDataSetup::Basic,
+        2,
+        CatanRules::preset("balanced").unwrap(),
+    );
+
+    // every outcome shows up at most once before the deck is reshuffled
+    let mut rolls = Vec::new();
+    for _ in 0..31 {
+        let dice = game.next_dice();
+        assert!(!rolls.contains(&dice));
+        rolls.push(dice);
     }
+}
 
-    struct NetWorkPlayer {
-        client_id: u128,
-        tx: UnboundedSender<ServerMsg>,
-        rx: UnboundedReceiver<ClientMsg>,
+#[test]
+fn test_rule_presets() {
+    for name in CatanRules::PRESETS {
+        assert!(CatanRules::preset(name).is_some());
     }
+    assert_eq!(CatanRules::preset("standard"), Some(CatanRules::default()));
+    assert_eq!(CatanRules::preset("unknown"), None);
 
-    impl GamePlayer for NetWorkPlayer {
-        fn get_name(&self) -> String {
-            self.client_id.to_string()
-        }
+    let rules = CatanRules {
+        win_score: 5,
+        dev_cards: vec![(DevCard::Monopoly, 3)],
+        bank_resource: 4,
+        ..Default::default()
+    };
+    let game = catan_state(CatanDataSetup::Basic, 2, rules);
+    assert_eq!(game.dev_cards, vec![DevCard::Monopoly; 3]);
+    assert_eq!(game.bank[TileKind::Stone as usize], 4);
+    assert_eq!(game.bank[TileKind::Dessert as usize], 0);
+}
+
+#[test]
+fn test_balanced_board() {
+    let layout = catan_state(CatanDataSetup::Basic, 1, CatanRules::default());
+    for seed in 0..20 {
+        let data = CatanData::new(CatanDataSetup::Balanced(BoardConstraints::new(seed)));
+        let again = CatanData::new(CatanDataSetup::Balanced(BoardConstraints::new(seed)));
+        assert_eq!(data.tiles, again.tiles);
+        assert_eq!(data.harbors, again.harbors);
+        assert_eq!(data.robber, again.robber);
+        assert!(data.fairness > 0.0 && data.fairness <= 100.0);
 
-        async fn get_action(&mut self) -> GamePlayerAction {
-            match self.rx.recv().await {
-                Some(ClientMsg::Catan(action)) => GamePlayerAction::Catan(action),
-                _ => GamePlayerAction::PlaceHolder,
+        let mut numbered = Vec::new();
+        for x in 0..5 {
+            for y in 0..5 {
+                if let Some(number) = data.tiles[x][y].number() {
+                    numbered.push((Coordinate::new(x, y), number));
+                }
             }
         }
+        assert_eq!(numbered.len(), 18);
+        assert_eq!(data.tiles[data.robber.x][data.robber.y].number(), None);
 
-        async fn send_message(&mut self, message: GamePlayerMessage) {
-            match message {
-                GamePlayerMessage::Catan(message) => {
-                    self.tx.send(ServerMsg::Catan(message)).unwrap();
-                },
-                GamePlayerMessage::PlaceHolder => {},
+        for (a, number_a) in numbered.iter() {
+            let points = layout.inner.tile_get_points(*a);
+            for (b, number_b) in numbered.iter().filter(|(b, _)| b != a) {
+                let shared = layout
+                    .inner
+                    .tile_get_points(*b)
+                    .iter()
+                    .filter(|point| points.contains(point))
+                    .count();
+                if shared == 2 {
+                    assert_ne!(number_a, number_b);
+                    assert!(!([6, 8].contains(number_a) && [6, 8].contains(number_b)));
+                }
             }
         }
     }
 
-    #[tokio::test]
-    async fn test_server() {
-        let (close_tx, close_rx) = tokio::sync::oneshot::channel::<()>();
-        let (player_tx, mut player_rx) =
-            tokio::sync::mpsc::unbounded_channel::<NetWorkPlayer>();
-
-        tokio::task::spawn(async move {
-            loop {
-                let player1 = player_rx.recv().await.unwrap();
-                let player2 = player_rx.recv().await.unwrap();
-                // tokio::task::spawn(async move {
-                //     CatanGame::run(vec![player1], CatanDataSetup::Basic).await;
-                // });
-
-                tokio::task::spawn(async move {
-                    CatanGame::run(vec![player1, player2], CatanDataSetup::Basic).await;
-                });
+    let a = CatanData::new(CatanDataSetup::Balanced(BoardConstraints::new(1)));
+    let b = CatanData::new(CatanDataSetup::Balanced(BoardConstraints::new(2)));
+    assert_ne!(a.tiles, b.tiles);
+
+    // no attempt can place its numbers, the fairest dealt board is used
+    let constraints = BoardConstraints {
+        max_attempts: 5,
+        max_placements: 0,
+        ..BoardConstraints::new(3)
+    };
+    let data = CatanData::new(CatanDataSetup::Balanced(constraints));
+    let numbers = data.tiles.iter().flatten().filter_map(|tile| tile.number());
+    assert_eq!(numbers.count(), 18);
+    assert_eq!(
+        data.dics_map
+            .values()
+            .map(|tiles| tiles.len())
+            .sum::<usize>(),
+        19
+    );
+}
+
+#[test]
+fn test_map_file() {
+    let map: CatanMap =
+        serde_json::from_str(include_str!("../maps/standard.json")).unwrap();
+    map.validate().unwrap();
+    let data = map.build();
+    assert_eq!(data.tiles.len(), 5);
+    assert_eq!(data.harbors.len(), 9);
+    assert_eq!(
+        data.tiles[data.robber.x][data.robber.y].kind(),
+        TileKind::Dessert
+    );
+    let numbered = data
+        .dics_map
+        .iter()
+        .filter(|(number, _)| **number != 7)
+        .map(|(_, tiles)| tiles.len())
+        .sum::<usize>();
+    assert_eq!(numbered, 18);
+
+    let path = std::env::temp_dir().join("catan_test_map.json");
+    let path = path.to_str().unwrap();
+    std::fs::write(path, serde_json::to_string(&map).unwrap()).unwrap();
+    assert_eq!(CatanMap::load(path), Ok(map.clone()));
+    let board = CatanBoard::load(path).unwrap();
+    assert!(matches!(board, CatanBoard::Map(ref loaded) if *loaded == map));
+    let data = CatanData::new(CatanDataSetup::FromMap(map.clone()));
+    assert_eq!(data.harbors.len(), 9);
+
+    std::fs::write(path, "{").unwrap();
+    CatanMap::load(path).expect_err("not a map");
+    CatanMap::load("/nonexistent/map.json").expect_err("missing file");
+    CatanBoard::load("/nonexistent/map.json").expect_err("missing file");
+    assert!(matches!(
+        CatanBoard::load("beginner"),
+        Ok(CatanBoard::Beginner)
+    ));
+
+    let mut invalid = map.clone();
+    invalid.land[0][0] = true;
+    invalid.validate().expect_err("more land than tiles");
+
+    let mut invalid = map.clone();
+    invalid.land.iter_mut().for_each(|row| row.resize(8, false));
+    let err = invalid.validate().expect_err("too wide for packed points");
+    assert_eq!(err, "Map of 5x8 tiles is too large, at most 15x7 fit");
+    let mut invalid = map.clone();
+    invalid.land.resize(16, vec![false; 5]);
+    invalid.validate().expect_err("too high for packed points");
+    let mut widest = map.clone();
+    widest.land.iter_mut().for_each(|row| row.resize(7, false));
+    widest.land.resize(15, vec![false; 7]);
+    widest.validate().unwrap();
+
+    let mut invalid = map.clone();
+    invalid.numbers = Placement::Fixed(vec![7; 18]);
+    invalid.validate().expect_err("seven is not a number token");
+
+    let mut invalid = map.clone();
+    invalid.numbers = Placement::Shuffled(vec![6; 17]);
+    invalid.validate().expect_err("too few numbers");
+
+    // every token a six, the other numbers hit nothing
+    let mut sixes = map.clone();
+    sixes.numbers = Placement::Shuffled(vec![6; 18]);
+    sixes.validate().unwrap();
+    let mut game = catan_state(CatanDataSetup::FromMap(sixes), 2, CatanRules::default());
+    game.update(GameUpdate::HitDice(2)).unwrap();
+    game.update(GameUpdate::HitDice(6)).unwrap();
+
+    let mut invalid = map.clone();
+    invalid.robber = Some(Coordinate::new(0, 0));
+    invalid.validate().expect_err("robber in the sea");
+
+    let mut invalid = map.clone();
+    invalid.harbors[0] = Line::new(Coordinate::new(2, 4), Coordinate::new(2, 5));
+    invalid.validate().expect_err("harbor inland");
+
+    let mut invalid = map.clone();
+    invalid.harbors[0] = Line::new(Coordinate::new(0, 2), Coordinate::new(0, 4));
+    invalid.validate().expect_err("harbor points not adjacent");
+
+    let layout = BoardLayout::new(&map.land, &map.harbors);
+    assert_eq!(layout.neighbors[&Coordinate::new(2, 2)].len(), 6);
+    assert_eq!(layout.neighbors[&Coordinate::new(0, 1)].len(), 3);
+    assert!(layout.harbor_tiles.iter().all(|tiles| tiles.len() == 1));
+
+    let mut invalid = map.clone();
+    invalid.harbor_kinds = Placement::Fixed(vec![TileKind::Wool]);
+    invalid
+        .validate()
+        .expect_err("harbor kinds do not match harbors");
+
+    let mut fixed = map.clone();
+    fixed.tiles = Placement::Fixed(match &map.tiles {
+        Placement::Shuffled(tiles) => tiles.clone(),
+        Placement::Fixed(tiles) => tiles.clone(),
+    });
+    fixed.robber = Some(Coordinate::new(2, 2));
+    fixed.validate().unwrap();
+    let data = fixed.build();
+    // the first land tile in the pool is the desert
+    assert_eq!(data.tiles[0][1].kind(), TileKind::Dessert);
+    assert_eq!(data.robber, Coordinate::new(2, 2));
+}
+
+#[tokio::test]
+async fn test_beginner_setup() {
+    let data = CatanData::new(CatanDataSetup::Beginner(false));
+    assert!(data.starts.is_empty());
+    assert_eq!(data.robber, Coordinate::new(2, 2));
+    assert_eq!(data.tiles[0][1].kind(), TileKind::Stone);
+    assert_eq!(data.tiles[0][1].number(), Some(10));
+    assert_eq!(data.tiles[4][3].kind(), TileKind::Wool);
+    assert_eq!(data.tiles[4][3].number(), Some(11));
+    assert_eq!(data.harbors.len(), 9);
+
+    let mut game = catan_state(CatanDataSetup::Beginner(true), 4, CatanRules::default());
+    assert_eq!(game.starts.len(), 4);
+    let order = game.setup_order();
+    game.preplace_starts(&order).unwrap();
+
+    let mut handed_out = 0;
+    for (seat, player) in order.iter().enumerate() {
+        let base = &game.players[*player].base;
+        assert_eq!(base.settlement_left, 3);
+        assert_eq!(base.roads.len(), 2);
+        assert_eq!(base.score, 2);
+
+        let (point, _) = game.starts[seat][1];
+        let mut expected = [0; TileKind::Max as usize];
+        for tile in game.inner.ponint_get_tile(point).into_iter().flatten() {
+            let kind = game.inner.tile(tile).kind();
+            if kind.is_resource() {
+                expected[kind as usize] += 1;
             }
-        });
-
-        tokio::task::spawn(async move {
-            let mut server = new_server();
-            let mut clients = HashMap::new();
-            let (server_tx, mut server_rx) =
-                tokio::sync::mpsc::unbounded_channel::<(u128, ServerMsg)>();
-            loop {
-                select! {
-                    server_event = server.next() => {
-                        if let Some((client_id, server_event)) = server_event {
-                            match server_event {
-                                NetworkServerEvent::Report(connection_report) => {
-                                    match connection_report {
-                                        bevy_simplenet::ServerReport::Connected(_, _) => {
-                                            // add client
-                                            let (clt_tx, clt_rx) =
-                                                tokio::sync::mpsc::unbounded_channel::<ClientMsg>();
-                                            let (srv_tx, mut srv_rx) =
-                                                tokio::sync::mpsc::unbounded_channel::<ServerMsg>();
-                                            let _ = clients.insert(client_id, clt_tx);
-                                            let server_tx_clone = server_tx.clone();
-                                            player_tx
-                                                .send(NetWorkPlayer {
-                                                    client_id,
-                                                    tx: srv_tx,
-                                                    rx: clt_rx,
-                                                })
-                                                .unwrap();
-                                            tokio::task::spawn(async move {
-                                                loop {
-                                                    match srv_rx.recv().await {
-                                                        Some(msg) => {
-                                                            server_tx_clone
-                                                                .send((client_id, msg))
-                                                                .unwrap();
-                                                        },
-                                                        _ => {},
-                                                    };
-                                                }
-                                            });
-                                        },
-                                        bevy_simplenet::ServerReport::Disconnected => {
-                                            // remove client
-                                            let _ = clients.remove(&client_id);
-                                        },
-                                    }
-                                },
-                                NetworkServerEvent::Msg(msg) => match clients.get(&client_id) {
-                                    Some(clt_tx) => {
-                                        match clt_tx.send(msg) {
-                                            Ok(_) => {},
-                                            Err(_) => {
-                                                let _ = clients.remove(&client_id);
-                                            },
-                                        }
-                                    },
-                                    None => {},
-                                },
-                                NetworkServerEvent::Request(..) => continue,
-                            }
-                        }
-                    },
-                    server_msg = server_rx.recv() => {
-                        match server_msg {
-                            Some((client_id, msg)) => {
-                                server.send(client_id, msg);
-                            },
-                            None => break,
-                        }
-                    },
-                }
+        }
+        assert_eq!(base.resources, expected);
+        handed_out += expected.iter().sum::<usize>();
+    }
+    assert_eq!(game.bank.iter().sum::<usize>(), 19 * 5 - handed_out);
+
+    // two seats sharing a start
+    let mut game = catan_state(CatanDataSetup::Beginner(true), 3, CatanRules::default());
+    game.starts[1] = game.starts[0].clone();
+    game.begin()
+        .expect_err("the second seat builds on the first");
+}
+
+#[cfg(feature = "network")]
+#[tokio::test]
+async fn test_server() {
+    let mut registry = GameRegistry::new();
+    registry.register::<CatanGame>(CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Balanced,
+    });
+    serve(registry).await;
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn test_registry() {
+    let mut registry = GameRegistry::new();
+    registry.register::<CatanGame>(CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Balanced,
+    });
+    assert_eq!(registry.games(), vec!["catan"]);
+    assert_eq!(registry.players("catan"), Some(2..=4));
+    assert_eq!(registry.players("snake"), None);
+    assert_eq!(registry.seats("catan"), Some(2));
+    assert!(registry.set_seats("catan", 5).is_err());
+    assert!(registry.set_seats("snake", 2).is_err());
+    registry.set_seats("catan", 4).unwrap();
+    assert_eq!(registry.seats("catan"), Some(4));
+    assert!(registry.launch("snake", None, Vec::new()).is_err());
+    // too few players never reach the runtime
+    assert!(registry.launch("catan", None, Vec::new()).is_err());
+
+    registry.check_preset("catan", "quick").unwrap();
+    assert!(registry.check_preset("catan", "unknown").is_err());
+    assert!(registry.check_preset("snake", "quick").is_err());
+    assert!(registry
+        .launch("catan", Some("unknown"), Vec::new())
+        .is_err());
+    let config = CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Beginner,
+    };
+    let quick = <CatanGame as NetworkGame>::preset(&config, "quick");
+    assert_eq!(Some(quick.rules), CatanRules::preset("quick"));
+    assert!(matches!(quick.board, CatanBoard::Beginner));
+}
+
+struct SnakeBot {
+    actions: VecDeque<SnakeAct>,
+    messages: Vec<SnakeMsg>,
+}
+
+impl GamePlayer<SnakeGame> for SnakeBot {
+    fn get_name(&self) -> String {
+        "bot".to_owned()
+    }
+
+    async fn get_action(&mut self) -> Option<SnakeAct> {
+        match self.actions.pop_front() {
+            Some(action) => Some(action),
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn send_message(&mut self, message: SnakeMsg) {
+        self.messages.push(message);
+    }
+}
+
+fn snake(body: &[(usize, usize)], direction: Direction) -> Snake {
+    Snake {
+        body: body.iter().map(|(x, y)| Coordinate::new(*x, *y)).collect(),
+        direction,
+        alive: true,
+    }
+}
+
+#[test]
+fn test_snake_tick() {
+    let mut rng = rand::thread_rng();
+    let mut board = SnakeBoard {
+        width: 8,
+        height: 8,
+        tick: 0,
+        snakes: vec![
+            snake(&[(2, 2), (1, 2), (0, 2)], Direction::Right),
+            snake(&[(6, 6), (6, 7)], Direction::Down),
+        ],
+        food: vec![Coordinate::new(3, 2)],
+    };
+
+    // turning back is ignored, the food makes the first snake grow
+    let mut turns = vec![Some(Direction::Left), None];
+    advance(&mut board, &mut turns, 1, &mut rng);
+    assert_eq!(turns, vec![None, None]);
+    assert_eq!(board.tick, 1);
+    assert_eq!(
+        board.snakes[0],
+        snake(&[(3, 2), (2, 2), (1, 2), (0, 2)], Direction::Right)
+    );
+    assert_eq!(board.snakes[1], snake(&[(6, 5), (6, 6)], Direction::Down));
+    assert_eq!(board.food.len(), 2);
+    assert!(!board.food.contains(&Coordinate::new(3, 2)));
+
+    // running into the wall
+    board.food.clear();
+    board.snakes[1] = snake(&[(7, 5), (6, 5)], Direction::Right);
+    advance(&mut board, &mut [None, None], 0, &mut rng);
+    assert!(board.snakes[0].alive);
+    assert!(!board.snakes[1].alive);
+    assert_eq!(
+        board.snakes[1].body,
+        vec![Coordinate::new(7, 5), Coordinate::new(6, 5)]
+    );
+
+    // dead snakes are no obstacle
+    board.snakes[0] = snake(&[(5, 5), (4, 5)], Direction::Right);
+    advance(&mut board, &mut [None, None], 0, &mut rng);
+    assert_eq!(board.snakes[0].head(), Coordinate::new(6, 5));
+}
+
+#[test]
+fn test_snake_collisions() {
+    let mut rng = rand::thread_rng();
+    let mut board = SnakeBoard {
+        width: 8,
+        height: 8,
+        tick: 0,
+        snakes: vec![
+            // head to head on (3, 1)
+            snake(&[(2, 1), (1, 1)], Direction::Right),
+            snake(&[(4, 1), (5, 1)], Direction::Left),
+            // into the body of the fourth snake
+            snake(&[(3, 5), (2, 5)], Direction::Right),
+            snake(&[(4, 4), (4, 5), (4, 6)], Direction::Down),
+        ],
+        food: Vec::new(),
+    };
+    advance(&mut board, &mut [None, None, None, None], 0, &mut rng);
+    assert_eq!(
+        board.snakes.iter().map(|s| s.alive).collect::<Vec<_>>(),
+        vec![false, false, false, true]
+    );
+
+    // chasing a tail is fine as long as the tail moves on
+    let mut board = SnakeBoard {
+        width: 8,
+        height: 8,
+        tick: 0,
+        snakes: vec![snake(&[(2, 2), (2, 3), (3, 3), (3, 2)], Direction::Right)],
+        food: Vec::new(),
+    };
+    advance(&mut board, &mut [None], 0, &mut rng);
+    assert!(board.snakes[0].alive);
+    board.snakes[0] = snake(&[(3, 2), (2, 2), (2, 3), (3, 3), (4, 3)], Direction::Up);
+    advance(&mut board, &mut [None], 0, &mut rng);
+    assert!(!board.snakes[0].alive);
+}
+
+#[tokio::test]
+async fn test_snake_game() {
+    let config = SnakeConfig {
+        tick: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let board = new_board(&config, 2);
+    assert_eq!(board.snakes[0].head(), Coordinate::new(3, 6));
+    assert_eq!(board.snakes[1].head(), Coordinate::new(16, 13));
+
+    // the second snake heads for the nearer wall
+    let players = vec![
+        SnakeBot {
+            actions: VecDeque::new(),
+            messages: Vec::new(),
+        },
+        SnakeBot {
+            actions: vec![SnakeAct::Turn(Direction::Up)].into(),
+            messages: Vec::new(),
+        },
+    ];
+    let mut game = SnakeArena::new(players, config);
+    let outcome = game.run().await;
+    assert_eq!(outcome.winner, Some(0));
+    assert!(!game.board.snakes[1].alive);
+
+    for (i, player) in game.players.iter().enumerate() {
+        assert!(matches!(player.messages[0], SnakeMsg::GameStart(seat, _) if seat == i));
+        assert_eq!(player.messages.last(), Some(&SnakeMsg::GameOver(Some(0))));
+        assert_eq!(
+            player.messages[player.messages.len() - 2],
+            SnakeMsg::Board(game.board.clone())
+        );
+    }
+}
+
+#[test]
+fn test_bot_setup_refused() {
+    let mut state = catan_state(CatanDataSetup::Basic, 2, CatanRules::default());
+    let mut bots = [CatanBot::default(), CatanBot::default()];
+    for (i, bot) in bots.iter_mut().enumerate() {
+        bot.react(&state.game_start(i));
+    }
+    let mut msgs = state.begin().unwrap();
+    let first = state.expects()[0];
+
+    // the spot the first bot wants is taken without it hearing about it
+    let mut probe = CatanBot::default();
+    probe.react(&state.game_start(first));
+    let GameAct::BuildSettlement(wanted) = probe.react(&GameMsg::PlayerInit(first))[0]
+    else {
+        panic!("the bot did not start with a settlement");
+    };
+    state.inner.add_settlement(1 - first, wanted);
+
+    let mut actions = VecDeque::new();
+    for _ in 0..100 {
+        for msg in msgs.drain(..) {
+            for (i, bot) in bots.iter_mut().enumerate() {
+                actions.extend(bot.react(&msg.seen_by(i)).into_iter().map(|a| (i, a)));
             }
-            let _ = close_tx.send(());
-        });
-        let _ = close_rx.await;
+        }
+        let Some((player, action)) = actions.pop_front() else {
+            break;
+        };
+        match state.apply(player, action) {
+            Ok(applied) => msgs = applied,
+            // asked again, the bot has to pick another spot
+            Err(_) => msgs.extend(state.prompt(player)),
+        }
+    }
+    assert!(!matches!(state.phase, Phase::Setup(_)));
+    assert_eq!(state.inner.point(wanted).owner(), Some(1 - first));
+    for player in &state.players {
+        assert_eq!(player.base.roads.len(), 2);
     }
 }
+
+#[tokio::test]
+async fn test_local_bots() {
+    // two seats need the setup placement, more would get the map starts
+    let (first, mut watched) = local_seat::<CatanGame>("watched");
+    let (second, seat) = local_seat::<CatanGame>("bot");
+    tokio::spawn(CatanBot::default().play(seat));
+    let config = CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Balanced,
+    };
+    let game = tokio::spawn(CatanGame::run(vec![first, second], config));
+
+    // the watched seat is a bot as well, the test only peeks at its messages
+    let mut bot = CatanBot::default();
+    let mut settlements = 0;
+    let mut turns = 0;
+    let played = tokio::time::timeout(Duration::from_secs(10), async {
+        while turns < 40 {
+            let msg = watched.messages.recv().await.unwrap();
+            match msg {
+                GameMsg::PlayerBuildSettlement(_) => settlements += 1,
+                GameMsg::PlayerEndTurn(_) => turns += 1,
+                _ => {},
+            }
+            for action in bot.react(&msg) {
+                watched.actions.send(action).unwrap();
+            }
+        }
+    })
+    .await;
+    assert!(played.is_ok(), "the bots got stuck after {} turns", turns);
+    assert_eq!(settlements, 4);
+    game.abort();
+}
+
+#[tokio::test]
+async fn test_player_left() {
+    let (first, mut seat) = local_seat::<CatanGame>("stays");
+    let (second, _) = local_seat::<CatanGame>("leaves");
+    let config = CatanConfig {
+        rules: CatanRules::default(),
+        board: CatanBoard::Balanced,
+    };
+    let outcome = tokio::time::timeout(
+        Duration::from_secs(1),
+        CatanGame::run(vec![first, second], config),
+    )
+    .await
+    .expect("the game waited for a player that left");
+    assert_eq!(outcome.winner, None);
+    assert_eq!(outcome.scores.len(), 2);
+
+    // the game is gone along with the seat it kept
+    while seat.messages.recv().await.is_some() {}
+    assert!(seat.actions.send(GameAct::Resync).is_err());
+}